@@ -0,0 +1,27 @@
+#include <stdio.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+void in_child(int n) {
+    printf("child %d\n", n);
+}
+
+void in_exec(int argc) {
+    printf("exec'd with %d arguments\n", argc);
+}
+
+int main(int argc, char *argv[]) {
+    if (argc > 1) {
+        in_exec(argc);
+        return 0;
+    }
+    pid_t pid = fork();
+    if (pid == 0) {
+        in_child(7);
+        execl("/proc/self/exe", argv[0], "again", NULL);
+        return 1;
+    }
+    waitpid(pid, NULL, 0);
+    printf("parent done\n");
+    return 0;
+}
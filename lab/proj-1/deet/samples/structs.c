@@ -0,0 +1,23 @@
+#include <stdio.h>
+typedef unsigned long ulong_t;
+enum color { RED, GREEN = 5, BLUE };
+struct point { int x; int y; };
+struct node { int value; struct node *next; };
+typedef struct { const char *name; struct point pos; enum color c; int scores[3]; } entity;
+union num { int i; float f; };
+volatile int counter = 3;
+int grid[2][3] = {{1, 2, 3}, {4, 5, 6}};
+int main() {
+    struct node n2 = { 2, NULL };
+    struct node n1 = { 1, &n2 };
+    struct node *head = &n1;
+    entity e = { "player", { 3, 4 }, GREEN, { 10, 20, 30 } };
+    entity *ep = &e;
+    char buf[8] = "hi";
+    ulong_t big = 99;
+    union num u; u.i = 1;
+    void (*fp)(void) = 0;
+    const char *const cp = "x";
+    printf("%d %s %lu %d %p %s\n", head->value, buf, big, u.i, fp, cp);
+    return 0;
+}
@@ -0,0 +1,29 @@
+#include <pthread.h>
+#include <stdio.h>
+
+int total = 0;
+pthread_mutex_t lock = PTHREAD_MUTEX_INITIALIZER;
+pthread_barrier_t started;
+
+void *work(void *arg) {
+    int id = *(int *)arg;
+    pthread_barrier_wait(&started);
+    pthread_mutex_lock(&lock);
+    total += id;
+    pthread_mutex_unlock(&lock);
+    return NULL;
+}
+
+int main() {
+    pthread_t threads[2];
+    int ids[2] = {1, 2};
+    pthread_barrier_init(&started, NULL, 2);
+    for (int i = 0; i < 2; i++) {
+        pthread_create(&threads[i], NULL, work, &ids[i]);
+    }
+    for (int i = 0; i < 2; i++) {
+        pthread_join(threads[i], NULL);
+    }
+    printf("total = %d\n", total);
+    return 0;
+}
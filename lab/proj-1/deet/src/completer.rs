@@ -0,0 +1,97 @@
+use crate::dwarf_data::DwarfData;
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+/// Tab-completes file and function names in command arguments, e.g. for "break foo.c:help".
+pub struct LocationCompleter {
+    /// Compilation unit names, with the functions defined in each.
+    files: Vec<(String, Vec<String>)>,
+}
+
+impl LocationCompleter {
+    pub fn new(debug_data: &DwarfData) -> LocationCompleter {
+        LocationCompleter {
+            files: debug_data
+                .get_files()
+                .iter()
+                .map(|file| {
+                    let functions = file
+                        .functions
+                        .iter()
+                        .filter(|func| func.text_length > 0)
+                        .map(|func| func.name.clone())
+                        .collect();
+                    (file.name.clone(), functions)
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the possible completions of a partially typed location.
+    fn candidates(&self, word: &str) -> Vec<String> {
+        let mut candidates: Vec<String> = Vec::new();
+        if let Some(colon) = word.rfind(':') {
+            let (file, func) = (&word[..colon], &word[colon + 1..]);
+            for (name, functions) in &self.files {
+                if name == file || name.ends_with(&format!("/{}", file)) {
+                    candidates.extend(
+                        functions
+                            .iter()
+                            .filter(|f| f.starts_with(func))
+                            .map(|f| format!("{}:{}", file, f)),
+                    );
+                }
+            }
+        } else {
+            for (name, functions) in &self.files {
+                let base_name = name.rsplit('/').next().unwrap();
+                for file in &[name.as_str(), base_name] {
+                    if file.starts_with(word) {
+                        candidates.push(format!("{}:", file));
+                    }
+                }
+                candidates.extend(functions.iter().filter(|f| f.starts_with(word)).cloned());
+            }
+        }
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+}
+
+impl Completer for LocationCompleter {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos].rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        // The first word is the command itself
+        if start == 0 {
+            return Ok((0, Vec::new()));
+        }
+        let pairs = self
+            .candidates(&line[start..pos])
+            .into_iter()
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: candidate,
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for LocationCompleter {}
+
+impl Highlighter for LocationCompleter {}
+
+impl Validator for LocationCompleter {}
+
+impl Helper for LocationCompleter {}
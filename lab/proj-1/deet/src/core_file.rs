@@ -0,0 +1,402 @@
+use crate::debugger::Breakpoint;
+use crate::inferior::Inferior;
+use crate::target::Target;
+use nix::errno::Errno;
+use nix::sys::ptrace;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem::size_of;
+
+const ET_CORE: u16 = 4;
+const EM_X86_64: u16 = 62;
+const ELF_HEADER_SIZE: usize = 64;
+const PROGRAM_HEADER_SIZE: usize = 56;
+
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+const NT_PRSTATUS: u32 = 1;
+const NT_FILE: u32 = 0x4649_4c45;
+const PAGE_SIZE: usize = 4096;
+
+/// Layout of struct elf_prstatus on x86-64.
+const PRSTATUS_SIZE: usize = 336;
+const PRSTATUS_SIGNO: usize = 0;
+const PRSTATUS_CURSIG: usize = 12;
+const PRSTATUS_PID: usize = 32;
+const PRSTATUS_REGS: usize = 112;
+
+/// A PT_LOAD segment: memory of the process that the core file may contain a copy of.
+struct Segment {
+    addr: usize,
+    mem_size: usize,
+    /// Where the dumped bytes are in the core file. Only the first file_size bytes are dumped.
+    offset: usize,
+    file_size: usize,
+    flags: u32,
+}
+
+/// A file mapped into the process, from the NT_FILE note.
+struct MappedFile {
+    start: usize,
+    end: usize,
+    /// Offset in the file that start corresponds to.
+    offset: usize,
+    path: String,
+}
+
+/// A core dump of a crashed or gcore'd process, which can be inspected like a stopped inferior
+/// but not resumed or modified.
+pub struct CoreFile {
+    data: Vec<u8>,
+    segments: Vec<Segment>,
+    files: Vec<MappedFile>,
+    registers: libc::user_regs_struct,
+    signal: i32,
+    pid: i32,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<usize> {
+    Some(u64::from_le_bytes(data.get(offset..offset + 8)?.try_into().ok()?) as usize)
+}
+
+/// Rounds n up to a multiple of 4, the alignment of note names and descriptors.
+fn note_align(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+impl CoreFile {
+    /// Loads the core dump at path.
+    pub fn open(path: &str) -> Result<CoreFile, String> {
+        let data = fs::read(path).map_err(|err| err.to_string())?;
+        if data.get(..4) != Some(b"\x7fELF") || data.get(4) != Some(&2) {
+            return Err("not a 64-bit ELF file".to_string());
+        }
+        if read_u16(&data, 16) != Some(ET_CORE) {
+            return Err("not a core dump".to_string());
+        }
+        let truncated = || "file is truncated".to_string();
+        let phoff = read_u64(&data, 32).ok_or_else(truncated)?;
+        let phnum = read_u16(&data, 56).ok_or_else(truncated)? as usize;
+
+        let mut core = CoreFile {
+            data: Vec::new(),
+            segments: Vec::new(),
+            files: Vec::new(),
+            registers: unsafe { std::mem::zeroed() },
+            signal: 0,
+            pid: 0,
+        };
+        let mut found_registers = false;
+        for index in 0..phnum {
+            let header = phoff + index * PROGRAM_HEADER_SIZE;
+            let p_type = read_u32(&data, header).ok_or_else(truncated)?;
+            let flags = read_u32(&data, header + 4).ok_or_else(truncated)?;
+            let offset = read_u64(&data, header + 8).ok_or_else(truncated)?;
+            let addr = read_u64(&data, header + 16).ok_or_else(truncated)?;
+            let file_size = read_u64(&data, header + 32).ok_or_else(truncated)?;
+            let mem_size = read_u64(&data, header + 40).ok_or_else(truncated)?;
+            match p_type {
+                PT_LOAD => core.segments.push(Segment {
+                    addr,
+                    mem_size,
+                    offset,
+                    file_size,
+                    flags,
+                }),
+                PT_NOTE => {
+                    let notes = data.get(offset..offset + file_size).ok_or_else(truncated)?;
+                    found_registers |= core.read_notes(notes).ok_or_else(truncated)?;
+                }
+                _ => {}
+            }
+        }
+        if !found_registers {
+            return Err("no NT_PRSTATUS note with the registers".to_string());
+        }
+        core.data = data;
+        Ok(core)
+    }
+
+    /// Reads the process status and file mappings out of a PT_NOTE segment. Returns whether
+    /// registers were found.
+    fn read_notes(&mut self, notes: &[u8]) -> Option<bool> {
+        let mut found_registers = false;
+        let mut offset = 0;
+        while offset < notes.len() {
+            let name_size = read_u32(notes, offset)? as usize;
+            let desc_size = read_u32(notes, offset + 4)? as usize;
+            let note_type = read_u32(notes, offset + 8)?;
+            let desc_start = offset + 12 + note_align(name_size);
+            let desc = notes.get(desc_start..desc_start + desc_size)?;
+            offset = desc_start + note_align(desc_size);
+            match note_type {
+                // there is one per thread, and the first is the thread that caused the dump
+                NT_PRSTATUS if !found_registers && desc.len() >= PRSTATUS_SIZE => {
+                    found_registers = true;
+                    self.signal = read_u16(desc, PRSTATUS_CURSIG)? as i32;
+                    self.pid = read_u32(desc, PRSTATUS_PID)? as i32;
+                    let regs = &desc[PRSTATUS_REGS..PRSTATUS_REGS + size_of::<libc::user_regs_struct>()];
+                    self.registers = unsafe {
+                        std::ptr::read_unaligned(regs.as_ptr() as *const libc::user_regs_struct)
+                    };
+                }
+                NT_FILE => self.files = read_file_note(desc)?,
+                _ => {}
+            }
+        }
+        Some(found_registers)
+    }
+
+    /// Returns the signal that caused the dump.
+    pub fn signal(&self) -> i32 {
+        self.signal
+    }
+
+    /// Returns the pid of the process the core was dumped from.
+    pub fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    /// Reads as many bytes as possible, up to len, from the one place that holds addr.
+    fn read_chunk(&self, addr: usize, len: usize) -> Option<Vec<u8>> {
+        let dumped = self
+            .segments
+            .iter()
+            .find(|segment| segment.addr <= addr && addr < segment.addr + segment.file_size);
+        if let Some(segment) = dumped {
+            let start = segment.offset + addr - segment.addr;
+            let len = len.min(segment.addr + segment.file_size - addr);
+            return self.data.get(start..start + len).map(|bytes| bytes.to_vec());
+        }
+        // Memory mapped from a file, such as code, usually isn't dumped, so read it from the file
+        let file = self.files.iter().find(|file| file.start <= addr && addr < file.end)?;
+        let mut bytes = vec![0; len.min(file.end - addr)];
+        let mut opened = fs::File::open(&file.path).ok()?;
+        opened
+            .seek(SeekFrom::Start((file.offset + addr - file.start) as u64))
+            .ok()?;
+        opened.read_exact(&mut bytes).ok()?;
+        Some(bytes)
+    }
+}
+
+/// Parses an NT_FILE note: a count and page size, then (start, end, page offset) for each
+/// mapping, then the NUL-terminated file names.
+fn read_file_note(desc: &[u8]) -> Option<Vec<MappedFile>> {
+    let count = read_u64(desc, 0)?;
+    let page_size = read_u64(desc, 8)?;
+    let names_start = 16 + count * 24;
+    let mut names = desc.get(names_start..)?.split(|byte| *byte == 0);
+    (0..count)
+        .map(|index| {
+            let entry = 16 + index * 24;
+            Some(MappedFile {
+                start: read_u64(desc, entry)?,
+                end: read_u64(desc, entry + 8)?,
+                offset: read_u64(desc, entry + 16)? * page_size,
+                path: String::from_utf8_lossy(names.next()?).to_string(),
+            })
+        })
+        .collect()
+}
+
+impl Target for CoreFile {
+    fn registers(&self) -> Result<libc::user_regs_struct, nix::Error> {
+        Ok(self.registers)
+    }
+
+    fn read_bytes(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error> {
+        let mut bytes = Vec::with_capacity(len);
+        while bytes.len() < len {
+            let chunk = self
+                .read_chunk(addr + bytes.len(), len - bytes.len())
+                .ok_or(nix::Error::Sys(Errno::EIO))?;
+            bytes.extend(chunk);
+        }
+        Ok(bytes)
+    }
+
+    fn memory_map(&self) -> String {
+        let mut maps = String::new();
+        for file in &self.files {
+            let flags = self
+                .segments
+                .iter()
+                .find(|segment| segment.addr <= file.start && file.start < segment.addr + segment.mem_size)
+                .map_or(PF_R, |segment| segment.flags);
+            maps += &format!(
+                "{:x}-{:x} {}{}{}p {:08x} 00:00 0 {}\n",
+                file.start,
+                file.end,
+                if flags & PF_R != 0 { 'r' } else { '-' },
+                if flags & PF_W != 0 { 'w' } else { '-' },
+                if flags & PF_X != 0 { 'x' } else { '-' },
+                file.offset,
+                file.path
+            );
+        }
+        maps
+    }
+}
+
+/// Appends a note with the name "CORE" to notes.
+fn push_note(notes: &mut Vec<u8>, note_type: u32, desc: &[u8]) {
+    let name = b"CORE\0";
+    notes.extend(&(name.len() as u32).to_le_bytes());
+    notes.extend(&(desc.len() as u32).to_le_bytes());
+    notes.extend(&note_type.to_le_bytes());
+    notes.extend(name);
+    notes.resize(note_align(notes.len()), 0);
+    notes.extend(desc);
+    notes.resize(note_align(notes.len()), 0);
+}
+
+fn push_program_header(
+    headers: &mut Vec<u8>,
+    p_type: u32,
+    flags: u32,
+    offset: usize,
+    addr: usize,
+    file_size: usize,
+    mem_size: usize,
+) {
+    headers.extend(&p_type.to_le_bytes());
+    headers.extend(&flags.to_le_bytes());
+    let align = if p_type == PT_NOTE { 4 } else { PAGE_SIZE };
+    for value in &[offset, addr, addr, file_size, mem_size, align] {
+        headers.extend(&(*value as u64).to_le_bytes());
+    }
+}
+
+/// Writes a core dump of the stopped inferior to path, in the same format the kernel uses, so
+/// that it can be loaded again with --core.
+pub fn write_core(
+    inferior: &Inferior,
+    breakpoints: &HashMap<usize, Option<Breakpoint>>,
+    path: &str,
+) -> io::Result<()> {
+    let to_io_error = |err: nix::Error| io::Error::new(io::ErrorKind::Other, err);
+    let pid = inferior.pid();
+    let registers = inferior.registers().map_err(to_io_error)?;
+    let signal = ptrace::getsiginfo(inferior.tid()).map_or(0, |info| info.si_signo);
+
+    // (start, end, flags, file offset, path) of each mapping that can be read
+    let mut mappings = Vec::new();
+    for line in inferior.memory_map().lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let range: Vec<usize> = fields[0]
+            .split('-')
+            .filter_map(|n| usize::from_str_radix(n, 16).ok())
+            .collect();
+        let perms = fields[1].as_bytes();
+        // [vsyscall] is outside the process's address space proper and can't be read
+        if range.len() != 2 || perms[0] != b'r' || fields.get(5) == Some(&"[vsyscall]") {
+            continue;
+        }
+        let flags = PF_R
+            | if perms[1] == b'w' { PF_W } else { 0 }
+            | if perms[2] == b'x' { PF_X } else { 0 };
+        let offset = usize::from_str_radix(fields[3], 16).unwrap_or(0);
+        mappings.push((range[0], range[1], flags, offset, fields.get(5).map(|s| s.to_string())));
+    }
+
+    let mut prstatus = vec![0u8; PRSTATUS_SIZE];
+    prstatus[PRSTATUS_SIGNO..PRSTATUS_SIGNO + 4].copy_from_slice(&signal.to_le_bytes());
+    prstatus[PRSTATUS_CURSIG..PRSTATUS_CURSIG + 2].copy_from_slice(&(signal as u16).to_le_bytes());
+    prstatus[PRSTATUS_PID..PRSTATUS_PID + 4].copy_from_slice(&pid.as_raw().to_le_bytes());
+    let register_bytes = unsafe {
+        std::slice::from_raw_parts(
+            &registers as *const libc::user_regs_struct as *const u8,
+            size_of::<libc::user_regs_struct>(),
+        )
+    };
+    prstatus[PRSTATUS_REGS..PRSTATUS_REGS + register_bytes.len()].copy_from_slice(register_bytes);
+
+    let files: Vec<_> = mappings
+        .iter()
+        .filter(|mapping| mapping.4.as_ref().map_or(false, |path| path.starts_with('/')))
+        .collect();
+    let mut file_note = Vec::new();
+    file_note.extend(&(files.len() as u64).to_le_bytes());
+    file_note.extend(&(PAGE_SIZE as u64).to_le_bytes());
+    for (start, end, _, offset, _) in &files {
+        for value in &[*start, *end, *offset / PAGE_SIZE] {
+            file_note.extend(&(*value as u64).to_le_bytes());
+        }
+    }
+    for (_, _, _, _, path) in &files {
+        file_note.extend(path.as_ref().unwrap().as_bytes());
+        file_note.push(0);
+    }
+
+    let mut notes = Vec::new();
+    push_note(&mut notes, NT_PRSTATUS, &prstatus);
+    push_note(&mut notes, NT_FILE, &file_note);
+
+    // Read all the memory up front; regions that can't be read, like [vvar], are left out
+    let mut memory = fs::File::open(format!("/proc/{}/mem", pid))?;
+    let contents: Vec<Vec<u8>> = mappings
+        .iter()
+        .map(|(start, end, _, _, _)| {
+            let mut bytes = vec![0; end - start];
+            if memory
+                .seek(SeekFrom::Start(*start as u64))
+                .and_then(|_| memory.read_exact(&mut bytes))
+                .is_err()
+            {
+                return Vec::new();
+            }
+            // the dump should show the program's code, not our 0xcc bytes
+            for bp in breakpoints.values().flatten() {
+                if *start <= bp.addr && bp.addr < *end {
+                    bytes[bp.addr - start] = bp.orig_byte;
+                }
+            }
+            bytes
+        })
+        .collect();
+
+    let phnum = mappings.len() + 1;
+    let notes_offset = ELF_HEADER_SIZE + phnum * PROGRAM_HEADER_SIZE;
+    let mut headers = Vec::new();
+    push_program_header(&mut headers, PT_NOTE, 0, notes_offset, 0, notes.len(), 0);
+    let mut offset = notes_offset + notes.len();
+    for ((start, end, flags, _, _), bytes) in mappings.iter().zip(&contents) {
+        push_program_header(&mut headers, PT_LOAD, *flags, offset, *start, bytes.len(), end - start);
+        offset += bytes.len();
+    }
+
+    let mut elf_header = vec![0u8; ELF_HEADER_SIZE];
+    // 64-bit, little endian, version 1
+    elf_header[..7].copy_from_slice(b"\x7fELF\x02\x01\x01");
+    elf_header[16..18].copy_from_slice(&ET_CORE.to_le_bytes());
+    elf_header[18..20].copy_from_slice(&EM_X86_64.to_le_bytes());
+    elf_header[20..24].copy_from_slice(&1u32.to_le_bytes());
+    elf_header[32..40].copy_from_slice(&(ELF_HEADER_SIZE as u64).to_le_bytes());
+    elf_header[52..54].copy_from_slice(&(ELF_HEADER_SIZE as u16).to_le_bytes());
+    elf_header[54..56].copy_from_slice(&(PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+    elf_header[56..58].copy_from_slice(&(phnum as u16).to_le_bytes());
+
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    out.write_all(&elf_header)?;
+    out.write_all(&headers)?;
+    out.write_all(&notes)?;
+    for bytes in &contents {
+        out.write_all(bytes)?;
+    }
+    out.flush()
+}
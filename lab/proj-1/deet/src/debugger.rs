@@ -1,225 +1,2872 @@
-use std::borrow::Borrow;
-
-use crate::debugger_command::DebuggerCommand;
-use crate::inferior::{Inferior, Status};
+use crate::completer::LocationCompleter;
+use crate::core_file::{self, CoreFile};
+use crate::debugger_command::{DebuggerCommand, ExamineFormat};
+use crate::disasm::{self, Instruction};
+use crate::expr::{self, Evaluator, Expr, FunctionCall, Value, ValueLocation};
+use crate::inferior::{ForkPolicy, Inferior, LineStep, Status};
+use crate::registers;
+use crate::signals::{self, SignalTable};
+use crate::solib;
+use crate::syscalls::{self, SyscallStop};
+use crate::source::SourceCache;
+use crate::target::{NoProcess, Target};
+use nix::sys::ptrace;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
-use crate::dwarf_data::{DwarfData, Error as DwarfError};
-use std::collections::HashMap;
+use crate::events::{self, Event};
+use crate::json::Json;
+use crate::dwarf_data::{DwarfData, Error as DwarfError, Function, Type};
+use crate::unwind::{self, Frame, FrameRegisters, Unwinder};
+use crate::value;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::time::Instant;
 
+/// A 0xcc byte written into the inferior. There is one per address that has an enabled
+/// breakpoint, no matter how many breakpoints the user has set there.
+#[derive(Clone)]
 pub struct Breakpoint {
     pub addr: usize,
     pub orig_byte: u8,
 }
 
+/// A breakpoint as the user sees it.
+pub struct BreakpointInfo {
+    /// Where the user asked for the breakpoint, so it can be resolved again for a new program.
+    pub location: String,
+    pub addr: usize,
+    pub enabled: bool,
+    /// Only stop if this condition holds when the breakpoint is hit.
+    pub condition: Option<String>,
+    /// Number of upcoming hits to continue through without stopping.
+    pub ignore_count: usize,
+    pub hit_count: usize,
+    /// Commands to run when the breakpoint stops the inferior.
+    pub commands: Vec<String>,
+    /// For a logpoint, the message logged each time it is hit instead of stopping, with the
+    /// expressions to fill in between braces.
+    pub message: Option<String>,
+}
+
+/// Number of source lines shown before and after the current line when the program stops.
+const CONTEXT_LINES: usize = 2;
+/// Number of source lines list shows at a time.
+const LIST_LINES: usize = 10;
+
+/// Where separate debug files are looked for unless set debug-file-directory says otherwise.
+const DEFAULT_DEBUG_FILE_DIRECTORY: &str = "/usr/lib/debug";
+
+/// Number of debug registers x86-64 has for watchpoint addresses.
+const NUM_WATCH_SLOTS: usize = 4;
+
+pub struct Watchpoint {
+    pub id: usize,
+    pub expr: String,
+    pub addr: usize,
+    pub len: usize,
+    /// Whether reads trigger the watchpoint as well as writes.
+    pub read: bool,
+    pub enabled: bool,
+    pub value_type: Type,
+    pub old_value: Vec<u8>,
+    /// The debug register slot used for each aligned chunk of the watched memory.
+    pub slots: Vec<usize>,
+}
+
+/// Stops the inferior at the entry and exit of syscalls.
+pub struct Catchpoint {
+    pub id: usize,
+    /// The syscalls caught, or every syscall if empty.
+    pub syscalls: Vec<usize>,
+    pub enabled: bool,
+    pub hit_count: usize,
+}
+
+/// An expression shown every time the program stops.
+pub struct AutoDisplay {
+    pub id: usize,
+    /// How to examine the memory the expression points to, or None to print its value.
+    pub format: Option<ExamineFormat>,
+    pub expr: String,
+}
+
 pub struct Debugger {
     target: String,
     history_path: String,
-    readline: Editor<()>,
+    readline: Editor<LocationCompleter>,
     inferior: Option<Inferior>,
     debug_data: DwarfData,
+    /// Installed breakpoints by address. The value is None until there is an inferior to write
+    /// them into.
     breakpoints: HashMap<usize, Option<Breakpoint>>,
+    /// User breakpoints by number.
+    breakpoint_info: BTreeMap<usize, BreakpointInfo>,
+    watchpoints: Vec<Watchpoint>,
+    catchpoints: Vec<Catchpoint>,
+    /// Breakpoints, watchpoints and catchpoints share numbers, which are never reused.
+    next_breakpoint_id: usize,
+    /// The stack frame whose variables print and info locals look at, 0 being the innermost.
+    selected_frame: usize,
+    /// Call frame information for the inferior, along with the memory map it was loaded for.
+    unwinder: Option<(String, Unwinder)>,
+    /// A core dump being examined, when there is no live inferior.
+    core: Option<CoreFile>,
+    displays: Vec<AutoDisplay>,
+    next_display_id: usize,
+    sources: SourceCache,
+    /// The source file list last showed, the first line it showed, and the line a list without
+    /// arguments continues from.
+    list_position: Option<(String, usize, usize)>,
+    /// Values print has shown, which $N refers to.
+    history: Vec<Value>,
+    /// Variables the user has set with "set $name = ...".
+    convenience: HashMap<String, Value>,
+    /// What happens when the inferior receives each signal.
+    signals: SignalTable,
+    fork_policy: ForkPolicy,
+    /// Processes forked off the inferior that deet keeps stopped, by inferior number. They have
+    /// no breakpoints written into them until they are switched to.
+    held_inferiors: Vec<(usize, Inferior)>,
+    /// The number of the current inferior.
+    inferior_number: usize,
+    next_inferior_number: usize,
+    /// Where separate debug files for stripped programs and libraries are looked for.
+    debug_file_directory: String,
+    /// Lines to run before reading from the terminal again: those of scripts, of user-defined
+    /// commands and of breakpoint commands.
+    pending_lines: VecDeque<String>,
+    /// Commands defined with define, by name.
+    user_commands: HashMap<String, Vec<String>>,
+    /// The breakpoints that stopped the inferior, whose commands have yet to be queued.
+    hit_breakpoints: Vec<usize>,
+    /// When the inferior was started or attached to, which logpoint messages are timed from.
+    run_started: Instant,
+    /// How many times each logpoint has been hit since the inferior started.
+    logpoint_hits: HashMap<usize, usize>,
+    /// The file logpoint messages are written to instead of being shown, along with its path.
+    log_file: Option<(String, File)>,
 }
 
 impl Debugger {
     /// Initializes the debugger.
     pub fn new(target: &str) -> Debugger {
-        // TODO (milestone 3): initialize the DwarfData
-        let debug_data = match DwarfData::from_file(target) {
-            Ok(val) => val,
-            Err(DwarfError::ErrorOpeningFile) => {
-                println!("Could not open file {}", target);
-                std::process::exit(1);
-            }
-            Err(DwarfError::DwarfFormatError(err)) => {
-                println!("Could not load debugging symbols from {}: {:?}", target, err);
-                std::process::exit(1);
-            }
+        let debug_data = match load_debug_data(target, DEFAULT_DEBUG_FILE_DIRECTORY) {
+            Some(debug_data) => debug_data,
+            None => std::process::exit(1),
         };
-        debug_data.print();
+        // under --interpreter=json, output only answers requests
+        if !events::is_json() {
+            debug_data.print();
+        }
 
         let history_path = format!("{}/.deet_history", std::env::var("HOME").unwrap());
-        let mut readline = Editor::<()>::new();
+        let mut readline = Editor::<LocationCompleter>::new();
+        readline.set_helper(Some(LocationCompleter::new(&debug_data)));
         // Attempt to load history from ~/.deet_history if it exists
         let _ = readline.load_history(&history_path);
 
-        Debugger {
+        let mut debugger = Debugger {
             target: target.to_string(),
             history_path,
             readline,
             inferior: None,
             debug_data: debug_data,
             breakpoints: HashMap::new(),
+            breakpoint_info: BTreeMap::new(),
+            watchpoints: Vec::new(),
+            catchpoints: Vec::new(),
+            next_breakpoint_id: 1,
+            selected_frame: 0,
+            unwinder: None,
+            core: None,
+            displays: Vec::new(),
+            next_display_id: 1,
+            sources: SourceCache::new(),
+            list_position: None,
+            history: Vec::new(),
+            convenience: HashMap::new(),
+            signals: SignalTable::new(),
+            fork_policy: ForkPolicy {
+                follow_child: false,
+                detach: true,
+            },
+            held_inferiors: Vec::new(),
+            inferior_number: 1,
+            next_inferior_number: 2,
+            debug_file_directory: DEFAULT_DEBUG_FILE_DIRECTORY.to_string(),
+            pending_lines: VecDeque::new(),
+            user_commands: HashMap::new(),
+            hit_breakpoints: Vec::new(),
+            run_started: Instant::now(),
+            logpoint_hits: HashMap::new(),
+            log_file: None,
+        };
+        debugger.load_init_files();
+        debugger
+    }
+
+    /// Queues the commands in ~/.deetinit and then those in ./.deetinit, if they exist.
+    fn load_init_files(&mut self) {
+        let mut paths = Vec::new();
+        if let Ok(home) = std::env::var("HOME") {
+            paths.push(format!("{}/.deetinit", home));
+        }
+        paths.push(".deetinit".to_string());
+        let mut loaded = Vec::new();
+        for path in paths {
+            // don't run the same file twice when deet is started in the home directory
+            match fs::canonicalize(&path) {
+                Ok(canonical) if !loaded.contains(&canonical) => {
+                    loaded.push(canonical);
+                    self.load_script(&path);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Queues the commands in a script to run after any that are already queued.
+    pub fn load_script(&mut self, path: &str) {
+        match read_script(path) {
+            Ok(lines) => self.pending_lines.extend(lines),
+            Err(err) => output!("{}: {}.", path, err),
+        }
+    }
+
+    /// Queues lines to run before any that are already queued.
+    fn run_next(&mut self, lines: Vec<String>) {
+        for line in lines.into_iter().rev() {
+            self.pending_lines.push_front(line);
+        }
+    }
+
+    /// Reads the lines of a define or commands block, up to the "end" that closes it.
+    fn read_block(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut depth = 0;
+        while let Some(line) = self.next_line(">") {
+            match line.split_whitespace().next() {
+                Some("end") if depth == 0 => break,
+                Some("end") => depth -= 1,
+                Some("define") | Some("commands") => depth += 1,
+                _ => {}
+            }
+            lines.push(line.trim().to_string());
         }
+        lines
     }
 
     pub fn run(&mut self) {
         loop {
-            match self.get_next_command() {
+            let command = self.get_next_command();
+            match command {
                 DebuggerCommand::Run(args) => {
-                    if let Some(inferior) = &mut self.inferior {
-                        inferior.kill().expect("inferior.kill wasn't running");
-                    }
-                    if let Some(inferior) = Inferior::new(&self.target, &args) {
+                    self.end_inferior();
+                    self.core = None;
+                    if let Some(mut inferior) = Inferior::new(&self.target, &args) {
                         // Create the inferior
+                        self.run_started = Instant::now();
+                        self.logpoint_hits.clear();
+                        self.configure_inferior(&mut inferior);
                         self.inferior = Some(inferior);
-                        // TODO (milestone 1): make the inferior run
-                        // You may use self.inferior.as_mut().unwrap() to get a mutable reference
-                        // to the Inferior object
-                        match self.inferior.as_mut().unwrap().continue_exec(&mut self.breakpoints) {
-                            Ok(status) => match status {
-                                Status::Exited(exit_status_code) => {
-                                    self.inferior = None;
-                                    println!("Child exited (status {})", exit_status_code);
-                                }
-                                Status::Signaled(signal) => {
-                                    self.inferior = None;
-                                    println!("Chile exited (signal {})", signal);
-                                }
-                                Status::Stopped(signal, rip) => {
-                                    println!("Child stopped (signal {})", signal);
-                                    if let Some(line) = self.debug_data.get_line_from_addr(rip) {
-                                        println!("Stopped at {} : {}", line.file, line.number);
-                                    }
-                                }
-                            },
-                            Err(err) => println!("Inferior can't be woken up and execute: {}", err),
-                        }
+                        self.load_libraries();
+                        self.install_breakpoints();
+                        let result = self.continue_inferior();
+                        self.report_status(result);
                     } else {
-                        println!("Error starting subprocess");
+                        output!("Error starting subprocess");
                     }
                 }
                 DebuggerCommand::Quit => {
-                    if let Some(inferior) = &mut self.inferior {
-                        inferior.kill().expect("inferior.kill wasn't running");
-                    }
+                    self.end_inferior();
                     return;
                 }
-                DebuggerCommand::Continue => {
-                    if let Some(inferior) = &self.inferior {
-                        match self.inferior.as_mut().unwrap().continue_exec(&mut self.breakpoints) {
-                            Ok(status) => match status {
-                                Status::Exited(exit_status_code) => {
-                                    self.inferior = None;
-                                    println!("Child exited (status {})", exit_status_code);
-                                }
-                                Status::Signaled(signal) => {
-                                    self.inferior = None;
-                                    println!("Chile exited (signal {})", signal);
-                                }
-                                Status::Stopped(signal, rip) => {
-                                    println!("Child stopped (signal {})", signal);
-                                    if let Some(line) = self.debug_data.get_line_from_addr(rip) {
-                                        println!("Stopped at {} :{}", line.file, line.number);
-                                    }
-                                }
-                            },
-                            Err(err) => println!("Inferior can't be woken up and execute: {}", err),
+                DebuggerCommand::Examine(format, expr) => {
+                    if self.examined().is_none() {
+                        output!("The program is not being run.");
+                        continue;
+                    }
+                    match self.evaluate_integer(&expr) {
+                        Ok(addr) => self.examine(addr, &format),
+                        Err(err) => output!("{}", err),
+                    }
+                }
+                DebuggerCommand::InfoRegisters(names) => self.print_registers(&names),
+                DebuggerCommand::Disassemble(range) => {
+                    if self.examined().is_none() {
+                        output!("The program is not being run.");
+                        continue;
+                    }
+                    if let Err(err) = self.disassemble(range) {
+                        output!("{}", err);
+                    }
+                }
+                DebuggerCommand::Display(format, Some(expr)) => {
+                    let display = AutoDisplay {
+                        id: self.next_display_id,
+                        format,
+                        expr,
+                    };
+                    self.next_display_id += 1;
+                    if self.examined().is_some() {
+                        self.show_display(&display);
+                    }
+                    self.displays.push(display);
+                }
+                DebuggerCommand::Display(_, None) => {
+                    if self.examined().is_some() {
+                        self.show_displays();
+                    }
+                }
+                DebuggerCommand::List(location) => {
+                    if let Err(err) = self.list(location.as_deref()) {
+                        output!("{}", err);
+                    }
+                }
+                DebuggerCommand::Directory(dir) => {
+                    match dir {
+                        Some(dir) => self.sources.add_directory(&dir),
+                        None => self.sources.clear_directories(),
+                    }
+                    let mut directories = self.sources.directories().to_vec();
+                    directories.push("$cdir".to_string());
+                    output!("Source directories searched: {}", directories.join(":"));
+                }
+                DebuggerCommand::Undisplay(ids) => {
+                    if ids.is_empty() {
+                        self.displays.clear();
+                    }
+                    for id in ids {
+                        match self.displays.iter().position(|display| display.id == id) {
+                            Some(index) => {
+                                self.displays.remove(index);
+                            }
+                            None => output!("No display number {}.", id),
+                        }
+                    }
+                }
+                DebuggerCommand::InfoDisplay => {
+                    if self.displays.is_empty() {
+                        output!("There are no auto-display expressions now.");
+                    } else {
+                        output!("Auto-display expressions now in effect:");
+                        output!("Num Enb Expression");
+                        for display in &self.displays {
+                            let format = display.format.as_ref().map_or(String::new(), |f| f.to_string() + " ");
+                            output!("{}:   y  {}{}", display.id, format, display.expr);
+                        }
+                    }
+                }
+                DebuggerCommand::Set(assignment) => {
+                    let frame = self.selected_frame;
+                    if let Err(err) = self.evaluate(&assignment, frame, |_, _| Ok(())) {
+                        output!("{}", err);
+                    }
+                }
+                DebuggerCommand::Gcore(path) => match &self.inferior {
+                    Some(inferior) => {
+                        let path = path.unwrap_or(format!("core.{}", inferior.pid()));
+                        match core_file::write_core(inferior, &self.breakpoints, &path) {
+                            Ok(()) => output!("Saved corefile {}", path),
+                            Err(err) => output!("Could not write core file {}: {}", path, err),
+                        }
+                    }
+                    None => output!("The program is not being run."),
+                },
+                DebuggerCommand::Attach(pid) => self.attach(Pid::from_raw(pid)),
+                DebuggerCommand::Detach => {
+                    if self.inferior.is_some() {
+                        self.detach();
+                    } else {
+                        output!("The program is not being run.");
+                    }
+                }
+                DebuggerCommand::SetOption(name, value) => {
+                    if let Err(err) = self.set_option(&name, &value) {
+                        output!("{}", err);
+                    }
+                }
+                DebuggerCommand::InfoInferiors => self.print_inferiors(),
+                DebuggerCommand::InfoSharedLibrary => self.print_shared_libraries(),
+                DebuggerCommand::Source(path) => match read_script(&path) {
+                    Ok(lines) => self.run_next(lines),
+                    Err(err) => output!("{}: {}.", path, err),
+                },
+                DebuggerCommand::Define(name) => {
+                    if self.pending_lines.is_empty() {
+                        output!("Type commands for definition of \"{}\".", name);
+                        output!("End with a line saying just \"end\".");
+                    }
+                    let body = self.read_block();
+                    self.user_commands.insert(name, body);
+                }
+                DebuggerCommand::Commands(id) => {
+                    let id = id.unwrap_or(self.next_breakpoint_id - 1);
+                    if self.pending_lines.is_empty() && self.breakpoint_info.contains_key(&id) {
+                        output!("Type commands for breakpoint {}, one per line.", id);
+                        output!("End with a line saying just \"end\".");
+                    }
+                    // the block is read even for a bad breakpoint, so that it isn't run instead
+                    let commands = self.read_block();
+                    match self.breakpoint_info.get_mut(&id) {
+                        Some(bp) => bp.commands = commands,
+                        None => output!("No breakpoint number {}.", id),
+                    }
+                }
+                DebuggerCommand::Inferior(number) => self.switch_inferior(number),
+                DebuggerCommand::InfoThreads => match &self.inferior {
+                    Some(_) => self.print_threads(),
+                    None => output!("No threads."),
+                },
+                DebuggerCommand::Thread(number) => {
+                    let inferior = match &mut self.inferior {
+                        Some(inferior) => inferior,
+                        None => {
+                            output!("No thread selected.");
+                            continue;
+                        }
+                    };
+                    let number = match number {
+                        Some(number) => number,
+                        None => {
+                            let number = inferior.thread_number();
+                            output!("[Current thread is {} (LWP {})]", number, inferior.tid());
+                            continue;
+                        }
+                    };
+                    match inferior.select_thread(number) {
+                        Some(tid) => {
+                            output!("[Switching to thread {} (LWP {})]", number, tid);
+                            self.selected_frame = 0;
+                            if let Ok(frames) = self.stack_frames() {
+                                self.print_frame(0, &frames[0]);
+                                self.show_source_context(frame_pc(0, &frames[0]));
+                            }
+                        }
+                        None => output!("Invalid thread ID: {}", number),
+                    }
+                }
+                DebuggerCommand::Handle(args) => self.handle_signals(&args),
+                DebuggerCommand::InfoSignals(name) => {
+                    let shown: Vec<Signal> = match name {
+                        Some(name) => match signals::parse_signal(&name) {
+                            Some(signal) => vec![signal],
+                            None => {
+                                output!("Unknown signal \"{}\".", name);
+                                continue;
+                            }
+                        },
+                        None => Signal::iterator().collect(),
+                    };
+                    self.signals.print_header();
+                    for signal in shown {
+                        self.signals.print_policy(signal);
+                    }
+                }
+                DebuggerCommand::Signal(name) => {
+                    let signal = match signals::parse_signal(&name) {
+                        Some(signal) => Some(signal),
+                        None if name == "0" => None,
+                        None => {
+                            output!("Unknown signal \"{}\".", name);
+                            continue;
+                        }
+                    };
+                    match &mut self.inferior {
+                        Some(inferior) => {
+                            match signal {
+                                Some(signal) => output!("Continuing with signal {}.", signal),
+                                None => output!("Continuing with no signal."),
+                            }
+                            inferior.set_pending_signal(signal);
+                            let result = self.continue_inferior();
+                            self.report_status(result);
                         }
+                        None => output!("The program is not being run."),
+                    }
+                }
+                DebuggerCommand::Continue => {
+                    if self.inferior.is_some() {
+                        let result = self.continue_inferior();
+                        self.report_status(result);
                     } else {
                         // if there is no inferior stopped, continue fails.
-                        println!("There is no inferior stopped!");
+                        output!("There is no inferior stopped!");
                     }
-                    
                 }
-                DebuggerCommand::Backtrace => {
+                DebuggerCommand::Step | DebuggerCommand::Next => {
+                    let step_into = matches!(command, DebuggerCommand::Step);
                     if let Some(inferior) = &self.inferior {
-                        match inferior.print_backtrace(&self.debug_data) {
-                            Ok(_) => {},
-                            Err(err) => {
-                                println!("Err print_backtrace: {}", err);
-                            },
+                        let result = match inferior.line_step(&self.debug_data, step_into) {
+                            Ok(step) => self.step_line(step),
+                            Err(err) => Err(err),
+                        };
+                        self.report_status(result);
+                    } else {
+                        output!("The program is not being run.");
+                    }
+                }
+                DebuggerCommand::StepInstruction | DebuggerCommand::NextInstruction => {
+                    if self.inferior.is_none() {
+                        output!("The program is not being run.");
+                        continue;
+                    }
+                    let step_over = matches!(command, DebuggerCommand::NextInstruction);
+                    let result = self.step_instruction(step_over);
+                    let stopped = matches!(result, Ok(Status::Stopped(_, _)));
+                    self.report_status(result);
+                    // breakpoint commands run by report_status may have ended the program
+                    if let (true, Some(inferior)) = (stopped, &self.inferior) {
+                        match ptrace::getregs(inferior.tid()) {
+                            Ok(regs) => self.print_instructions(regs.rip as usize, 1),
+                            Err(err) => output!("Could not read registers: {}", err),
                         }
                     }
                 }
-                DebuggerCommand::Breakpoint(bp_target) => {
-                    let addr: usize;
-                    if bp_target.starts_with("*") {
-                        addr = self.parse_address(&bp_target[1..]).unwrap();
-                    } else if let Ok(line_number) = bp_target.parse::<usize>() {
-                        if let Some(address) = self.debug_data.get_addr_for_line(None, line_number) {
-                            addr = address;
-                        } else {
-                            println!("line number can't find the corresponding address");
+                DebuggerCommand::Finish => {
+                    let inferior = match &mut self.inferior {
+                        Some(inferior) => inferior,
+                        None => {
+                            output!("The program is not being run.");
                             continue;
                         }
-                    } else if let Some(address) = self.debug_data.get_addr_for_function(None, &bp_target) {
-                        addr = address;
-                    } else {
-                        println!("{} can't be parsed to a breakpoint target", bp_target);
-                        println!("Usage: b | break | breakpoint *address | line | func");
+                    };
+                    let rip = match ptrace::getregs(inferior.tid()) {
+                        Ok(regs) => regs.rip as usize,
+                        Err(err) => {
+                            output!("Could not read registers: {}", err);
+                            continue;
+                        }
+                    };
+                    let func = self.debug_data.get_function_from_addr(rip);
+                    if func.as_deref() == Some("main") {
+                        output!("\"finish\" not meaningful in the outermost frame.");
                         continue;
                     }
-                    println!("Set breakpoint {} at {}", self.breakpoints.len(), addr);
-                    
-                    // self.breakpoint.push(addr);
-
-                    if let Some(inferior) = &mut self.inferior {
-                        match inferior.write_byte(addr, 0xcc) {
-                            Ok(orig_byte) => {
-                                self.breakpoints.insert(addr, Some(Breakpoint { addr: addr, orig_byte: orig_byte }));
+                    output!("Run till exit from {}", func.unwrap_or("??".to_string()));
+                    let (ret_addr, frame_rsp) = match self.return_address() {
+                        Ok(found) => found,
+                        Err(err) => {
+                            output!("{}", err);
+                            continue;
+                        }
+                    };
+                    let result = self.run_to_return(ret_addr, frame_rsp);
+                    // anything else, such as a breakpoint or a signal, stopped it on the way
+                    let returned = match result {
+                        Ok(Status::Stopped(Signal::SIGTRAP, rip)) => rip == ret_addr,
+                        _ => false,
+                    };
+                    self.report_status(result);
+                    // breakpoint commands run by report_status may have ended the program
+                    if let (true, Some(inferior)) = (returned, &self.inferior) {
+                        match ptrace::getregs(inferior.tid()) {
+                            Ok(regs) => {
+                                output!("Value returned is {} ({:#x})", regs.rax as i64, regs.rax)
                             }
+                            Err(err) => output!("Could not read registers: {}", err),
+                        }
+                    }
+                }
+                DebuggerCommand::Print(expr) => self.print_expression(&expr, true),
+                DebuggerCommand::Call(expr) => self.print_expression(&expr, false),
+                DebuggerCommand::InfoLocals => {
+                    if self.examined().is_some() {
+                        let (rip, frame_base) = match self.frame_context(self.selected_frame) {
+                            Ok(context) => context,
                             Err(err) => {
-                                println!("Debugger::new breakpoint write_byte: {}", err);
+                                output!("{}", err);
+                                continue;
+                            }
+                        };
+                        let inferior = self.examined().unwrap();
+                        match self.debug_data.get_function(rip) {
+                            Some(func) if !func.variables.is_empty() => {
+                                for var in &func.variables {
+                                    let addr = value::variable_address(var, frame_base);
+                                    self.print_value(inferior, &var.name, addr, &var.entity_type);
+                                }
                             }
+                            Some(_) => output!("No locals."),
+                            None => output!("No symbol table info available."),
                         }
                     } else {
-                        self.breakpoints.insert(addr, None);
+                        output!("The program is not being run.");
                     }
                 }
-            }
-        }
-    }
-
-    /// This function prompts the user to enter a command, and continues re-prompting until the user
-    /// enters a valid command. It uses DebuggerCommand::from_tokens to do the command parsing.
-    ///
-    /// You don't need to read, understand, or modify this function.
-    fn get_next_command(&mut self) -> DebuggerCommand {
-        loop {
-            // Print prompt and get next line of user input
-            match self.readline.readline("(deet) ") {
-                Err(ReadlineError::Interrupted) => {
-                    // User pressed ctrl+c. We're going to ignore it
-                    println!("Type \"quit\" to exit");
+                DebuggerCommand::Watch(expr, len, read) => {
+                    if self.inferior.is_some() {
+                        let target = self.evaluate(&expr, self.selected_frame, |_, value| {
+                            match value.location {
+                                ValueLocation::Memory(addr) => Ok((addr, value.value_type)),
+                                _ => Err(format!("Cannot watch constant value `{}'.", expr)),
+                            }
+                        });
+                        let (addr, mut value_type) = match target {
+                            Ok(target) => target,
+                            Err(err) => {
+                                output!("{}", err);
+                                continue;
+                            }
+                        };
+                        if let Some(len) = len {
+                            value_type.size = len;
+                        }
+                        let inferior = self.inferior.as_mut().unwrap();
+                        let used_slots: usize = self.watchpoints.iter().map(|w| w.slots.len()).sum();
+                        let needed_slots = watch_chunks(addr, value_type.size).len();
+                        if value_type.size == 0 || used_slots + needed_slots > NUM_WATCH_SLOTS {
+                            output!(
+                                "Hardware watchpoints used exceeds limit of {} debug registers.",
+                                NUM_WATCH_SLOTS
+                            );
+                            continue;
+                        }
+                        let mut watchpoint = Watchpoint {
+                            id: self.next_breakpoint_id,
+                            expr: expr.clone(),
+                            addr,
+                            len: value_type.size,
+                            read,
+                            enabled: true,
+                            value_type,
+                            old_value: Vec::new(),
+                            slots: Vec::new(),
+                        };
+                        // pick slots that no other watchpoint is using
+                        let watchpoints = &self.watchpoints;
+                        watchpoint.slots = (0..NUM_WATCH_SLOTS)
+                            .filter(|slot| !watchpoints.iter().any(|w| w.slots.contains(slot)))
+                            .take(needed_slots)
+                            .collect();
+                        match install_watchpoint(inferior, &mut watchpoint) {
+                            Ok(()) => {
+                                output!(
+                                    "Hardware {}watchpoint {}: {}",
+                                    if read { "access (read/write) " } else { "" },
+                                    watchpoint.id,
+                                    expr
+                                );
+                                self.next_breakpoint_id += 1;
+                                self.watchpoints.push(watchpoint);
+                            }
+                            Err(err) => output!("Could not set watchpoint: {}", err),
+                        }
+                    } else {
+                        output!("The program is not being run.");
+                    }
                 }
-                Err(ReadlineError::Eof) => {
-                    // User pressed ctrl+d, which is the equivalent of "quit" for our purposes
-                    return DebuggerCommand::Quit;
+                DebuggerCommand::CatchSyscall(names) => {
+                    if let Some(name) = names.iter().find(|name| syscalls::parse(name).is_none()) {
+                        output!("Unknown syscall name '{}'.", name);
+                        continue;
+                    }
+                    let catchpoint = Catchpoint {
+                        id: self.next_breakpoint_id,
+                        syscalls: names.iter().filter_map(|name| syscalls::parse(name)).collect(),
+                        enabled: true,
+                        hit_count: 0,
+                    };
+                    self.next_breakpoint_id += 1;
+                    output!(
+                        "Catchpoint {} ({})",
+                        catchpoint.id,
+                        describe_syscalls(&catchpoint.syscalls)
+                    );
+                    self.catchpoints.push(catchpoint);
+                    self.update_caught_syscalls();
                 }
-                Err(err) => {
-                    panic!("Unexpected I/O error: {:?}", err);
+                DebuggerCommand::Backtrace => {
+                    if self.examined().is_some() {
+                        match self.stack_frames() {
+                            Ok(frames) => {
+                                for (index, frame) in frames.iter().enumerate() {
+                                    self.print_frame(index, frame);
+                                }
+                            }
+                            Err(err) => output!("Could not read the stack: {}", err),
+                        }
+                    } else {
+                        output!("No stack.");
+                    }
                 }
-                Ok(line) => {
-                    if line.trim().len() == 0 {
+                DebuggerCommand::Frame(_) | DebuggerCommand::Up(_) | DebuggerCommand::Down(_) => {
+                    if self.examined().is_none() {
+                        output!("No stack.");
                         continue;
                     }
-                    self.readline.add_history_entry(line.as_str());
-                    if let Err(err) = self.readline.save_history(&self.history_path) {
-                        println!(
-                            "Warning: failed to save history file at {}: {}",
-                            self.history_path, err
-                        );
-                    }
-                    let tokens: Vec<&str> = line.split_whitespace().collect();
-                    if let Some(cmd) = DebuggerCommand::from_tokens(&tokens) {
-                        return cmd;
-                    } else {
-                        println!("Unrecognized command.");
+                    let frames = match self.stack_frames() {
+                        Ok(frames) => frames,
+                        Err(err) => {
+                            output!("Could not read the stack: {}", err);
+                            continue;
+                        }
+                    };
+                    let (selected, outermost) = (self.selected_frame, frames.len() - 1);
+                    let index = match command {
+                        DebuggerCommand::Frame(Some(index)) if index > outermost => {
+                            output!("No frame at level {}.", index);
+                            continue;
+                        }
+                        DebuggerCommand::Frame(index) => index.unwrap_or(selected),
+                        DebuggerCommand::Up(_) if selected >= outermost => {
+                            output!("Initial frame selected; you cannot go up.");
+                            continue;
+                        }
+                        DebuggerCommand::Up(count) => (selected + count).min(outermost),
+                        DebuggerCommand::Down(_) if selected == 0 => {
+                            output!("Bottom (innermost) frame selected; you cannot go down.");
+                            continue;
+                        }
+                        DebuggerCommand::Down(count) => selected.saturating_sub(count),
+                        _ => unreachable!(),
+                    };
+                    self.selected_frame = index;
+                    self.print_frame(index, &frames[index]);
+                    self.show_source_context(frame_pc(index, &frames[index]));
+                }
+                DebuggerCommand::Breakpoint(bp_target) => self.set_breakpoint(bp_target, None),
+                DebuggerCommand::Logpoint(location, message) => {
+                    self.set_breakpoint(location, Some(message))
+                }
+                DebuggerCommand::InfoBreakpoints => self.print_breakpoints(),
+                DebuggerCommand::Delete(ids) => {
+                    let ids = if ids.is_empty() { self.all_breakpoint_ids() } else { ids };
+                    for id in ids {
+                        if let Some(bp) = self.breakpoint_info.remove(&id) {
+                            self.update_breakpoint(bp.addr);
+                        } else if let Some(index) = self.watchpoints.iter().position(|w| w.id == id) {
+                            let watchpoint = self.watchpoints.remove(index);
+                            if let Some(inferior) = &mut self.inferior {
+                                for slot in watchpoint.slots {
+                                    let _ = inferior.clear_hardware_watchpoint(slot);
+                                }
+                            }
+                        } else if let Some(index) = self.catchpoints.iter().position(|c| c.id == id) {
+                            self.catchpoints.remove(index);
+                            self.update_caught_syscalls();
+                        } else {
+                            output!("No breakpoint number {}.", id);
+                        }
                     }
                 }
+                DebuggerCommand::Enable(ids) => self.set_breakpoints_enabled(ids, true),
+                DebuggerCommand::Disable(ids) => self.set_breakpoints_enabled(ids, false),
+                DebuggerCommand::Condition(id, condition) => match self.breakpoint_info.get_mut(&id) {
+                    Some(bp) => {
+                        if condition.is_none() {
+                            output!("Breakpoint {} now unconditional.", id);
+                        }
+                        bp.condition = condition;
+                    }
+                    None => output!("No breakpoint number {}.", id),
+                },
+                DebuggerCommand::Ignore(id, count) => match self.breakpoint_info.get_mut(&id) {
+                    Some(bp) => {
+                        bp.ignore_count = count;
+                        output!("Will ignore next {} crossings of breakpoint {}.", count, id);
+                    }
+                    None => output!("No breakpoint number {}.", id),
+                },
             }
         }
     }
 
-    fn parse_address(&self, addr: &str) -> Option<usize> {
-        let addr_without_0x = if addr.to_lowercase().starts_with("0x") {
-            &addr[2..]
+    /// Attaches to the running process pid, switching to its executable's debugging symbols if
+    /// it isn't the current target.
+    pub fn attach(&mut self, pid: Pid) {
+        if self.inferior.is_some() {
+            output!("A program is already being debugged. Detach from it or kill it first.");
+            return;
+        }
+        let exe = match fs::read_link(format!("/proc/{}/exe", pid)) {
+            Ok(exe) => exe,
+            Err(err) => {
+                output!("Could not attach to process {}: {}", pid, err);
+                return;
+            }
+        };
+        let same_target = fs::canonicalize(&self.target).ok() == Some(exe.clone());
+        if !same_target && !self.load_target(&exe.to_string_lossy()) {
+            return;
+        }
+        match Inferior::attach(pid) {
+            Ok((mut inferior, status)) => {
+                output!("Attached to process {}", pid);
+                self.run_started = Instant::now();
+                self.logpoint_hits.clear();
+                self.configure_inferior(&mut inferior);
+                self.inferior = Some(inferior);
+                self.core = None;
+                self.load_libraries();
+                self.install_breakpoints();
+                self.report_status(Ok(status));
+            }
+            Err(err) => output!("Could not attach to process {}: {}", pid, err),
+        }
+    }
+
+    /// Passes the settings that inferiors need to know about on to a new or switched to one.
+    fn configure_inferior(&self, inferior: &mut Inferior) {
+        inferior.set_signals(&self.signals);
+        inferior.set_fork_policy(self.fork_policy);
+        inferior.set_caught_syscalls(self.caught_syscalls());
+    }
+
+    /// Returns the syscalls that enabled catchpoints catch, an empty list meaning all of them,
+    /// or None if there are no enabled catchpoints.
+    fn caught_syscalls(&self) -> Option<Vec<usize>> {
+        let enabled: Vec<&Catchpoint> = self.catchpoints.iter().filter(|c| c.enabled).collect();
+        if enabled.is_empty() {
+            None
+        } else if enabled.iter().any(|catchpoint| catchpoint.syscalls.is_empty()) {
+            Some(Vec::new())
         } else {
-            &addr
+            Some(enabled.iter().flat_map(|catchpoint| catchpoint.syscalls.clone()).collect())
+        }
+    }
+
+    /// Tells the inferior which syscalls to stop at after the catchpoints changed.
+    fn update_caught_syscalls(&mut self) {
+        let caught = self.caught_syscalls();
+        if let Some(inferior) = &mut self.inferior {
+            inferior.set_caught_syscalls(caught);
+        }
+    }
+
+    /// Changes one of deet's settings with "set <name> <value>".
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match (name, value) {
+            ("follow-fork-mode", "parent") => self.fork_policy.follow_child = false,
+            ("follow-fork-mode", "child") => self.fork_policy.follow_child = true,
+            ("follow-fork-mode", _) => return Err(format!("Undefined item: \"{}\".", value)),
+            ("detach-on-fork", "on") => self.fork_policy.detach = true,
+            ("detach-on-fork", "off") => self.fork_policy.detach = false,
+            ("detach-on-fork", _) => return Err("\"on\" or \"off\" expected.".to_string()),
+            ("logpoint-file", "off") => {
+                self.log_file = None;
+                output!("Logpoint messages will be shown.");
+                return Ok(());
+            }
+            ("logpoint-file", _) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(value)
+                    .map_err(|err| format!("Could not open {}: {}", value, err))?;
+                self.log_file = Some((value.to_string(), file));
+                output!("Logpoint messages will be written to {}.", value);
+                return Ok(());
+            }
+            ("debug-file-directory", _) => {
+                self.debug_file_directory = value.to_string();
+                self.reload_symbols();
+                return Ok(());
+            }
+            _ => return Err(format!("Undefined set command: \"{}\".", name)),
+        }
+        if let Some(inferior) = &mut self.inferior {
+            inferior.set_fork_policy(self.fork_policy);
+        }
+        Ok(())
+    }
+
+    /// Takes on the processes that the inferior has forked off and that deet holds on to as
+    /// further inferiors.
+    fn collect_held_inferiors(&mut self) {
+        if let Some(inferior) = &mut self.inferior {
+            for held in inferior.take_held() {
+                output!("[New inferior {} (process {})]", self.next_inferior_number, held.pid());
+                self.held_inferiors.push((self.next_inferior_number, held));
+                self.next_inferior_number += 1;
+            }
+        }
+    }
+
+    /// Lists the current inferior and the ones being held.
+    fn print_inferiors(&self) {
+        let mut inferiors: Vec<(usize, Option<&Inferior>)> = self
+            .held_inferiors
+            .iter()
+            .map(|(number, inferior)| (*number, Some(inferior)))
+            .collect();
+        inferiors.push((self.inferior_number, self.inferior.as_ref()));
+        inferiors.sort_by_key(|(number, _)| *number);
+        output!("  Num  Description       Executable");
+        for (number, inferior) in inferiors {
+            let marker = if number == self.inferior_number { '*' } else { ' ' };
+            let (description, exe) = match inferior {
+                Some(inferior) => (
+                    format!("process {}", inferior.pid()),
+                    fs::read_link(format!("/proc/{}/exe", inferior.pid()))
+                        .map_or(String::new(), |exe| exe.to_string_lossy().to_string()),
+                ),
+                None => ("<null>".to_string(), self.target.clone()),
+            };
+            output!("{} {:<4} {:<17} {}", marker, number, description, exe);
+        }
+    }
+
+    /// Makes a held inferior the current one, holding on to the current one in its place.
+    fn switch_inferior(&mut self, number: usize) {
+        let index = match self.held_inferiors.iter().position(|(n, _)| *n == number) {
+            Some(index) => index,
+            None if number == self.inferior_number => {
+                output!("Inferior {} is already selected.", number);
+                return;
+            }
+            None => {
+                output!("No inferior number '{}'.", number);
+                return;
+            }
         };
-        usize::from_str_radix(addr_without_0x, 16).ok()
+        let (_, mut inferior) = self.held_inferiors.remove(index);
+        // the process may be running a different program after an exec
+        let exe = fs::read_link(format!("/proc/{}/exe", inferior.pid()))
+            .map_or(self.target.clone(), |exe| exe.to_string_lossy().to_string());
+        let same_target = fs::canonicalize(&self.target).ok() == fs::canonicalize(&exe).ok();
+        if self.inferior.is_some() {
+            self.remove_breakpoints();
+            let current = self.inferior.take().unwrap();
+            self.held_inferiors.push((self.inferior_number, current));
+            self.held_inferiors.sort_by_key(|(number, _)| *number);
+        }
+        if !same_target {
+            self.load_target(&exe);
+        }
+        output!("[Switching to inferior {} [process {}] ({})]", number, inferior.pid(), exe);
+        self.configure_inferior(&mut inferior);
+        self.inferior = Some(inferior);
+        self.inferior_number = number;
+        self.load_libraries();
+        self.install_breakpoints();
+        self.selected_frame = 0;
+        if let Ok(frames) = self.stack_frames() {
+            self.print_frame(0, &frames[0]);
+            self.show_source_context(frame_pc(0, &frames[0]));
+        }
+    }
+
+    /// Switches to the program the inferior has just started running with exec, placing the
+    /// breakpoints in it again.
+    fn follow_exec(&mut self) {
+        let pid = self.inferior.as_ref().unwrap().pid();
+        // the new program has none of the breakpoints that were written into the old one
+        self.forget_breakpoints();
+        match fs::read_link(format!("/proc/{}/exe", pid)) {
+            Ok(exe) => {
+                let exe = exe.to_string_lossy().to_string();
+                output!("process {} is executing new program: {}", pid, exe);
+                if self.load_target(&exe) {
+                    self.load_libraries();
+                    self.install_breakpoints();
+                }
+            }
+            Err(err) => output!("Could not find the new program of process {}: {}", pid, err),
+        }
+    }
+
+    /// Applies the handle keywords among args to the signals among them, and shows the result.
+    fn handle_signals(&mut self, args: &[String]) {
+        let (names, keywords): (Vec<&String>, Vec<&String>) =
+            args.iter().partition(|arg| signals::parse_signal(arg).is_some());
+        if names.is_empty() {
+            output!("Argument required (signal names).");
+            return;
+        }
+        let handled: Vec<Signal> =
+            names.iter().filter_map(|name| signals::parse_signal(name)).collect();
+        for keyword in keywords {
+            for signal in &handled {
+                if let Err(err) = self.signals.handle(*signal, keyword) {
+                    output!("{}", err);
+                    return;
+                }
+            }
+        }
+        if let Some(inferior) = &mut self.inferior {
+            inferior.set_signals(&self.signals);
+        }
+        self.signals.print_header();
+        for signal in handled {
+            self.signals.print_policy(signal);
+        }
+    }
+
+    /// Evaluates an expression to an address or other integer, using the address of arrays,
+    /// structs and functions.
+    fn evaluate_integer(&mut self, expr: &str) -> Result<usize, String> {
+        self.evaluate(expr, self.selected_frame, |evaluator, value| evaluator.as_address(&value))
+    }
+
+    /// Evaluates an expression in the given stack frame and passes the result to f, along with
+    /// the evaluator for reading and formatting it. An assignment is carried out first.
+    fn evaluate<T>(
+        &mut self,
+        text: &str,
+        frame: usize,
+        f: impl FnOnce(&mut Evaluator, Value) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let debug_data = &self.debug_data;
+        let mut expr = expr::parse(text, &|name| debug_data.get_type_by_name(name).is_some())?;
+        self.make_calls(&mut expr, frame)?;
+        let assigned = match &expr {
+            Expr::Assign(lhs, rhs) => Some(self.assign(lhs, rhs, frame)?),
+            _ => None,
+        };
+        let mut evaluator = self.evaluator(frame)?;
+        let value = match assigned {
+            Some(value) => value,
+            None => evaluator.evaluate(&expr)?,
+        };
+        f(&mut evaluator, value)
+    }
+
+    /// Calls the functions an expression calls, innermost first, replacing each call with the
+    /// value it returned. As in C, only the side of && or || and the branch of ?: that get
+    /// evaluated make their calls, and sizeof makes none.
+    fn make_calls(&mut self, expr: &mut Expr, frame: usize) -> Result<(), String> {
+        let lazy = match expr {
+            Expr::SizeofExpr(_) => return Ok(()),
+            Expr::Binary("&&", _, rest) | Expr::Binary("||", _, rest) => makes_calls(rest),
+            Expr::Conditional(_, if_true, if_false) => {
+                makes_calls(if_true) || makes_calls(if_false)
+            }
+            _ => false,
+        };
+        let condition = match expr {
+            Expr::Binary(_, condition, _) | Expr::Conditional(condition, _, _) if lazy => {
+                Some(condition)
+            }
+            _ => None,
+        };
+        if let Some(condition) = condition {
+            // the condition decides which calls are made, so it is settled first
+            self.make_calls(condition, frame)?;
+            let mut evaluator = self.evaluator(frame)?;
+            let value = evaluator.evaluate(condition)?;
+            let holds = evaluator.is_true(&value)?;
+            **condition = Expr::Integer(holds as u64);
+            match expr {
+                Expr::Binary("&&", _, rest) if holds => self.make_calls(rest, frame)?,
+                Expr::Binary("||", _, rest) if !holds => self.make_calls(rest, frame)?,
+                Expr::Conditional(_, if_true, _) if holds => self.make_calls(if_true, frame)?,
+                Expr::Conditional(_, _, if_false) if !holds => self.make_calls(if_false, frame)?,
+                _ => {}
+            }
+            return Ok(());
+        }
+        for operand in expr.operands_mut() {
+            self.make_calls(operand, frame)?;
+        }
+        if let Expr::Call(func, args) = expr {
+            let returned = self.call_function(func, args, frame)?;
+            *expr = Expr::Value(returned);
+        }
+        Ok(())
+    }
+
+    /// Calls a function in the current thread and returns what it returned, with the registers
+    /// put back the way they were. If the function stops at a breakpoint or gets a signal, the
+    /// call is abandoned.
+    fn call_function(&mut self, func: &Expr, args: &[Expr], frame: usize) -> Result<Value, String> {
+        if self.inferior.is_none() {
+            return Err("You can't do that without a process to debug.".to_string());
+        }
+        let mut evaluator = self.evaluator(frame)?;
+        let func = evaluator.evaluate(func)?;
+        let args = args
+            .iter()
+            .map(|arg| evaluator.evaluate(arg))
+            .collect::<Result<Vec<Value>, String>>()?;
+        let call = evaluator.prepare_call(&func, &args)?;
+        let inferior = self.inferior.as_mut().unwrap();
+        // the function returns to a temporary breakpoint at the program's entry point
+        let return_addr = inferior
+            .entry_point()
+            .ok_or("Cannot find the program's entry point to return to.")?;
+        let saved = inferior.save_registers().map_err(|err| err.to_string())?;
+        let result = match inferior.setup_call(call.addr, &call.args, return_addr) {
+            Ok(frame_rsp) => self.run_call(call.addr, return_addr, frame_rsp),
+            Err(err) => Err(err),
+        };
+        let stopped = match result {
+            Ok(Status::Stopped(Signal::SIGTRAP, rip)) if rip == return_addr => {
+                let inferior = self.inferior.as_mut().unwrap();
+                let registers = inferior.return_registers();
+                inferior.restore_registers(&saved).map_err(|err| err.to_string())?;
+                let (rax, xmm0) = registers.map_err(|err| err.to_string())?;
+                return Ok(self.evaluator(frame)?.returned(&call, rax, xmm0));
+            }
+            Ok(Status::Stopped(Signal::SIGTRAP, _)) | Ok(Status::Syscall(_, _)) => "stopped",
+            Ok(Status::Stopped(signal, _)) => {
+                output!("Child stopped (signal {})", signal);
+                "was signaled"
+            }
+            Ok(status) => {
+                self.report_status(Ok(status));
+                return Err(abandoned_call(&call, "exited", false));
+            }
+            Err(err) => {
+                output!("{}", err);
+                "stopped"
+            }
+        };
+        self.hit_breakpoints.clear();
+        if let Some(frame) = self.stack_frames().ok().and_then(|frames| frames.into_iter().next()) {
+            self.print_frame(0, &frame);
+        }
+        let inferior = self.inferior.as_mut().unwrap();
+        inferior.restore_registers(&saved).map_err(|err| err.to_string())?;
+        Err(abandoned_call(&call, stopped, true))
     }
 
+    /// Runs a call of the function at addr set up in the inferior until it returns to
+    /// return_addr, continuing through breakpoints that shouldn't stop it.
+    fn run_call(
+        &mut self,
+        addr: usize,
+        return_addr: usize,
+        frame_rsp: usize,
+    ) -> Result<Status, nix::Error> {
+        // continuing would step over a breakpoint on the function's first instruction
+        if self.breakpoints.contains_key(&addr) && self.should_stop_at_breakpoint(addr) {
+            return Ok(Status::Stopped(Signal::SIGTRAP, addr));
+        }
+        self.run_to_return(return_addr, frame_rsp)
+    }
+
+    /// Runs the inferior until the frame whose stack pointer will be frame_rsp returns to
+    /// ret_addr, continuing through breakpoints whose condition is false, that are being ignored
+    /// or that are logpoints.
+    fn run_to_return(&mut self, ret_addr: usize, frame_rsp: usize) -> Result<Status, nix::Error> {
+        loop {
+            let inferior = self.inferior.as_mut().unwrap();
+            let status = inferior.run_to_return(ret_addr, frame_rsp, &self.breakpoints)?;
+            if let Status::Stopped(Signal::SIGTRAP, rip) = status {
+                if self.breakpoints.contains_key(&rip)
+                    && !self.should_stop_at_breakpoint(rip)
+                    && rip != ret_addr
+                {
+                    continue;
+                }
+            }
+            return Ok(status);
+        }
+    }
+
+    /// Steps to another source line, continuing through breakpoints on the way that shouldn't
+    /// stop the inferior. A breakpoint where the step ends is still counted, and logs if it is a
+    /// logpoint.
+    fn step_line(&mut self, mut step: LineStep) -> Result<Status, nix::Error> {
+        loop {
+            let inferior = self.inferior.as_mut().unwrap();
+            let status = inferior.step_line(&self.debug_data, &self.breakpoints, &mut step)?;
+            if let Status::Stopped(Signal::SIGTRAP, rip) = status {
+                if self.breakpoints.contains_key(&rip)
+                    && !self.should_stop_at_breakpoint(rip)
+                    && !step.finished
+                {
+                    continue;
+                }
+            }
+            return Ok(status);
+        }
+    }
+
+    /// Returns an evaluator for expressions in the given stack frame. Without a program, only
+    /// globals are in scope, and reading them fails.
+    fn evaluator(&mut self, frame: usize) -> Result<Evaluator<'_>, String> {
+        let (pc, frame_base, regs) = match self.examined() {
+            Some(target) => {
+                let regs = target.registers().map_err(|err| err.to_string())?;
+                let frames = self.stack_frames().map_err(|err| err.to_string())?;
+                let frame_info = frames.get(frame).ok_or("No stack.")?;
+                let regs = frame_info.regs.apply(&regs);
+                let pc = frame_pc(frame, frame_info);
+                let func = self.debug_data.get_function(pc);
+                (pc, frame_base(frame_info, func), Some(regs))
+            }
+            None => (0, 0, None),
+        };
+        Ok(Evaluator {
+            target: match (&self.inferior, &self.core) {
+                (Some(inferior), _) => inferior as &dyn Target,
+                (None, Some(core)) => core,
+                (None, None) => &NoProcess,
+            },
+            debug_data: &mut self.debug_data,
+            pc,
+            frame_base,
+            regs,
+            history: &self.history,
+            convenience: &self.convenience,
+        })
+    }
+
+    /// Carries out lhs = rhs and returns the new value of lhs, which can be an lvalue in memory,
+    /// a $register or a convenience variable.
+    fn assign(&mut self, lhs: &Expr, rhs: &Expr, frame: usize) -> Result<Value, String> {
+        let mut evaluator = self.evaluator(frame)?;
+        let rhs = evaluator.evaluate(rhs)?;
+        if let Expr::Dollar(name) = lhs {
+            if expr::is_convenience_variable(name) {
+                let value = evaluator.record(&rhs)?;
+                self.convenience.insert(name.clone(), value.clone());
+                return Ok(value);
+            }
+        }
+        let lhs = evaluator.evaluate(lhs)?;
+        let bytes = evaluator.convert(&rhs, &lhs.value_type)?;
+        if self.inferior.is_none() {
+            return Err(match self.core {
+                Some(_) => "Cannot modify a core file.".to_string(),
+                None => "The program is not being run.".to_string(),
+            });
+        }
+        match &lhs.location {
+            ValueLocation::Memory(addr) => self.write_memory(*addr, &bytes)?,
+            ValueLocation::Register(_) if frame != 0 => {
+                return Err("Registers can only be set in the innermost frame.".to_string())
+            }
+            ValueLocation::Register(name) => {
+                let tid = self.inferior.as_ref().unwrap().tid();
+                let mut regs = ptrace::getregs(tid).map_err(|err| err.to_string())?;
+                *registers::get_mut(&mut regs, name).unwrap() = value::read_uint(&bytes);
+                ptrace::setregs(tid, regs).map_err(|err| err.to_string())?;
+            }
+            ValueLocation::Computed(_) => {
+                return Err("Left operand of assignment is not an lvalue.".to_string())
+            }
+        }
+        Ok(lhs)
+    }
+
+    /// Reads memory of the examined program, with the original bytes in place of breakpoints'
+    /// 0xcc bytes.
+    fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error> {
+        let mut bytes = self.examined().unwrap().read_bytes(addr, len)?;
+        for bp in self.breakpoints.values().flatten() {
+            if addr <= bp.addr && bp.addr < addr + len {
+                bytes[bp.addr - addr] = bp.orig_byte;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Writes memory of the inferior. Bytes written where there are breakpoints become the
+    /// breakpoints' original bytes, and the 0xcc bytes stay in place.
+    fn write_memory(&mut self, addr: usize, bytes: &[u8]) -> Result<(), String> {
+        let inferior = self.inferior.as_mut().ok_or("The program is not being run.")?;
+        let mut bytes = bytes.to_vec();
+        for bp in self.breakpoints.values_mut().flatten() {
+            if addr <= bp.addr && bp.addr < addr + bytes.len() {
+                bp.orig_byte = bytes[bp.addr - addr];
+                bytes[bp.addr - addr] = 0xcc;
+            }
+        }
+        inferior
+            .write_bytes(addr, &bytes)
+            .or(Err(format!("Cannot access memory at address {:#x}", addr)))
+    }
+
+    /// Prints memory starting at addr the way gdb's x command does.
+    fn examine(&self, addr: usize, format: &ExamineFormat) {
+        let target = self.examined().unwrap();
+        match format.format {
+            's' => {
+                let mut addr = addr;
+                for _ in 0..format.count {
+                    match value::read_string_bytes(target, addr, value::MAX_STRING_LEN) {
+                        Some((bytes, _)) => {
+                            output!("{:#x}:\t{}", addr, value::read_string(target, addr).unwrap());
+                            addr += bytes.len() + 1;
+                        }
+                        None => {
+                            output!("Cannot access memory at address {:#x}", addr);
+                            return;
+                        }
+                    }
+                }
+            }
+            'i' => self.print_instructions(addr, format.count),
+            _ => {
+                let size = format.size.unwrap_or(match format.format {
+                    'a' | 'f' => 8,
+                    'c' => 1,
+                    _ => 4,
+                });
+                let per_line = match (format.format, size) {
+                    ('c', _) => 8,
+                    (_, 8) => 2,
+                    (_, 4) => 4,
+                    _ => 8,
+                };
+                for first in (0..format.count).step_by(per_line) {
+                    let line_addr = addr + first * size;
+                    let mut line = format!("{:#x}{}:", line_addr, self.symbol_suffix(line_addr));
+                    for index in first..format.count.min(first + per_line) {
+                        let unit_addr = addr + index * size;
+                        match self.read_memory(unit_addr, size) {
+                            Ok(bytes) => line += &format!("\t{}", self.format_unit(&bytes, format.format)),
+                            Err(_) => {
+                                output!("{}", line);
+                                output!("Cannot access memory at address {:#x}", unit_addr);
+                                return;
+                            }
+                        }
+                    }
+                    output!("{}", line);
+                }
+            }
+        }
+    }
+
+    /// Formats one unit of memory for x.
+    fn format_unit(&self, bytes: &[u8], format: char) -> String {
+        let unsigned = value::read_uint(bytes);
+        match (format, bytes.len()) {
+            ('x', len) => format!("{:#0width$x}", unsigned, width = 2 + 2 * len),
+            ('d', _) => value::read_int(bytes).to_string(),
+            ('u', _) => unsigned.to_string(),
+            ('o', _) => format!("0{:o}", unsigned),
+            ('t', len) => format!("{:0width$b}", unsigned, width = 8 * len),
+            ('c', _) => value::format_char(bytes[0]),
+            ('f', 4) => value::format_float(f32::from_bits(unsigned as u32) as f64),
+            ('f', 8) => value::format_float(f64::from_bits(unsigned)),
+            ('a', _) => format!("{:#x}{}", unsigned, self.symbol_suffix(unsigned as usize)),
+            _ => value::read_int(bytes).to_string(),
+        }
+    }
+
+    /// Returns " <symbol+offset>" for an address in a function or global variable, or "".
+    fn symbol_suffix(&self, addr: usize) -> String {
+        value::symbol_suffix(&self.debug_data, addr)
+    }
+
+    /// Decodes the instruction at addr, as it was before any breakpoint was set there.
+    fn decode_instruction(&self, addr: usize) -> Option<Instruction> {
+        // the instruction may be close to the end of readable memory
+        for len in &[disasm::MAX_INSTRUCTION_LEN, 8, 1] {
+            if let Ok(bytes) = self.read_memory(addr, *len) {
+                return Some(disasm::decode(&bytes, addr));
+            }
+        }
+        None
+    }
+
+    /// Formats an instruction with the symbols of the addresses it refers to.
+    fn format_instruction(&self, instruction: &Instruction) -> String {
+        let mut text = instruction.to_string();
+        if let Some(target) = instruction.branch_target {
+            text += &self.symbol_suffix(target);
+        }
+        if let Some(target) = instruction.memory_target {
+            text += &format!("        # {:#x}{}", target, self.symbol_suffix(target));
+        }
+        text
+    }
+
+    /// Returns the %rip of the examined program.
+    fn current_pc(&self) -> Option<usize> {
+        Some(self.examined()?.registers().ok()?.rip as usize)
+    }
+
+    /// Prints count instructions starting at addr, marking the current one with "=>".
+    fn print_instructions(&self, mut addr: usize, count: usize) {
+        let pc = self.current_pc();
+        for _ in 0..count {
+            let instruction = match self.decode_instruction(addr) {
+                Some(instruction) => instruction,
+                None => {
+                    output!("Cannot access memory at address {:#x}", addr);
+                    return;
+                }
+            };
+            output!(
+                "{}{:#x}{}:\t{}",
+                if Some(addr) == pc { "=> " } else { "   " },
+                addr,
+                self.symbol_suffix(addr),
+                self.format_instruction(&instruction)
+            );
+            addr += instruction.len;
+        }
+    }
+
+    /// Carries out the disassemble command. Without a range, disassembles the function the
+    /// selected frame is in.
+    fn disassemble(&mut self, range: Option<String>) -> Result<(), String> {
+        let (start, end, func_name) = match range {
+            Some(range) if range.contains(',') => {
+                let comma = range.find(',').unwrap();
+                let start = self.evaluate_integer(&range[..comma])?;
+                let end = range[comma + 1..].trim();
+                let end = if end.starts_with('+') {
+                    let len = self.evaluate_integer(&end[1..])?;
+                    start.checked_add(len).ok_or("Invalid range")?
+                } else {
+                    self.evaluate_integer(end)?
+                };
+                (start, end, None)
+            }
+            range => {
+                let addr = match range {
+                    Some(expr) => self.evaluate_integer(&expr)?,
+                    None => self.frame_context(self.selected_frame)?.0,
+                };
+                let func = self
+                    .debug_data
+                    .get_function(addr)
+                    .ok_or("No function contains specified address.")?;
+                (func.address, func.address + func.text_length, Some(func.name.clone()))
+            }
+        };
+        match &func_name {
+            Some(name) => output!("Dump of assembler code for function {}:", name),
+            None => output!("Dump of assembler code from {:#x} to {:#x}:", start, end),
+        }
+        let pc = self.current_pc();
+        let mut line_number = None;
+        let mut addr = start;
+        while addr < end {
+            let instruction = match self.decode_instruction(addr) {
+                Some(instruction) => instruction,
+                None => {
+                    output!("Cannot access memory at address {:#x}", addr);
+                    break;
+                }
+            };
+            // show where each source line's code starts
+            if let Some(line) = self.debug_data.get_line_from_addr(addr) {
+                if line_number != Some(line.number) {
+                    match self.sources.lines(&line.file).and_then(|lines| lines.get(line.number - 1)) {
+                        Some(text) => output!("{}\t{}", line.number, text),
+                        None => output!("{}:", line),
+                    }
+                    line_number = Some(line.number);
+                }
+            }
+            let label = match &func_name {
+                Some(_) => format!(" <+{}>", addr - start),
+                None => self.symbol_suffix(addr),
+            };
+            output!(
+                "{}{:#018x}{}:\t{}",
+                if Some(addr) == pc { "=> " } else { "   " },
+                addr,
+                label,
+                self.format_instruction(&instruction)
+            );
+            addr += instruction.len;
+        }
+        output!("End of assembler dump.");
+        Ok(())
+    }
+
+    /// Prints the source lines around the line containing pc, with an arrow at that line, and
+    /// makes list continue from there.
+    fn show_source_context(&mut self, pc: usize) {
+        let line = match self.debug_data.get_line_from_addr(pc) {
+            Some(line) => line,
+            None => return,
+        };
+        let first = line.number.saturating_sub(CONTEXT_LINES).max(1);
+        match self.sources.lines(&line.file) {
+            Some(lines) => {
+                let last = (line.number + CONTEXT_LINES).min(lines.len());
+                for number in first..=last {
+                    let marker = if number == line.number { "=>" } else { "  " };
+                    output!("{} {:<4} {}", marker, number, lines[number - 1]);
+                }
+            }
+            None => output!("{}\tin {}", line.number, line.file),
+        }
+        let centered = line.number.saturating_sub(LIST_LINES / 2).max(1);
+        self.list_position = Some((line.file, centered, centered));
+    }
+
+    /// Carries out the list command.
+    fn list(&mut self, location: Option<&str>) -> Result<(), String> {
+        // list - goes back from the first line of a run of lists without arguments
+        let mut run_first = None;
+        let (path, first, last) = match location {
+            None => {
+                let (path, first, next) = match self.list_position.clone() {
+                    Some(position) => position,
+                    None => {
+                        let (path, number) = self.default_source_line()?;
+                        let centered = number.saturating_sub(LIST_LINES / 2).max(1);
+                        (path, centered, centered)
+                    }
+                };
+                run_first = Some(first);
+                (path, next, next + LIST_LINES - 1)
+            }
+            Some("-") => {
+                let (path, first, _) = self.list_position.clone().ok_or("No previous listing.")?;
+                if first <= 1 {
+                    return Err(format!("Already at the start of {}.", path));
+                }
+                (path, first.saturating_sub(LIST_LINES).max(1), first - 1)
+            }
+            Some(location) => {
+                let (path, number) = self.source_line(location)?;
+                let first = number.saturating_sub(LIST_LINES / 2).max(1);
+                (path, first, first + LIST_LINES - 1)
+            }
+        };
+        let lines = self
+            .sources
+            .lines(&path)
+            .ok_or(format!("{}: No such file or directory.", path))?;
+        if first > lines.len() {
+            return Err(format!(
+                "Line number {} out of range; \"{}\" has {} lines.",
+                first,
+                path,
+                lines.len()
+            ));
+        }
+        let last = last.min(lines.len());
+        for number in first..=last {
+            output!("{}\t{}", number, lines[number - 1]);
+        }
+        self.list_position = Some((path, run_first.unwrap_or(first), last + 1));
+        Ok(())
+    }
+
+    /// Finds the source file and line of a list location: *address, line, function, file:line
+    /// or file:function.
+    fn source_line(&self, location: &str) -> Result<(String, usize), String> {
+        let addr = if location.starts_with('*') {
+            self.parse_address(&location[1..])
+                .ok_or(format!("Invalid address {}", &location[1..]))?
+        } else {
+            let (file, target) = match file_separator(location) {
+                Some(colon) => (Some(&location[..colon]), &location[colon + 1..]),
+                None => (None, location),
+            };
+            if let Ok(number) = target.parse::<usize>() {
+                let path = match (file, &self.list_position) {
+                    (Some(file), _) => {
+                        self.debug_data.get_source_path(file).map_err(|err| err.to_string())?
+                    }
+                    (None, Some((path, _, _))) => path.clone(),
+                    (None, None) => self.default_source_line()?.0,
+                };
+                return Ok((path, number));
+            }
+            self.debug_data
+                .get_addr_for_function(file, target)
+                .map_err(|err| err.to_string())?
+        };
+        let line = self
+            .debug_data
+            .get_line_from_addr(addr)
+            .ok_or(format!("No line number information available for address {:#x}", addr))?;
+        Ok((line.file, line.number))
+    }
+
+    /// Returns the source file and line that list shows when nothing has been listed or shown
+    /// yet: where the program is, or else main.
+    fn default_source_line(&self) -> Result<(String, usize), String> {
+        let pc = match self.examined().and_then(|target| target.registers().ok()) {
+            Some(regs) => regs.rip as usize,
+            _ => self
+                .debug_data
+                .get_addr_for_function(None, "main")
+                .map_err(|err| err.to_string())?,
+        };
+        let line = self
+            .debug_data
+            .get_line_from_addr(pc)
+            .ok_or("No symbol table is loaded.")?;
+        Ok((line.file, line.number))
+    }
+
+    /// Executes one instruction. If step_over is set and it is a call, the called function is
+    /// run until it returns.
+    fn step_instruction(&mut self, step_over: bool) -> Result<Status, nix::Error> {
+        let regs = ptrace::getregs(self.inferior.as_ref().unwrap().tid())?;
+        let call = match step_over {
+            true => self.decode_instruction(regs.rip as usize).filter(|i| i.is_call()),
+            false => None,
+        };
+        match call {
+            Some(call) => self.run_to_return(call.addr + call.len, regs.rsp as usize),
+            None => self.inferior.as_mut().unwrap().step_instruction(&self.breakpoints),
+        }
+    }
+
+    /// Prints the value of an expression in the selected frame and adds it to the value history.
+    /// Unless show_void is set, what a void function returned is left out, as call does.
+    fn print_expression(&mut self, expr: &str, show_void: bool) {
+        let result = self.evaluate(expr, self.selected_frame, |evaluator, value| {
+            if expr::is_void(&value) && !show_void {
+                return Ok(None);
+            }
+            Ok(Some((evaluator.format(&value)?, evaluator.record(&value)?)))
+        });
+        match result {
+            Ok(Some((formatted, value))) => {
+                self.history.push(value);
+                output!("${} = {}", self.history.len(), formatted);
+            }
+            Ok(None) => {}
+            Err(err) => output!("{}", err),
+        }
+    }
+
+    /// Evaluates an expression in the selected frame, returning "expr = value" or the error.
+    fn format_expression(&mut self, expr: &str) -> String {
+        let frame = self.selected_frame;
+        match self.evaluate(expr, frame, |evaluator, value| evaluator.format(&value)) {
+            Ok(formatted) => format!("{} = {}", expr, formatted),
+            Err(err) => err,
+        }
+    }
+
+    fn show_display(&mut self, display: &AutoDisplay) {
+        match &display.format {
+            Some(format) => {
+                output!("{}: x{} {}", display.id, format, display.expr);
+                match self.evaluate_integer(&display.expr) {
+                    Ok(addr) => self.examine(addr, format),
+                    Err(err) => output!("{}", err),
+                }
+            }
+            None => {
+                let formatted = self.format_expression(&display.expr);
+                output!("{}: {}", display.id, formatted);
+            }
+        }
+    }
+
+    fn show_displays(&mut self) {
+        let displays = std::mem::take(&mut self.displays);
+        for display in &displays {
+            self.show_display(display);
+        }
+        self.displays = displays;
+    }
+
+    /// Prints the given registers, or all of them, in hex and in their natural format.
+    fn print_registers(&self, names: &[String]) {
+        let regs = match self.examined().map(|target| target.registers()) {
+            Some(Ok(regs)) => regs,
+            Some(Err(err)) => {
+                output!("Could not read registers: {}", err);
+                return;
+            }
+            None => {
+                output!("The program has no registers now.");
+                return;
+            }
+        };
+        let names: Vec<&str> = if names.is_empty() {
+            registers::REGISTER_NAMES.to_vec()
+        } else {
+            names.iter().map(|name| name.as_str()).collect()
+        };
+        for name in names {
+            let value = match registers::get(&regs, name) {
+                Some(value) => value,
+                None => {
+                    output!("Invalid register `{}'", name);
+                    continue;
+                }
+            };
+            let natural = match name {
+                "eflags" => registers::format_eflags(value),
+                "rip" | "pc" => format!("{:#x}{}", value, self.symbol_suffix(value as usize)),
+                "rsp" | "rbp" | "sp" | "fp" | "fs_base" | "gs_base" => format!("{:#x}", value),
+                _ => (value as i64).to_string(),
+            };
+            output!("{:<15}{:<19}{}", name, format!("{:#x}", value), natural);
+        }
+    }
+
+    /// Loads a core dump for post-mortem inspection and shows where the program was.
+    pub fn load_core(&mut self, path: &str) {
+        let core = match CoreFile::open(path) {
+            Ok(core) => core,
+            Err(err) => {
+                output!("Could not load core file {}: {}", path, err);
+                return;
+            }
+        };
+        output!("Core was generated by process {}.", core.pid());
+        if let Ok(signal) = Signal::try_from(core.signal()) {
+            output!("Program terminated with signal {}.", signal);
+        }
+        self.core = Some(core);
+        self.selected_frame = 0;
+        self.load_libraries();
+        match self.stack_frames() {
+            Ok(frames) if !frames.is_empty() => {
+                self.print_frame(0, &frames[0]);
+                self.show_source_context(frames[0].pc);
+            }
+            _ => output!("No stack."),
+        }
+    }
+
+    /// Switches to debugging a different program. Breakpoints are resolved again in the new
+    /// program where possible; watchpoints are deleted.
+    fn load_target(&mut self, target: &str) -> bool {
+        let debug_data = match load_debug_data(target, &self.debug_file_directory) {
+            Some(debug_data) => debug_data,
+            None => return false,
+        };
+        output!("Reading symbols from {}", target);
+        self.readline.set_helper(Some(LocationCompleter::new(&debug_data)));
+        self.debug_data = debug_data;
+        self.target = target.to_string();
+
+        self.breakpoints.clear();
+        // the new symbols may still have to be relocated, so breakpoints are only written into
+        // the inferior by install_breakpoints once load_libraries has done that
+        let inferior = self.inferior.take();
+        self.resolve_breakpoints();
+        self.inferior = inferior;
+        if !self.watchpoints.is_empty() {
+            output!("Deleting watchpoints, which refer to the previous program.");
+            self.watchpoints.clear();
+        }
+        true
+    }
+
+    /// Reads the symbols of the program and its libraries again, e.g. after the directory debug
+    /// files are looked for in changed.
+    fn reload_symbols(&mut self) {
+        if self.inferior.is_some() {
+            self.remove_breakpoints();
+        }
+        let target = self.target.clone();
+        if self.load_target(&target) {
+            self.load_libraries();
+        }
+        if self.inferior.is_some() {
+            self.install_breakpoints();
+        }
+    }
+
+    /// Relocates the program's symbols to where it is loaded, which differs from run to run for
+    /// position independent executables, and brings the symbols of the shared libraries it has
+    /// loaded up to date. Breakpoints are resolved again if any of that changed.
+    fn load_libraries(&mut self) {
+        let bias = match self.examined() {
+            Some(target) => solib::program_bias(target, &self.target, &self.debug_data),
+            None => return,
+        };
+        let mut changed = false;
+        if let Some(bias) = bias.filter(|bias| *bias != self.debug_data.bias()) {
+            self.debug_data.relocate(bias);
+            changed = true;
+        }
+        let loaded = match (self.examined(), self.debug_data.dynamic_address()) {
+            (Some(target), Some(dynamic)) => solib::loaded_libraries(target, dynamic),
+            _ => Vec::new(),
+        };
+        changed |= self.debug_data.update_libraries(&loaded);
+        if changed {
+            self.resolve_breakpoints();
+        }
+    }
+
+    /// Prints the shared libraries the inferior has loaded.
+    fn print_shared_libraries(&self) {
+        let libraries = self.debug_data.get_libraries();
+        if libraries.is_empty() {
+            output!("No shared libraries loaded at this time.");
+            return;
+        }
+        output!("From                To                  Syms Read   Shared Object Library");
+        for library in &libraries {
+            output!(
+                "{:#018x}  {:#018x}  {:<11} {}",
+                library.text_range.0,
+                library.text_range.1,
+                if library.has_debug_info { "Yes" } else { "Yes (*)" },
+                library.path
+            );
+        }
+        if libraries.iter().any(|library| !library.has_debug_info) {
+            output!("(*): Shared library is missing debugging information.");
+        }
+    }
+
+    /// Resolves the location of every breakpoint again after the symbols changed, moving the
+    /// ones whose address changed and deleting the ones that can't be resolved any more.
+    fn resolve_breakpoints(&mut self) {
+        let locations: Vec<(usize, String, usize)> = self
+            .breakpoint_info
+            .iter()
+            .map(|(id, bp)| (*id, bp.location.clone(), bp.addr))
+            .collect();
+        for (id, location, old_addr) in locations {
+            match self.resolve_location(&location) {
+                Ok(addr) => self.breakpoint_info.get_mut(&id).unwrap().addr = addr,
+                Err(err) => {
+                    output!("Deleting breakpoint {}: {}", id, err);
+                    self.breakpoint_info.remove(&id);
+                }
+            }
+            self.update_breakpoint(old_addr);
+            if let Some(addr) = self.breakpoint_info.get(&id).map(|bp| bp.addr) {
+                self.update_breakpoint(addr);
+            }
+        }
+    }
+
+    /// Marks every breakpoint as not written into the inferior, once the process it was written
+    /// into has gone or replaced its program.
+    fn forget_breakpoints(&mut self) {
+        for breakpoint in self.breakpoints.values_mut() {
+            *breakpoint = None;
+        }
+    }
+
+    /// Writes the breakpoints that aren't in the inferior yet and the enabled watchpoints into a
+    /// newly started or attached inferior.
+    fn install_breakpoints(&mut self) {
+        let inferior = self.inferior.as_mut().unwrap();
+        for (addr, breakpoint) in self.breakpoints.iter_mut().filter(|(_, bp)| bp.is_none()) {
+            match inferior.write_byte(*addr, 0xcc) {
+                Ok(orig_byte) => {
+                    *breakpoint = Some(Breakpoint { addr: *addr, orig_byte });
+                }
+                Err(err) => output!("Could not install breakpoint at {:#x}: {}", addr, err),
+            }
+        }
+        for watchpoint in self.watchpoints.iter_mut().filter(|w| w.enabled) {
+            if let Err(err) = install_watchpoint(inferior, watchpoint) {
+                output!("Could not install watchpoint {}: {}", watchpoint.expr, err);
+            }
+        }
+    }
+
+    /// Restores the original bytes under all breakpoints and clears the debug registers, leaving
+    /// the inferior as it would be without deet.
+    fn remove_breakpoints(&mut self) {
+        let inferior = self.inferior.as_mut().unwrap();
+        for (addr, breakpoint) in self.breakpoints.iter_mut() {
+            if let Some(bp) = breakpoint.take() {
+                if let Err(err) = inferior.write_byte(*addr, bp.orig_byte) {
+                    output!("Could not remove breakpoint at {:#x}: {}", addr, err);
+                }
+            }
+        }
+        for watchpoint in &self.watchpoints {
+            for slot in &watchpoint.slots {
+                let _ = inferior.clear_hardware_watchpoint(*slot);
+            }
+        }
+        let _ = inferior.clear_watchpoint_hits();
+    }
+
+    /// Detaches from the inferior, which carries on running without breakpoints.
+    fn detach(&mut self) {
+        self.remove_breakpoints();
+        let inferior = self.inferior.take().unwrap();
+        let pid = inferior.pid();
+        match inferior.detach() {
+            Ok(()) => output!("Detached from process {}", pid),
+            Err(err) => output!("Could not detach from process {}: {}", pid, err),
+        }
+    }
+
+    /// Gets rid of the current inferior, if any, and any held ones. A process deet attached to is
+    /// detached so that it keeps running, while one that deet started is killed.
+    fn end_inferior(&mut self) {
+        match &mut self.inferior {
+            Some(inferior) if inferior.is_attached() => self.detach(),
+            Some(inferior) => {
+                if let Err(err) = inferior.kill() {
+                    output!("Could not kill inferior: {}", err);
+                }
+                self.inferior = None;
+            }
+            None => {}
+        }
+        // held inferiors have no breakpoints in them, and go the same way
+        for (_, mut inferior) in self.held_inferiors.drain(..) {
+            let result = if inferior.is_attached() { inferior.detach() } else { inferior.kill() };
+            if let Err(err) = result {
+                output!("Could not end held inferior: {}", err);
+            }
+        }
+        self.inferior_number = 1;
+        self.next_inferior_number = 2;
+        self.forget_breakpoints();
+    }
+
+    /// Enables or disables the given breakpoints and watchpoints, or all of them if ids is empty.
+    fn set_breakpoints_enabled(&mut self, ids: Vec<usize>, enable: bool) {
+        let ids = if ids.is_empty() { self.all_breakpoint_ids() } else { ids };
+        for id in ids {
+            if let Some(bp) = self.breakpoint_info.get_mut(&id) {
+                bp.enabled = enable;
+                let addr = bp.addr;
+                self.update_breakpoint(addr);
+            } else if let Some(watchpoint) = self.watchpoints.iter_mut().find(|w| w.id == id) {
+                watchpoint.enabled = enable;
+                if let Some(inferior) = &mut self.inferior {
+                    let result = if enable {
+                        install_watchpoint(inferior, watchpoint)
+                    } else {
+                        watchpoint
+                            .slots
+                            .iter()
+                            .map(|slot| inferior.clear_hardware_watchpoint(*slot))
+                            .collect()
+                    };
+                    if let Err(err) = result {
+                        output!("Could not update watchpoint {}: {}", id, err);
+                    }
+                }
+            } else if let Some(catchpoint) = self.catchpoints.iter_mut().find(|c| c.id == id) {
+                catchpoint.enabled = enable;
+                self.update_caught_syscalls();
+            } else {
+                output!("No breakpoint number {}.", id);
+            }
+        }
+    }
+
+    /// Sets a breakpoint at location, or a logpoint if there is a message to log.
+    fn set_breakpoint(&mut self, location: String, message: Option<String>) {
+        let addr = match self.resolve_location(&location) {
+            Ok(addr) => addr,
+            Err(err) => {
+                output!("{}", err);
+                if message.is_some() {
+                    output!("Usage: logpoint *address | [file:]line | [file:]func \"<message>\"");
+                } else {
+                    output!("Usage: b | break *address | [file:]line | [file:]func");
+                }
+                return;
+            }
+        };
+        let id = self.next_breakpoint_id;
+        self.next_breakpoint_id += 1;
+        self.breakpoint_info.insert(
+            id,
+            BreakpointInfo {
+                location,
+                addr,
+                enabled: true,
+                condition: None,
+                ignore_count: 0,
+                hit_count: 0,
+                commands: Vec::new(),
+                message: message.clone(),
+            },
+        );
+        let line = self.debug_data.get_line_from_addr(addr);
+        events::emit(Event::BreakpointSet {
+            id,
+            addr,
+            function: self.debug_data.get_function_from_addr(addr),
+            file: line.as_ref().map(|line| line.file.clone()),
+            line: line.map(|line| line.number),
+            message,
+        });
+        self.update_breakpoint(addr);
+    }
+
+    /// Resolves a breakpoint location of the form *address, line, function, file:line or
+    /// file:function to an address. Functions may be given by their path, as in
+    /// crate::module::func.
+    fn resolve_location(&self, location: &str) -> Result<usize, String> {
+        if location.starts_with('*') {
+            return self
+                .parse_address(&location[1..])
+                .ok_or(format!("Invalid address {}", &location[1..]));
+        }
+        let (file, target) = match file_separator(location) {
+            Some(colon) => (Some(&location[..colon]), &location[colon + 1..]),
+            None => (None, location),
+        };
+        let result = if let Ok(line_number) = target.parse::<usize>() {
+            let file = file.map(|file| file.to_string()).or_else(|| self.default_file());
+            self.debug_data.get_addr_for_line(file.as_deref(), line_number)
+        } else {
+            // stop after the prologue, so that the function's variables can be read
+            self.debug_data
+                .get_addr_for_function(file, target)
+                .map(|addr| self.debug_data.get_function_body_addr(addr).unwrap_or(addr))
+        };
+        result.map_err(|err| err.to_string())
+    }
+
+    /// Returns the file that a line number without a file name refers to: the one the inferior
+    /// is stopped in, or else the one that defines main.
+    fn default_file(&self) -> Option<String> {
+        if let Some(target) = self.examined() {
+            if let Ok(regs) = target.registers() {
+                if let Some(line) = self.debug_data.get_line_from_addr(regs.rip as usize) {
+                    return Some(line.file);
+                }
+            }
+        }
+        self.debug_data
+            .get_files()
+            .iter()
+            .find(|file| {
+                file.functions
+                    .iter()
+                    .any(|func| func.name == "main" && func.text_length > 0)
+            })
+            .map(|file| file.name.clone())
+    }
+
+    /// Returns the numbers of all breakpoints, watchpoints and catchpoints.
+    fn all_breakpoint_ids(&self) -> Vec<usize> {
+        let mut ids: Vec<usize> = self.breakpoint_info.keys().cloned().collect();
+        ids.extend(self.watchpoints.iter().map(|w| w.id));
+        ids.extend(self.catchpoints.iter().map(|c| c.id));
+        ids
+    }
+
+    /// Installs or removes the 0xcc byte at addr so that it is present exactly when an enabled
+    /// breakpoint is set there.
+    fn update_breakpoint(&mut self, addr: usize) {
+        let wanted = self.breakpoint_info.values().any(|bp| bp.addr == addr && bp.enabled);
+        match (wanted, self.breakpoints.get(&addr).cloned()) {
+            (true, None) => {
+                if let Some(inferior) = &mut self.inferior {
+                    match inferior.write_byte(addr, 0xcc) {
+                        Ok(orig_byte) => {
+                            self.breakpoints.insert(addr, Some(Breakpoint { addr, orig_byte }));
+                        }
+                        Err(err) => output!("Could not insert breakpoint at {:#x}: {}", addr, err),
+                    }
+                } else {
+                    self.breakpoints.insert(addr, None);
+                }
+            }
+            (false, Some(installed)) => {
+                if let (Some(inferior), Some(bp)) = (&mut self.inferior, installed) {
+                    if let Err(err) = inferior.write_byte(addr, bp.orig_byte) {
+                        output!("Could not remove breakpoint at {:#x}: {}", addr, err);
+                    }
+                }
+                self.breakpoints.remove(&addr);
+            }
+            _ => {}
+        }
+    }
+
+    /// Resumes the inferior until it stops somewhere the user should hear about, continuing
+    /// through breakpoints whose condition is false or that are being ignored.
+    fn continue_inferior(&mut self) -> Result<Status, nix::Error> {
+        loop {
+            let inferior = self.inferior.as_mut().unwrap();
+            let status = inferior.continue_exec(&self.breakpoints)?;
+            if let Status::Exec(_) = status {
+                self.follow_exec();
+                continue;
+            }
+            if let Status::Stopped(Signal::SIGTRAP, rip) = status {
+                if self.breakpoints.contains_key(&rip) && !self.should_stop_at_breakpoint(rip) {
+                    continue;
+                }
+            }
+            return Ok(status);
+        }
+    }
+
+    /// Counts a hit on every enabled breakpoint at addr whose condition holds, and returns
+    /// whether any of them should stop the inferior. Logpoints log their message instead.
+    fn should_stop_at_breakpoint(&mut self, addr: usize) -> bool {
+        let ids: Vec<usize> = self
+            .breakpoint_info
+            .iter()
+            .filter(|(_, bp)| bp.addr == addr && bp.enabled)
+            .map(|(id, _)| *id)
+            .collect();
+        let mut stop = false;
+        for id in ids {
+            if let Some(condition) = self.breakpoint_info[&id].condition.clone() {
+                match self.evaluate(&condition, 0, |evaluator, value| evaluator.is_true(&value)) {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(err) => {
+                        output!("Error in testing condition for breakpoint {}:\n{}", id, err)
+                    }
+                }
+            }
+            let bp = self.breakpoint_info.get_mut(&id).unwrap();
+            bp.hit_count += 1;
+            if bp.ignore_count > 0 {
+                bp.ignore_count -= 1;
+                continue;
+            }
+            if let Some(message) = bp.message.clone() {
+                self.log(id, &message);
+                continue;
+            }
+            self.report_thread_switch();
+            output!("Breakpoint {}", id);
+            self.hit_breakpoints.push(id);
+            stop = true;
+        }
+        stop
+    }
+
+    /// Logs the message of logpoint id, with its expressions filled in and the time since the
+    /// inferior started.
+    fn log(&mut self, id: usize, message: &str) {
+        *self.logpoint_hits.entry(id).or_insert(0) += 1;
+        let message = self.format_log_message(message);
+        let time = self.run_started.elapsed().as_secs_f64();
+        match &mut self.log_file {
+            Some((path, file)) => {
+                if let Err(err) = writeln!(file, "[{:.6}] {}", time, message) {
+                    output!("Could not write to {}: {}", path, err);
+                }
+            }
+            None => events::emit(Event::Log { logpoint: id, time, message }),
+        }
+    }
+
+    /// Fills in the {expr} parts of a logpoint's message with the values of the expressions in
+    /// the innermost frame. {{ and }} stand for braces.
+    fn format_log_message(&mut self, message: &str) -> String {
+        let mut formatted = String::new();
+        let mut chars = message.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' | '}' if chars.peek() == Some(&c) => {
+                    chars.next();
+                    formatted.push(c);
+                }
+                '{' => {
+                    let expr: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                    match self.evaluate(&expr, 0, |evaluator, value| evaluator.format(&value)) {
+                        Ok(value) => formatted += &value,
+                        Err(err) => formatted += &format!("<{}>", err),
+                    }
+                }
+                c => formatted.push(c),
+            }
+        }
+        formatted
+    }
+
+    /// Says how many times each logpoint was hit while the inferior that just ended ran.
+    fn report_logpoint_hits(&mut self) {
+        let hits = std::mem::take(&mut self.logpoint_hits);
+        let logpoints: Vec<(&usize, &BreakpointInfo)> =
+            self.breakpoint_info.iter().filter(|(_, bp)| bp.message.is_some()).collect();
+        if logpoints.is_empty() {
+            return;
+        }
+        output!("Logpoint hits:");
+        for (id, bp) in logpoints {
+            let count = hits.get(id).cloned().unwrap_or(0);
+            output!(
+                "  {} at {}: {} time{}",
+                id,
+                bp.location,
+                count,
+                if count == 1 { "" } else { "s" }
+            );
+        }
+    }
+
+    /// Lists breakpoints and watchpoints along with where they are and how often they were hit.
+    fn print_breakpoints(&self) {
+        if self.breakpoint_info.is_empty()
+            && self.watchpoints.is_empty()
+            && self.catchpoints.is_empty()
+        {
+            output!("No breakpoints or watchpoints.");
+            return;
+        }
+        output!("{:<8}{:<15}{:<4}{:<19}{}", "Num", "Type", "Enb", "Address", "What");
+        for (id, bp) in &self.breakpoint_info {
+            let func = self.debug_data.get_function_from_addr(bp.addr);
+            let line = self.debug_data.get_line_from_addr(bp.addr);
+            let what = match (func, line) {
+                (Some(func), Some(line)) => format!("in {} at {}", func, line),
+                (Some(func), None) => format!("in {}", func),
+                _ => String::new(),
+            };
+            let kind = if bp.message.is_some() { "logpoint" } else { "breakpoint" };
+            output!(
+                "{:<8}{:<15}{:<4}{:<#19x}{}",
+                id,
+                kind,
+                if bp.enabled { "y" } else { "n" },
+                bp.addr,
+                what
+            );
+            if let Some(message) = &bp.message {
+                output!("\tlog \"{}\"", message);
+            }
+            if let Some(condition) = &bp.condition {
+                output!("\tstop only if {}", condition);
+            }
+            if bp.hit_count > 0 {
+                output!(
+                    "\t{} already hit {} time{}",
+                    kind,
+                    bp.hit_count,
+                    if bp.hit_count == 1 { "" } else { "s" }
+                );
+            }
+            if bp.ignore_count > 0 {
+                output!("\tWill ignore next {} crossings of breakpoint.", bp.ignore_count);
+            }
+            for command in &bp.commands {
+                output!("        {}", command);
+            }
+        }
+        for watchpoint in &self.watchpoints {
+            output!(
+                "{:<8}{:<15}{:<4}{:<19}{}",
+                watchpoint.id,
+                if watchpoint.read { "acc watchpoint" } else { "hw watchpoint" },
+                if watchpoint.enabled { "y" } else { "n" },
+                "",
+                watchpoint.expr
+            );
+        }
+        for catchpoint in &self.catchpoints {
+            output!(
+                "{:<8}{:<15}{:<4}{:<19}{}",
+                catchpoint.id,
+                "catchpoint",
+                if catchpoint.enabled { "y" } else { "n" },
+                "",
+                describe_syscalls(&catchpoint.syscalls)
+            );
+            if catchpoint.hit_count > 0 {
+                output!(
+                    "\tcatchpoint already hit {} time{}",
+                    catchpoint.hit_count,
+                    if catchpoint.hit_count == 1 { "" } else { "s" }
+                );
+            }
+        }
+    }
+
+    /// Prints how the inferior stopped after being resumed. If it terminated, the inferior is
+    /// dropped.
+    fn report_status(&mut self, result: Result<Status, nix::Error>) {
+        self.collect_held_inferiors();
+        let hit_breakpoints = std::mem::take(&mut self.hit_breakpoints);
+        match result {
+            Ok(Status::Exited(exit_status_code)) => {
+                self.inferior = None;
+                self.forget_breakpoints();
+                events::emit(Event::Exited(exit_status_code));
+                self.report_logpoint_hits();
+            }
+            Ok(Status::Signaled(signal)) => {
+                self.inferior = None;
+                self.forget_breakpoints();
+                events::emit(Event::Signaled(signal.to_string()));
+                self.report_logpoint_hits();
+            }
+            Ok(Status::Stopped(signal, rip)) => {
+                self.selected_frame = 0;
+                self.load_libraries();
+                self.report_thread_switch();
+                let line = self.debug_data.get_line_from_addr(rip);
+                events::emit(Event::Stopped {
+                    signal: signal.to_string(),
+                    addr: rip,
+                    thread: Some(self.inferior.as_ref().unwrap().thread_number()),
+                    breakpoints: hit_breakpoints.clone(),
+                    function: self.debug_data.get_function_from_addr(rip),
+                    file: line.as_ref().map(|line| line.file.clone()),
+                    line: line.map(|line| line.number),
+                });
+                if signal == Signal::SIGTRAP {
+                    self.report_watchpoint_hits();
+                }
+                if let Some(line) = self.debug_data.get_line_from_addr(rip) {
+                    output!("Stopped at {} : {}", line.file, line.number);
+                }
+                self.show_source_context(rip);
+                self.show_displays();
+                // run the commands of the breakpoints that were hit before anything else
+                let commands = hit_breakpoints
+                    .iter()
+                    .filter_map(|id| self.breakpoint_info.get(id))
+                    .flat_map(|bp| bp.commands.clone())
+                    .collect();
+                self.run_next(commands);
+            }
+            Ok(Status::Syscall(stop, rip)) => {
+                self.selected_frame = 0;
+                self.load_libraries();
+                self.report_thread_switch();
+                self.report_syscall(&stop, rip);
+                self.show_displays();
+            }
+            Ok(Status::Exec(rip)) => {
+                self.follow_exec();
+                self.report_status(Ok(Status::Stopped(Signal::SIGTRAP, rip)));
+            }
+            Err(err) => output!("Inferior can't be woken up and execute: {}", err),
+        }
+    }
+
+    /// Reports a stop at a syscall to the catchpoint that caught it, and shows the source line
+    /// that made the syscall, selecting its frame.
+    fn report_syscall(&mut self, stop: &SyscallStop, rip: usize) {
+        let mut catchpoint = 0;
+        for candidate in self.catchpoints.iter_mut().filter(|c| c.enabled).rev() {
+            if candidate.syscalls.is_empty() || candidate.syscalls.contains(&stop.number) {
+                candidate.hit_count += 1;
+                catchpoint = candidate.id;
+            }
+        }
+        let inferior = self.inferior.as_ref().unwrap();
+        let call = syscalls::format_call(inferior, stop);
+        let thread = Some(inferior.thread_number());
+        let caller = self.source_frame();
+        let pc = caller.as_ref().map_or(rip, |(index, frame)| frame_pc(*index, frame));
+        let line = self.debug_data.get_line_from_addr(pc);
+        events::emit(Event::Syscall {
+            catchpoint,
+            number: stop.number,
+            call,
+            result: stop.result,
+            addr: rip,
+            thread,
+            function: self.debug_data.get_function_from_addr(pc),
+            file: line.as_ref().map(|line| line.file.clone()),
+            line: line.map(|line| line.number),
+        });
+        if let Some((index, frame)) = caller {
+            self.selected_frame = index;
+            self.print_frame(index, &frame);
+            self.show_source_context(pc);
+        }
+    }
+
+    /// Returns the innermost stack frame that has source line information, along with its
+    /// index. That is the program's own code that called into a library.
+    fn source_frame(&mut self) -> Option<(usize, Frame)> {
+        let frames = self.stack_frames().ok()?;
+        let debug_data = &self.debug_data;
+        frames
+            .into_iter()
+            .enumerate()
+            .find(|(index, frame)| debug_data.get_line_from_addr(frame_pc(*index, frame)).is_some())
+    }
+
+    /// Runs the program to completion, printing every syscall it makes along with the source
+    /// line that made it, the way strace does. Signals the program receives are passed on.
+    pub fn trace_syscalls(&mut self, args: &[String]) {
+        let mut inferior = match Inferior::new(&self.target, args) {
+            Some(inferior) => inferior,
+            None => {
+                output!("Error starting subprocess");
+                return;
+            }
+        };
+        self.configure_inferior(&mut inferior);
+        inferior.set_caught_syscalls(Some(Vec::new()));
+        self.inferior = Some(inferior);
+        // exec is decoded at its entry, while the memory its arguments point to is still there
+        let mut entries: HashMap<Pid, (String, String)> = HashMap::new();
+        loop {
+            match self.continue_inferior() {
+                Ok(Status::Syscall(stop, _)) => {
+                    self.load_libraries();
+                    let inferior = self.inferior.as_ref().unwrap();
+                    let tid = inferior.tid();
+                    let call = syscalls::format_call(inferior, &stop);
+                    let location = self.source_frame().map_or(String::new(), |(index, frame)| {
+                        let pc = frame_pc(index, &frame);
+                        let func = self.debug_data.get_function_from_addr(pc).unwrap_or_default();
+                        let line = self.debug_data.get_line_from_addr(pc).unwrap();
+                        format!("  [{} at {}]", func, line)
+                    });
+                    match stop.result {
+                        None if syscalls::is_exit(stop.number) => {
+                            output!("{} = ?{}", call, location);
+                        }
+                        None if syscalls::is_exec(stop.number) => {
+                            entries.insert(tid, (call, location));
+                        }
+                        None => {}
+                        Some(result) => {
+                            let (call, location) = entries.remove(&tid).unwrap_or((call, location));
+                            let result = syscalls::format_result(stop.number, result);
+                            output!("{} = {}{}", call, result, location);
+                        }
+                    }
+                }
+                Ok(Status::Stopped(signal, _)) => {
+                    output!("--- {} ---", signal);
+                    if signal != Signal::SIGTRAP {
+                        self.inferior.as_mut().unwrap().set_pending_signal(Some(signal));
+                    }
+                }
+                Ok(status) => {
+                    match status {
+                        Status::Exited(code) => output!("+++ exited with {} +++", code),
+                        Status::Signaled(signal) => output!("+++ killed by {} +++", signal),
+                        _ => {}
+                    }
+                    self.inferior = None;
+                    return;
+                }
+                Err(err) => {
+                    output!("Inferior can't be woken up and execute: {}", err);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Says so if the inferior stopped in a different thread than the user last heard about.
+    fn report_thread_switch(&mut self) {
+        if let Some((number, tid)) = self.inferior.as_mut().unwrap().take_thread_switch() {
+            output!("[Switching to thread {} (LWP {})]", number, tid);
+        }
+    }
+
+    /// Returns what print, backtrace and x look at: the inferior if there is one, or else the
+    /// loaded core dump.
+    fn examined(&self) -> Option<&dyn Target> {
+        match (&self.inferior, &self.core) {
+            (Some(inferior), _) => Some(inferior),
+            (None, Some(core)) => Some(core),
+            (None, None) => None,
+        }
+    }
+
+    /// Unwinds the examined program's stack. The call frame information loaded last time is
+    /// reused unless the memory map changed.
+    fn stack_frames(&mut self) -> Result<Vec<Frame>, nix::Error> {
+        let maps = self.examined().unwrap().memory_map();
+        if self.unwinder.as_ref().map_or(true, |(loaded_for, _)| *loaded_for != maps) {
+            let unwinder = Unwinder::new(&maps);
+            self.unwinder = Some((maps, unwinder));
+        }
+        let target = self.examined().unwrap();
+        let regs = FrameRegisters::from_regs(&target.registers()?);
+        let unwinder = &self.unwinder.as_ref().unwrap().1;
+        Ok(unwinder.backtrace(regs, |addr| target.read_word(addr).ok()))
+    }
+
+    /// Returns the address the current function will return to, along with the value %rsp will
+    /// have once it has returned, which is the function's canonical frame address.
+    fn return_address(&mut self) -> Result<(usize, usize), String> {
+        let frames = self.stack_frames().map_err(|err| err.to_string())?;
+        match (frames.get(0).and_then(|frame| frame.cfa), frames.get(1)) {
+            (Some(cfa), Some(caller)) => Ok((caller.pc, cfa)),
+            _ => Err("Cannot find the caller of the current function.".to_string()),
+        }
+    }
+
+    /// Returns the code address and frame base to look up variables with in the given frame.
+    fn frame_context(&mut self, index: usize) -> Result<(usize, usize), String> {
+        let frames = self.stack_frames().map_err(|err| err.to_string())?;
+        let frame = frames.get(index).ok_or("No stack.")?;
+        let pc = frame_pc(index, frame);
+        Ok((pc, frame_base(frame, self.debug_data.get_function(pc))))
+    }
+
+    /// Prints a line of a backtrace: the frame's function with its arguments, and where it is.
+    fn print_frame(&self, index: usize, frame: &Frame) {
+        output!("#{:<3}{}", index, self.describe_frame(index, frame));
+    }
+
+    /// Describes a stack frame as its function, arguments and source line.
+    fn describe_frame(&self, index: usize, frame: &Frame) -> String {
+        let inferior = self.examined().unwrap();
+        let pc = frame_pc(index, frame);
+        let func = self.debug_data.get_function(pc);
+        let mut description = String::new();
+        if index > 0 || func.is_none() {
+            description += &format!("{:#018x} in ", frame.pc);
+        }
+        match func {
+            Some(func) => {
+                let args: Vec<String> = func
+                    .variables
+                    .iter()
+                    .filter(|var| var.is_parameter)
+                    .map(|var| {
+                        let addr = value::variable_address(var, frame_base(frame, Some(func)));
+                        let value_type = &var.entity_type;
+                        match inferior.read_bytes(addr, value_type.size) {
+                            Ok(bytes) => format!(
+                                "{}={}",
+                                var.name,
+                                value::format_value(inferior, &self.debug_data, value_type, &bytes)
+                            ),
+                            Err(_) => format!("{}=<unavailable>", var.name),
+                        }
+                    })
+                    .collect();
+                description += &format!("{} ({})", func.name, args.join(", "));
+                if let Some(line) = self.debug_data.get_line_from_addr(pc) {
+                    description += &format!(" at {}", line);
+                }
+            }
+            None => description += "?? ()",
+        }
+        description
+    }
+
+    /// Lists the threads of the inferior along with the frame each is in.
+    fn print_threads(&mut self) {
+        let inferior = self.inferior.as_mut().unwrap();
+        let (threads, selected) = (inferior.threads(), inferior.thread_number());
+        output!("  Id   Target Id         Frame");
+        for (number, tid) in threads {
+            self.inferior.as_mut().unwrap().select_thread(number);
+            let frame = match self.stack_frames() {
+                Ok(frames) => self.describe_frame(0, &frames[0]),
+                Err(err) => format!("<unavailable: {}>", err),
+            };
+            let marker = if number == selected { '*' } else { ' ' };
+            output!("{} {:<4} {:<17} {}", marker, number, format!("LWP {}", tid), frame);
+        }
+        self.inferior.as_mut().unwrap().select_thread(selected);
+    }
+
+    /// Prints the old and new values of any watchpoints that triggered the last stop.
+    fn report_watchpoint_hits(&mut self) {
+        let inferior = self.inferior.as_mut().unwrap();
+        let debug_data = &self.debug_data;
+        let hits = match inferior.watchpoint_hits() {
+            Ok(hits) => hits,
+            Err(_) => return,
+        };
+        for watchpoint in self.watchpoints.iter_mut() {
+            if !watchpoint.enabled || !watchpoint.slots.iter().any(|slot| hits.contains(slot)) {
+                continue;
+            }
+            let new_value = match inferior.read_bytes(watchpoint.addr, watchpoint.len) {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    output!("Watchpoint {}: cannot read {}: {}", watchpoint.id, watchpoint.expr, err);
+                    continue;
+                }
+            };
+            let format = |bytes: &[u8]| {
+                value::format_value(inferior, debug_data, &watchpoint.value_type, bytes)
+            };
+            output!("\nHardware watchpoint {}: {}\n", watchpoint.id, watchpoint.expr);
+            if new_value != watchpoint.old_value {
+                output!("Old value = {}", format(&watchpoint.old_value));
+                output!("New value = {}", format(&new_value));
+            } else {
+                output!("Value = {}", format(&new_value));
+            }
+            watchpoint.old_value = new_value;
+        }
+        let _ = inferior.clear_watchpoint_hits();
+    }
+
+    /// Reads a value of the given type from the inferior and prints it.
+    fn print_value(&self, inferior: &dyn Target, name: &str, addr: usize, value_type: &Type) {
+        match inferior.read_bytes(addr, value_type.size) {
+            Ok(bytes) => output!(
+                "{} = {}",
+                name,
+                value::format_value(inferior, &self.debug_data, value_type, &bytes)
+            ),
+            Err(_) => output!("{} = <error: Cannot access memory at address {:#x}>", name, addr),
+        }
+    }
+
+    /// This function returns the next command to run, from a script or the user, and continues
+    /// re-prompting until it gets a valid command. It uses DebuggerCommand::from_tokens to do the
+    /// command parsing. User-defined commands are expanded into the lines that define them.
+    fn get_next_command(&mut self) -> DebuggerCommand {
+        loop {
+            let line = match self.next_line("(deet) ") {
+                Some(line) => line,
+                None => return DebuggerCommand::Quit,
+            };
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens.is_empty() || tokens[0].starts_with('#') {
+                continue;
+            }
+            if let Some(cmd) = DebuggerCommand::from_tokens(&tokens) {
+                return cmd;
+            } else if let Some(body) = self.user_commands.get(tokens[0]) {
+                match substitute_arguments(body, &tokens[1..]) {
+                    Ok(lines) => self.run_next(lines),
+                    Err(err) => events::emit(Event::Error(err)),
+                }
+            } else {
+                events::emit(Event::Error("Unrecognized command.".to_string()));
+            }
+        }
+    }
+
+    /// Returns the next line queued to run, or else prompts the user for one. Returns None once
+    /// the input has ended.
+    fn next_line(&mut self, prompt: &str) -> Option<String> {
+        if let Some(line) = self.pending_lines.pop_front() {
+            return Some(line);
+        }
+        if events::is_json() {
+            return next_request();
+        }
+        loop {
+            // Print prompt and get next line of user input
+            match self.readline.readline(prompt) {
+                Err(ReadlineError::Interrupted) => {
+                    // User pressed ctrl+c. We're going to ignore it
+                    output!("Type \"quit\" to exit");
+                }
+                Err(ReadlineError::Eof) => {
+                    // User pressed ctrl+d, which is the equivalent of "quit" for our purposes
+                    return None;
+                }
+                Err(err) => {
+                    panic!("Unexpected I/O error: {:?}", err);
+                }
+                Ok(line) => {
+                    if line.trim().len() == 0 {
+                        continue;
+                    }
+                    self.readline.add_history_entry(line.as_str());
+                    if let Err(err) = self.readline.save_history(&self.history_path) {
+                        output!(
+                            "Warning: failed to save history file at {}: {}",
+                            self.history_path, err
+                        );
+                    }
+                    return Some(line);
+                }
+            }
+        }
+    }
+
+    fn parse_address(&self, addr: &str) -> Option<usize> {
+        let addr_without_0x = if addr.to_lowercase().starts_with("0x") {
+            &addr[2..]
+        } else {
+            &addr
+        };
+        usize::from_str_radix(addr_without_0x, 16).ok()
+    }
+
+}
+
+/// Returns an address within the instruction a frame is executing: for callers, the return
+/// address may already belong to the next line or function, so the call instruction is used.
+fn frame_pc(index: usize, frame: &Frame) -> usize {
+    if index == 0 {
+        frame.pc
+    } else {
+        frame.pc - 1
+    }
+}
+
+/// Describes the syscalls a catchpoint catches, like gdb does.
+fn describe_syscalls(numbers: &[usize]) -> String {
+    let syscalls: Vec<String> = numbers
+        .iter()
+        .map(|number| format!("'{}' [{}]", syscalls::describe(*number), number))
+        .collect();
+    match syscalls.len() {
+        0 => "any syscall".to_string(),
+        1 => format!("syscall {}", syscalls[0]),
+        _ => format!("syscalls {}", syscalls.join(" ")),
+    }
+}
+
+/// Returns whether evaluating an expression would call a function in the program.
+fn makes_calls(expr: &mut Expr) -> bool {
+    match expr {
+        Expr::Call(_, _) => true,
+        Expr::SizeofExpr(_) => false,
+        _ => expr.operands_mut().into_iter().any(makes_calls),
+    }
+}
+
+/// Explains that a function call was abandoned because the program being debugged stopped,
+/// was signaled or exited during it.
+fn abandoned_call(call: &FunctionCall, what_happened: &str, restored: bool) -> String {
+    let mut message = format!(
+        "The program being debugged {} while in a function called from deet.\n",
+        what_happened
+    );
+    if restored {
+        message.push_str("deet has restored the context to what it was before the call.\n");
+    }
+    message.push_str(&format!(
+        "Evaluation of the expression containing the function\n({}) will be abandoned.",
+        call.name
+    ));
+    message
+}
+
+/// Reads the lines of a command script.
+fn read_script(path: &str) -> std::io::Result<Vec<String>> {
+    Ok(fs::read_to_string(path)?.lines().map(|line| line.to_string()).collect())
+}
+
+/// Returns the body of a user-defined command with $arg0, $arg1, ... replaced by the arguments
+/// it was given and $argc by how many there are, or an error naming an argument that is missing.
+fn substitute_arguments(body: &[String], args: &[&str]) -> Result<Vec<String>, String> {
+    body.iter()
+        .map(|line| {
+            let mut substituted = String::new();
+            let mut rest = line.as_str();
+            while let Some(start) = rest.find("$arg") {
+                substituted += &rest[..start];
+                rest = &rest[start + 4..];
+                // the whole number is the index, so that $arg10 isn't taken for $arg1
+                let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                if digits > 0 {
+                    let arg = rest[..digits].parse::<usize>().ok().and_then(|i| args.get(i));
+                    substituted += arg.ok_or_else(|| {
+                        format!("Missing argument {} in user function.", &rest[..digits])
+                    })?;
+                    rest = &rest[digits..];
+                } else if rest.starts_with('c') {
+                    substituted += &args.len().to_string();
+                    rest = &rest[1..];
+                } else {
+                    substituted += "$arg";
+                }
+            }
+            substituted += rest;
+            Ok(substituted)
+        })
+        .collect()
+}
+
+/// Returns where the colon between the file and the line or function of a location is, skipping
+/// the ::s of paths like crate::module::func.
+fn file_separator(location: &str) -> Option<usize> {
+    let bytes = location.as_bytes();
+    (0..bytes.len()).rev().find(|&i| {
+        bytes[i] == b':'
+            && (i == 0 || bytes[i - 1] != b':')
+            && bytes.get(i + 1).map_or(true, |next| *next != b':')
+    })
+}
+
+/// Returns the address that the local variables of func, running in frame, are found relative
+/// to. gcc makes that the canonical frame address, while rustc uses a register.
+fn frame_base(frame: &Frame, func: Option<&Function>) -> usize {
+    if let Some(register) = func.and_then(|func| func.frame_base) {
+        return frame.regs.get(register as usize).unwrap_or(0);
+    }
+    // without call frame information, assume a standard frame with a frame pointer
+    frame
+        .cfa
+        .unwrap_or_else(|| frame.regs.get(unwind::RBP).unwrap_or(0) + 16)
+}
+
+/// Loads the debugging symbols of target, printing what went wrong if that isn't possible. A
+/// program without DWARF data can still be debugged with the functions in its symbol table.
+fn load_debug_data(target: &str, debug_dir: &str) -> Option<DwarfData> {
+    match DwarfData::from_file(target, debug_dir) {
+        Ok(debug_data) => {
+            if !debug_data.has_debug_info() {
+                output!("(No debugging symbols found in {})", target);
+                output!("Line numbers and variables are unavailable.");
+            }
+            Some(debug_data)
+        }
+        Err(DwarfError::ErrorOpeningFile) => {
+            output!("Could not open file {}", target);
+            None
+        }
+        Err(DwarfError::DwarfFormatError(err)) => {
+            output!("Could not load debugging symbols from {}: {:?}", target, err);
+            None
+        }
+    }
+}
+
+/// Splits len bytes at addr into the naturally aligned 1, 2, 4 or 8 byte chunks that a debug
+/// register can watch.
+fn watch_chunks(mut addr: usize, mut len: usize) -> Vec<(usize, usize)> {
+    let mut chunks = Vec::new();
+    while len > 0 {
+        let chunk_len = [8, 4, 2, 1]
+            .iter()
+            .cloned()
+            .find(|size| addr % size == 0 && *size <= len)
+            .unwrap();
+        chunks.push((addr, chunk_len));
+        addr += chunk_len;
+        len -= chunk_len;
+    }
+    chunks
+}
+
+/// Programs the watchpoint's debug register slots in the inferior and records its current value.
+fn install_watchpoint(
+    inferior: &mut Inferior,
+    watchpoint: &mut Watchpoint,
+) -> Result<(), nix::Error> {
+    for (slot, (addr, len)) in watchpoint
+        .slots
+        .iter()
+        .zip(watch_chunks(watchpoint.addr, watchpoint.len))
+    {
+        inferior.set_hardware_watchpoint(*slot, addr, len, watchpoint.read)?;
+    }
+    watchpoint.old_value = inferior.read_bytes(watchpoint.addr, watchpoint.len)?;
+    Ok(())
+}
+
+/// Reads the next JSON request from stdin and returns its command line. The previous request is
+/// finished first, since deet only reads on once it is done with it. Returns None at the end of
+/// the input.
+fn next_request() -> Option<String> {
+    events::finish_request();
+    loop {
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) => return None,
+            Ok(_) => {}
+            Err(err) => panic!("Unexpected I/O error: {:?}", err),
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = match Json::parse(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                events::begin_request(Json::Null);
+                events::emit(Event::Error(format!("Invalid request: {}", err)));
+                continue;
+            }
+        };
+        events::begin_request(request.get("id").cloned().unwrap_or(Json::Null));
+        let command = match request.get("command").and_then(Json::as_str) {
+            Some(command) => command.to_string(),
+            None => {
+                events::emit(Event::Error("Request has no command".to_string()));
+                continue;
+            }
+        };
+        // arguments can also be given separately, which saves the client from quoting them
+        let args: Vec<&str> = match request.get("args") {
+            Some(Json::Array(args)) => args.iter().filter_map(Json::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if args.is_empty() {
+            return Some(command);
+        }
+        return Some(format!("{} {}", command, args.join(" ")));
+    }
 }
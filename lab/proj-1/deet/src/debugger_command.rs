@@ -1,9 +1,115 @@
+use std::fmt;
+
+/// How x shows memory, from the /<count><format><size> suffix.
+#[derive(Clone)]
+pub struct ExamineFormat {
+    pub count: usize,
+    /// One of gdb's format letters: x, d, u, o, t, a, c, f, s or i.
+    pub format: char,
+    /// Size in bytes of each unit, if given.
+    pub size: Option<usize>,
+}
+
+impl fmt::Display for ExamineFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/")?;
+        if self.count != 1 {
+            write!(f, "{}", self.count)?;
+        }
+        write!(f, "{}", self.format)?;
+        match self.size {
+            Some(1) => write!(f, "b"),
+            Some(2) => write!(f, "h"),
+            Some(4) => write!(f, "w"),
+            Some(8) => write!(f, "g"),
+            _ => Ok(()),
+        }
+    }
+}
+
 pub enum DebuggerCommand {
     Quit,
     Run(Vec<String>),
     Continue,
     Backtrace,
     Breakpoint(String),
+    /// Sets a logpoint at a location, which logs a message with the values of the expressions
+    /// in braces in it whenever it is hit, and carries on without stopping.
+    Logpoint(String, String),
+    Step,
+    Next,
+    StepInstruction,
+    NextInstruction,
+    Finish,
+    Print(String),
+    /// Evaluates an expression, usually a function call, and prints its value unless it is void.
+    Call(String),
+    InfoLocals,
+    /// Contains the watched expression, an optional length, and whether reads also trigger it.
+    Watch(String, Option<usize>, bool),
+    /// Stops at the entry and exit of the syscalls given by name or number, or of any syscall.
+    CatchSyscall(Vec<String>),
+    InfoBreakpoints,
+    /// Breakpoint commands take a list of breakpoint numbers; an empty list means all of them.
+    Delete(Vec<usize>),
+    Disable(Vec<usize>),
+    Enable(Vec<usize>),
+    Condition(usize, Option<String>),
+    Ignore(usize, usize),
+    /// Contains the format and the address expression.
+    Examine(ExamineFormat, String),
+    /// Disassembles a function, the function containing an address, or an address range given
+    /// as "start,end" or "start,+length".
+    Disassemble(Option<String>),
+    /// Adds an expression to show every time the program stops, optionally examined with a
+    /// format like x. With no expression, shows them all now.
+    Display(Option<ExamineFormat>, Option<String>),
+    Undisplay(Vec<usize>),
+    InfoDisplay,
+    /// Lists source lines around a location, after the last lines listed, or before them ("-").
+    List(Option<String>),
+    /// Adds a directory to search for source files in, or forgets them all.
+    Directory(Option<String>),
+    /// Shows the given registers, or all of them.
+    InfoRegisters(Vec<String>),
+    /// Evaluates an assignment to a variable, memory ({type} addr), a $register or a convenience
+    /// variable.
+    Set(String),
+    /// Changes one of deet's settings, like follow-fork-mode, to the given value.
+    SetOption(String, String),
+    /// Writes a core dump of the inferior, to the given file or core.<pid>.
+    Gcore(Option<String>),
+    Attach(i32),
+    Detach,
+    InfoThreads,
+    /// Lists the inferior and the processes forked off it that deet is holding on to.
+    InfoInferiors,
+    /// Lists the shared libraries the inferior has loaded.
+    InfoSharedLibrary,
+    /// Switches to the inferior with the given number.
+    Inferior(usize),
+    /// Selects the thread with the given number, or shows the current one.
+    Thread(Option<usize>),
+    /// Changes what happens when the inferior receives the given signals, according to gdb's
+    /// keywords (stop, nostop, print, noprint, pass, nopass). Contains signals and keywords mixed.
+    Handle(Vec<String>),
+    /// Shows what happens for the given signal, or for all of them.
+    InfoSignals(Option<String>),
+    /// Continues the inferior with a signal, or with none for "0".
+    Signal(String),
+    /// Selects the given stack frame, or shows the selected one.
+    Frame(Option<usize>),
+    /// Moves the selected frame by that many frames towards the outermost (up) or innermost
+    /// (down) frame.
+    Up(usize),
+    Down(usize),
+    /// Runs the commands in a file.
+    Source(String),
+    /// Defines a command with the given name, whose lines follow up to "end".
+    Define(String),
+    /// Sets the commands run when the given breakpoint, or the last one set, stops the
+    /// inferior. They follow up to "end".
+    Commands(Option<usize>),
 }
 
 impl DebuggerCommand {
@@ -19,8 +125,170 @@ impl DebuggerCommand {
             "c"  | "cont" | "continue" => Some(DebuggerCommand::Continue), 
             "bt" | "back" | "backtrace" => Some(DebuggerCommand::Backtrace),
             "b"  | "break" => Some(DebuggerCommand::Breakpoint(tokens[1].to_string())),
+            "logpoint" => {
+                let location = tokens.get(1)?.to_string();
+                // the message is quoted, since it may have spaces in it
+                let message = tokens[2..].join(" ");
+                let message = message.strip_prefix('"')?.strip_suffix('"')?;
+                Some(DebuggerCommand::Logpoint(location, message.to_string()))
+            }
+            "s"  | "step" => Some(DebuggerCommand::Step),
+            "n"  | "next" => Some(DebuggerCommand::Next),
+            "si" | "stepi" => Some(DebuggerCommand::StepInstruction),
+            "ni" | "nexti" => Some(DebuggerCommand::NextInstruction),
+            "fin" | "finish" => Some(DebuggerCommand::Finish),
+            // without an expression, print shows the last value again
+            "p"  | "print" => Some(DebuggerCommand::Print(if tokens.len() > 1 {
+                tokens[1..].join(" ")
+            } else {
+                "$".to_string()
+            })),
+            "call" => Some(DebuggerCommand::Call(tokens[1..].join(" "))),
+            "watch" | "awatch" => Some(DebuggerCommand::Watch(
+                tokens.get(1)?.to_string(),
+                match tokens.get(2) {
+                    Some(len) => Some(len.parse().ok()?),
+                    None => None,
+                },
+                tokens[0] == "awatch",
+            )),
+            "catch" => match *tokens.get(1)? {
+                "syscall" => Some(DebuggerCommand::CatchSyscall(
+                    tokens[2..].iter().map(|syscall| syscall.to_string()).collect(),
+                )),
+                _ => None,
+            },
+            "d" | "delete" => Some(DebuggerCommand::Delete(parse_numbers(&tokens[1..])?)),
+            "disable" => Some(DebuggerCommand::Disable(parse_numbers(&tokens[1..])?)),
+            "enable" => Some(DebuggerCommand::Enable(parse_numbers(&tokens[1..])?)),
+            "condition" => Some(DebuggerCommand::Condition(
+                tokens.get(1)?.parse().ok()?,
+                if tokens.len() > 2 {
+                    Some(tokens[2..].join(" "))
+                } else {
+                    None
+                },
+            )),
+            "ignore" => Some(DebuggerCommand::Ignore(
+                tokens.get(1)?.parse().ok()?,
+                tokens.get(2)?.parse().ok()?,
+            )),
+            "f" | "frame" => Some(DebuggerCommand::Frame(match tokens.get(1) {
+                Some(number) => Some(number.parse().ok()?),
+                None => None,
+            })),
+            "up" => Some(DebuggerCommand::Up(
+                tokens.get(1).map_or(Some(1), |n| n.parse().ok())?,
+            )),
+            "down" => Some(DebuggerCommand::Down(
+                tokens.get(1).map_or(Some(1), |n| n.parse().ok())?,
+            )),
+            cmd if cmd == "x" || cmd.starts_with("x/") => Some(DebuggerCommand::Examine(
+                parse_examine_format(cmd.get(2..).unwrap_or(""))?,
+                tokens[1..].join(" "),
+            )),
+            "disas" | "disassemble" => Some(DebuggerCommand::Disassemble(if tokens.len() > 1 {
+                Some(tokens[1..].join(" "))
+            } else {
+                None
+            })),
+            cmd if cmd == "display" || cmd.starts_with("display/") => Some(DebuggerCommand::Display(
+                match cmd.find('/') {
+                    Some(slash) => Some(parse_examine_format(&cmd[slash + 1..])?),
+                    None => None,
+                },
+                if tokens.len() > 1 {
+                    Some(tokens[1..].join(" "))
+                } else {
+                    None
+                },
+            )),
+            "l" | "list" => Some(DebuggerCommand::List(tokens.get(1).map(|arg| arg.to_string()))),
+            "dir" | "directory" => {
+                Some(DebuggerCommand::Directory(tokens.get(1).map(|dir| dir.to_string())))
+            }
+            "undisplay" => Some(DebuggerCommand::Undisplay(parse_numbers(&tokens[1..])?)),
+            "set" => {
+                let assignment = tokens[1..].join(" ");
+                // "set var x = 1" is the same as "set x = 1"
+                let assignment = assignment.trim_start_matches("var ");
+                if !assignment.contains('=') {
+                    // "set <setting> <value>" changes one of deet's settings
+                    return match tokens.len() {
+                        3 => Some(DebuggerCommand::SetOption(
+                            tokens[1].to_string(),
+                            tokens[2].to_string(),
+                        )),
+                        _ => None,
+                    };
+                }
+                Some(DebuggerCommand::Set(assignment.to_string()))
+            }
+            "gcore" => Some(DebuggerCommand::Gcore(tokens.get(1).map(|path| path.to_string()))),
+            "attach" => Some(DebuggerCommand::Attach(tokens.get(1)?.parse().ok()?)),
+            "detach" => Some(DebuggerCommand::Detach),
+            "handle" => Some(DebuggerCommand::Handle(
+                tokens[1..].iter().map(|arg| arg.to_string()).collect(),
+            )),
+            "inferiors" => Some(DebuggerCommand::InfoInferiors),
+            "inferior" => Some(DebuggerCommand::Inferior(tokens.get(1)?.parse().ok()?)),
+            "thread" => Some(DebuggerCommand::Thread(match tokens.get(1) {
+                Some(number) => Some(number.parse().ok()?),
+                None => None,
+            })),
+            "signal" => Some(DebuggerCommand::Signal(tokens.get(1)?.to_string())),
+            "source" => Some(DebuggerCommand::Source(tokens.get(1)?.to_string())),
+            "define" => Some(DebuggerCommand::Define(tokens.get(1)?.to_string())),
+            "commands" => Some(DebuggerCommand::Commands(match tokens.get(1) {
+                Some(number) => Some(number.parse().ok()?),
+                None => None,
+            })),
+            "i"  | "info" => match *tokens.get(1)? {
+                "locals" => Some(DebuggerCommand::InfoLocals),
+                "display" => Some(DebuggerCommand::InfoDisplay),
+                "threads" => Some(DebuggerCommand::InfoThreads),
+                "inferiors" => Some(DebuggerCommand::InfoInferiors),
+                "sharedlibrary" | "sharedlib" => Some(DebuggerCommand::InfoSharedLibrary),
+                "signals" | "handle" => {
+                    Some(DebuggerCommand::InfoSignals(tokens.get(2).map(|sig| sig.to_string())))
+                }
+                "b" | "break" | "breakpoints" | "watchpoints" => {
+                    Some(DebuggerCommand::InfoBreakpoints)
+                }
+                "r" | "registers" => Some(DebuggerCommand::InfoRegisters(
+                    tokens[2..].iter().map(|name| name.trim_start_matches('$').to_string()).collect(),
+                )),
+                _ => None,
+            },
             // Default case:
             _ => None,
         }
     }
 }
+
+/// Parses the count, format letter and size letter of x/<count><format><size>, which may all be
+/// left out.
+fn parse_examine_format(spec: &str) -> Option<ExamineFormat> {
+    let digits = spec.find(|c: char| !c.is_ascii_digit()).unwrap_or(spec.len());
+    let mut format = ExamineFormat {
+        count: if digits == 0 { 1 } else { spec[..digits].parse().ok()? },
+        format: 'x',
+        size: None,
+    };
+    for letter in spec[digits..].chars() {
+        match letter {
+            'b' => format.size = Some(1),
+            'h' => format.size = Some(2),
+            'w' => format.size = Some(4),
+            'g' => format.size = Some(8),
+            'x' | 'd' | 'u' | 'o' | 't' | 'a' | 'c' | 'f' | 's' | 'i' => format.format = letter,
+            _ => return None,
+        }
+    }
+    Some(format)
+}
+
+/// Parses a list of breakpoint numbers, returning None if any of them isn't a number.
+fn parse_numbers(tokens: &[&str]) -> Option<Vec<usize>> {
+    tokens.iter().map(|token| token.parse().ok()).collect()
+}
@@ -0,0 +1,892 @@
+use std::fmt;
+
+/// The longest an x86 instruction can be.
+pub const MAX_INSTRUCTION_LEN: usize = 15;
+
+const REGS_64: [&str; 16] = [
+    "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15",
+];
+const REGS_32: [&str; 16] = [
+    "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8d", "r9d", "r10d", "r11d", "r12d",
+    "r13d", "r14d", "r15d",
+];
+const REGS_16: [&str; 16] = [
+    "ax", "cx", "dx", "bx", "sp", "bp", "si", "di", "r8w", "r9w", "r10w", "r11w", "r12w", "r13w",
+    "r14w", "r15w",
+];
+const REGS_8: [&str; 16] = [
+    "al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil", "r8b", "r9b", "r10b", "r11b", "r12b",
+    "r13b", "r14b", "r15b",
+];
+/// Byte registers 4-7 when there is no REX prefix.
+const REGS_8_LEGACY: [&str; 4] = ["ah", "ch", "dh", "bh"];
+
+const ALU_OPS: [&str; 8] = ["add", "or", "adc", "sbb", "and", "sub", "xor", "cmp"];
+const SHIFT_OPS: [&str; 8] = ["rol", "ror", "rcl", "rcr", "shl", "shr", "shl", "sar"];
+const UNARY_OPS: [&str; 8] = ["test", "test", "not", "neg", "mul", "imul", "div", "idiv"];
+/// Condition codes of jcc, setcc and cmovcc, by the low four bits of the opcode.
+const CONDITIONS: [&str; 16] = [
+    "o", "no", "b", "ae", "e", "ne", "be", "a", "s", "ns", "p", "np", "l", "ge", "le", "g",
+];
+
+/// An x86-64 instruction decoded from machine code.
+pub struct Instruction {
+    pub addr: usize,
+    pub len: usize,
+    pub mnemonic: String,
+    /// The operands in AT&T syntax: sources first, then the destination.
+    pub operands: String,
+    /// Where a direct jump or call goes.
+    pub branch_target: Option<usize>,
+    /// The address a %rip-relative memory operand refers to.
+    pub memory_target: Option<usize>,
+}
+
+impl Instruction {
+    pub fn is_call(&self) -> bool {
+        self.mnemonic == "call"
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.operands.is_empty() {
+            write!(f, "{}", self.mnemonic)
+        } else {
+            write!(f, "{:<6} {}", self.mnemonic, self.operands)
+        }
+    }
+}
+
+/// Decodes the instruction at the start of bytes, which were read from address addr. Bytes that
+/// aren't an instruction the decoder knows (x87 and AVX instructions, for example) decode to a
+/// one-byte "(bad)".
+pub fn decode(bytes: &[u8], addr: usize) -> Instruction {
+    let mut decoder = Decoder {
+        bytes,
+        addr,
+        pos: 0,
+        rex: 0,
+        operand_16: false,
+        address_32: false,
+        rep: None,
+        lock: false,
+        segment: None,
+        rip_disp: None,
+        branch_target: None,
+    };
+    match decoder.decode() {
+        Some((mnemonic, operands)) => {
+            let len = decoder.pos;
+            Instruction {
+                addr,
+                len,
+                mnemonic: if decoder.lock {
+                    format!("lock {}", mnemonic)
+                } else {
+                    mnemonic
+                },
+                operands: operands.join(","),
+                branch_target: decoder.branch_target,
+                memory_target: decoder
+                    .rip_disp
+                    .map(|disp| (addr as i64 + len as i64 + disp) as usize),
+            }
+        }
+        None => Instruction {
+            addr,
+            len: 1,
+            mnemonic: "(bad)".to_string(),
+            operands: String::new(),
+            branch_target: None,
+            memory_target: None,
+        },
+    }
+}
+
+/// The kind of register a ModRM field names.
+#[derive(Clone, Copy)]
+enum Reg {
+    /// A general purpose register of the given size in bytes.
+    Gpr(usize),
+    Xmm,
+}
+
+struct ModRm {
+    md: u8,
+    reg: u8,
+    rm: u8,
+}
+
+type Decoded = (String, Vec<String>);
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    addr: usize,
+    pos: usize,
+    rex: u8,
+    /// Whether there is a 0x66 operand size prefix.
+    operand_16: bool,
+    /// Whether there is a 0x67 address size prefix.
+    address_32: bool,
+    /// A 0xf3 or 0xf2 prefix.
+    rep: Option<u8>,
+    lock: bool,
+    segment: Option<&'static str>,
+    /// The displacement of a %rip-relative operand, which is relative to the end of the
+    /// instruction.
+    rip_disp: Option<i64>,
+    branch_target: Option<usize>,
+}
+
+impl<'a> Decoder<'a> {
+    fn decode(&mut self) -> Option<Decoded> {
+        loop {
+            match *self.bytes.get(self.pos)? {
+                0x66 => self.operand_16 = true,
+                0x67 => self.address_32 = true,
+                prefix @ 0xf2 | prefix @ 0xf3 => self.rep = Some(prefix),
+                0xf0 => self.lock = true,
+                0x64 => self.segment = Some("fs"),
+                0x65 => self.segment = Some("gs"),
+                0x26 | 0x2e | 0x36 | 0x3e => {}
+                _ => break,
+            }
+            self.byte()?;
+        }
+        if let Some(0x40..=0x4f) = self.bytes.get(self.pos) {
+            self.rex = self.byte()?;
+        }
+        match self.byte()? {
+            0x0f => self.decode_two_byte(),
+            opcode => self.decode_one_byte(opcode),
+        }
+    }
+
+    fn decode_one_byte(&mut self, opcode: u8) -> Option<Decoded> {
+        let size = self.operand_size();
+        let gpr = Reg::Gpr(size);
+        // many opcodes come in pairs whose even member works on bytes
+        let byte_sized = opcode & 1 == 0;
+        let pair_size = if byte_sized { 1 } else { size };
+        Some(match opcode {
+            0x00..=0x3f if opcode & 7 < 6 => {
+                let operands = match opcode & 7 {
+                    0..=3 => {
+                        let (g, e) = self.modrm_operands(Reg::Gpr(pair_size))?;
+                        if opcode & 2 == 0 {
+                            vec![g, e]
+                        } else {
+                            vec![e, g]
+                        }
+                    }
+                    4 => vec![imm_operand(self.signed(1)?, 1), "%al".to_string()],
+                    _ => vec![imm_operand(self.imm(size)?, size), self.reg_name(0, gpr)],
+                };
+                sized(ALU_OPS[opcode as usize >> 3], pair_size, operands)
+            }
+            0x50..=0x5f => {
+                let reg = self.reg_name(opcode & 7 | self.rex_b(), Reg::Gpr(8));
+                named(if opcode < 0x58 { "push" } else { "pop" }, vec![reg])
+            }
+            0x63 => {
+                let m = self.modrm()?;
+                let e = self.rm_operand(&m, Reg::Gpr(4))?;
+                named("movslq", vec![e, self.reg_operand(&m, gpr)])
+            }
+            0x68 => named("push", vec![imm_operand(self.signed(4)?, 8)]),
+            0x6a => named("push", vec![imm_operand(self.signed(1)?, 8)]),
+            0x69 | 0x6b => {
+                let m = self.modrm()?;
+                let e = self.rm_operand(&m, gpr)?;
+                let imm = if opcode == 0x69 { self.imm(size)? } else { self.signed(1)? };
+                named("imul", vec![imm_operand(imm, size), e, self.reg_operand(&m, gpr)])
+            }
+            0x70..=0x7f => {
+                let rel = self.signed(1)?;
+                (format!("j{}", CONDITIONS[opcode as usize & 0xf]), vec![self.branch(rel)])
+            }
+            0x80 | 0x81 | 0x83 => {
+                let size = if opcode == 0x80 { 1 } else { size };
+                let m = self.modrm()?;
+                let e = self.rm_operand(&m, Reg::Gpr(size))?;
+                let imm = if opcode == 0x81 { self.imm(size)? } else { self.signed(1)? };
+                sized(ALU_OPS[m.reg as usize], size, vec![imm_operand(imm, size), e])
+            }
+            0x84..=0x8b => {
+                let (g, e) = self.modrm_operands(Reg::Gpr(pair_size))?;
+                let mnemonic = match opcode {
+                    0x84 | 0x85 => "test",
+                    0x86 | 0x87 => "xchg",
+                    _ => "mov",
+                };
+                let operands = if opcode >= 0x8a { vec![e, g] } else { vec![g, e] };
+                sized(mnemonic, pair_size, operands)
+            }
+            0x8d => {
+                let (g, e) = self.modrm_operands(gpr)?;
+                named("lea", vec![e, g])
+            }
+            0x8f => {
+                let m = self.modrm()?;
+                named("pop", vec![self.rm_operand(&m, Reg::Gpr(8))?])
+            }
+            0x90 if self.rex_b() == 0 && !self.operand_16 => {
+                named(if self.rep == Some(0xf3) { "pause" } else { "nop" }, vec![])
+            }
+            0x90..=0x97 => {
+                let reg = self.reg_name(opcode & 7 | self.rex_b(), gpr);
+                named("xchg", vec![reg, self.reg_name(0, gpr)])
+            }
+            0x98 => named(["cbtw", "cwtl", "cltq"][size / 4], vec![]),
+            0x99 => named(["cwtd", "cltd", "cqto"][size / 4], vec![]),
+            0xa4..=0xa7 | 0xaa..=0xaf => self.string_op(opcode, pair_size),
+            0xa8 => named("test", vec![imm_operand(self.signed(1)?, 1), "%al".to_string()]),
+            0xa9 => named("test", vec![imm_operand(self.imm(size)?, size), self.reg_name(0, gpr)]),
+            0xb0..=0xb7 => {
+                let reg = self.reg_name(opcode & 7 | self.rex_b(), Reg::Gpr(1));
+                named("mov", vec![imm_operand(self.signed(1)?, 1), reg])
+            }
+            0xb8..=0xbf => {
+                let reg = self.reg_name(opcode & 7 | self.rex_b(), gpr);
+                if size == 8 {
+                    named("movabs", vec![imm_operand(self.signed(8)?, 8), reg])
+                } else {
+                    named("mov", vec![imm_operand(self.imm(size)?, size), reg])
+                }
+            }
+            0xc0 | 0xc1 | 0xd0..=0xd3 => {
+                let m = self.modrm()?;
+                let e = self.rm_operand(&m, Reg::Gpr(pair_size))?;
+                let mnemonic = SHIFT_OPS[m.reg as usize];
+                // only the shifted operand says whether a size suffix is needed
+                let (mnemonic, _) = sized(mnemonic, pair_size, vec![e.clone()]);
+                let operands = match opcode {
+                    0xc0 | 0xc1 => vec![imm_operand(self.signed(1)?, 1), e],
+                    0xd2 | 0xd3 => vec!["%cl".to_string(), e],
+                    _ => vec![e],
+                };
+                (mnemonic, operands)
+            }
+            0xc2 => named("ret", vec![imm_operand(self.signed(2)?, 2)]),
+            0xc3 => named(if self.rep == Some(0xf3) { "repz ret" } else { "ret" }, vec![]),
+            0xc6 | 0xc7 => {
+                let m = self.modrm()?;
+                if m.reg != 0 {
+                    return None;
+                }
+                let e = self.rm_operand(&m, Reg::Gpr(pair_size))?;
+                let imm = self.imm(pair_size)?;
+                sized("mov", pair_size, vec![imm_operand(imm, pair_size), e])
+            }
+            0xc9 => named("leave", vec![]),
+            0xcc => named("int3", vec![]),
+            0xcd => named("int", vec![imm_operand(self.signed(1)?, 1)]),
+            0xe3 | 0xe8 | 0xe9 | 0xeb => {
+                let rel = if opcode == 0xe8 || opcode == 0xe9 {
+                    self.signed(4)?
+                } else {
+                    self.signed(1)?
+                };
+                let mnemonic = match opcode {
+                    0xe3 => "jrcxz",
+                    0xe8 => "call",
+                    _ => "jmp",
+                };
+                named(mnemonic, vec![self.branch(rel)])
+            }
+            0xf4 => named("hlt", vec![]),
+            0xf5 => named("cmc", vec![]),
+            0xf8 => named("clc", vec![]),
+            0xf9 => named("stc", vec![]),
+            0xfa => named("cli", vec![]),
+            0xfb => named("sti", vec![]),
+            0xfc => named("cld", vec![]),
+            0xfd => named("std", vec![]),
+            0xf6 | 0xf7 => {
+                let m = self.modrm()?;
+                let e = self.rm_operand(&m, Reg::Gpr(pair_size))?;
+                let operands = if m.reg < 2 {
+                    vec![imm_operand(self.imm(pair_size)?, pair_size), e]
+                } else {
+                    vec![e]
+                };
+                sized(UNARY_OPS[m.reg as usize], pair_size, operands)
+            }
+            0xfe | 0xff => {
+                let m = self.modrm()?;
+                match m.reg {
+                    0 | 1 => {
+                        let e = self.rm_operand(&m, Reg::Gpr(pair_size))?;
+                        sized(["inc", "dec"][m.reg as usize], pair_size, vec![e])
+                    }
+                    2 | 4 if opcode == 0xff => {
+                        let e = self.rm_operand(&m, Reg::Gpr(8))?;
+                        named(if m.reg == 2 { "call" } else { "jmp" }, vec![format!("*{}", e)])
+                    }
+                    6 if opcode == 0xff => named("push", vec![self.rm_operand(&m, Reg::Gpr(8))?]),
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        })
+    }
+
+    fn decode_two_byte(&mut self) -> Option<Decoded> {
+        let opcode = self.byte()?;
+        let size = self.operand_size();
+        let gpr = Reg::Gpr(size);
+        Some(match opcode {
+            0x05 => named("syscall", vec![]),
+            0x0b => named("ud2", vec![]),
+            0x31 => named("rdtsc", vec![]),
+            0xa2 => named("cpuid", vec![]),
+            0x1e if self.rep == Some(0xf3) && self.bytes.get(self.pos) == Some(&0xfa) => {
+                self.byte()?;
+                named("endbr64", vec![])
+            }
+            0x18 => {
+                let m = self.modrm()?;
+                if m.reg > 3 || m.md == 3 {
+                    return None;
+                }
+                let mnemonic = ["prefetchnta", "prefetcht0", "prefetcht1", "prefetcht2"];
+                named(mnemonic[m.reg as usize], vec![self.rm_operand(&m, gpr)?])
+            }
+            0x1f => {
+                let m = self.modrm()?;
+                sized("nop", size, vec![self.rm_operand(&m, gpr)?])
+            }
+            0x40..=0x4f => {
+                let (g, e) = self.modrm_operands(gpr)?;
+                (format!("cmov{}", CONDITIONS[opcode as usize & 0xf]), vec![e, g])
+            }
+            0x80..=0x8f => {
+                let rel = self.signed(4)?;
+                (format!("j{}", CONDITIONS[opcode as usize & 0xf]), vec![self.branch(rel)])
+            }
+            0x90..=0x9f => {
+                let m = self.modrm()?;
+                let e = self.rm_operand(&m, Reg::Gpr(1))?;
+                (format!("set{}", CONDITIONS[opcode as usize & 0xf]), vec![e])
+            }
+            0xa3 | 0xab | 0xb3 | 0xbb => {
+                let (g, e) = self.modrm_operands(gpr)?;
+                named(["bt", "bts", "btr", "btc"][(opcode as usize >> 3) & 3], vec![g, e])
+            }
+            0xba => {
+                let m = self.modrm()?;
+                if m.reg < 4 {
+                    return None;
+                }
+                let e = self.rm_operand(&m, gpr)?;
+                let imm = imm_operand(self.signed(1)?, 1);
+                sized(["bt", "bts", "btr", "btc"][m.reg as usize - 4], size, vec![imm, e])
+            }
+            0xa4 | 0xa5 | 0xac | 0xad => {
+                let (g, e) = self.modrm_operands(gpr)?;
+                let count = if opcode & 1 == 0 {
+                    imm_operand(self.signed(1)?, 1)
+                } else {
+                    "%cl".to_string()
+                };
+                named(if opcode < 0xa8 { "shld" } else { "shrd" }, vec![count, g, e])
+            }
+            0xaf => {
+                let (g, e) = self.modrm_operands(gpr)?;
+                named("imul", vec![e, g])
+            }
+            0xb0 | 0xb1 | 0xc0 | 0xc1 => {
+                let (g, e) = self.modrm_operands(Reg::Gpr(if opcode & 1 == 0 { 1 } else { size }))?;
+                named(if opcode < 0xc0 { "cmpxchg" } else { "xadd" }, vec![g, e])
+            }
+            0xb6 | 0xb7 | 0xbe | 0xbf => {
+                let source_size = if opcode & 1 == 0 { 1 } else { 2 };
+                let m = self.modrm()?;
+                let e = self.rm_operand(&m, Reg::Gpr(source_size))?;
+                let mnemonic = format!(
+                    "mov{}{}{}",
+                    if opcode < 0xb8 { "z" } else { "s" },
+                    suffix(source_size),
+                    suffix(size)
+                );
+                (mnemonic, vec![e, self.reg_operand(&m, gpr)])
+            }
+            0xb8 | 0xbc | 0xbd => {
+                let (g, e) = self.modrm_operands(gpr)?;
+                let mnemonic = match (self.rep == Some(0xf3), opcode) {
+                    (true, 0xb8) => "popcnt",
+                    (false, 0xb8) => return None,
+                    (true, 0xbc) => "tzcnt",
+                    (true, _) => "lzcnt",
+                    (false, 0xbc) => "bsf",
+                    (false, _) => "bsr",
+                };
+                named(mnemonic, vec![e, g])
+            }
+            0xc8..=0xcf => named("bswap", vec![self.reg_name(opcode & 7 | self.rex_b(), gpr)]),
+            0xae => {
+                let m = self.modrm()?;
+                match (m.md, m.reg) {
+                    (3, 5) => named("lfence", vec![]),
+                    (3, 6) => named("mfence", vec![]),
+                    (3, 7) => named("sfence", vec![]),
+                    (3, _) => return None,
+                    (_, 2) => named("ldmxcsr", vec![self.rm_operand(&m, gpr)?]),
+                    (_, 3) => named("stmxcsr", vec![self.rm_operand(&m, gpr)?]),
+                    _ => return None,
+                }
+            }
+            _ => return self.decode_sse(opcode),
+        })
+    }
+
+    /// Decodes the SSE instructions compilers use for floating point and for copying memory.
+    /// Their variant is chosen by a 0x66, 0xf3 or 0xf2 prefix.
+    fn decode_sse(&mut self, opcode: u8) -> Option<Decoded> {
+        let prefix = match (self.rep, self.operand_16) {
+            (Some(rep), _) => rep,
+            (None, true) => 0x66,
+            (None, false) => 0,
+        };
+        let kind = match prefix {
+            0 => "ps",
+            0x66 => "pd",
+            0xf3 => "ss",
+            _ => "sd",
+        };
+        let xmm = Reg::Xmm;
+        let gpr = Reg::Gpr(if self.rex_w() { 8 } else { 4 });
+        let m = self.modrm()?;
+        // the mnemonic, the kinds of the r/m and reg operands, and whether the reg operand is
+        // the destination
+        let (mnemonic, rm_reg, reg_reg, load) = match (opcode, prefix) {
+            (0x10, _) | (0x11, _) => {
+                let mnemonic = match prefix {
+                    0 => "movups".to_string(),
+                    0x66 => "movupd".to_string(),
+                    _ => format!("mov{}", kind),
+                };
+                (mnemonic, xmm, xmm, opcode == 0x10)
+            }
+            (0x12, 0) if m.md == 3 => ("movhlps".to_string(), xmm, xmm, true),
+            (0x16, 0) if m.md == 3 => ("movlhps".to_string(), xmm, xmm, true),
+            (0x12, 0) | (0x13, 0) | (0x12, 0x66) | (0x13, 0x66) => {
+                (format!("movl{}", kind), xmm, xmm, opcode == 0x12)
+            }
+            (0x16, 0) | (0x17, 0) | (0x16, 0x66) | (0x17, 0x66) => {
+                (format!("movh{}", kind), xmm, xmm, opcode == 0x16)
+            }
+            (0x12, 0xf2) => ("movddup".to_string(), xmm, xmm, true),
+            (0x12, 0xf3) => ("movsldup".to_string(), xmm, xmm, true),
+            (0x16, 0xf3) => ("movshdup".to_string(), xmm, xmm, true),
+            (0x14, 0) | (0x14, 0x66) => (format!("unpckl{}", kind), xmm, xmm, true),
+            (0x15, 0) | (0x15, 0x66) => (format!("unpckh{}", kind), xmm, xmm, true),
+            (0x28, 0) | (0x29, 0) | (0x28, 0x66) | (0x29, 0x66) => {
+                (format!("mova{}", kind), xmm, xmm, opcode == 0x28)
+            }
+            (0x2a, 0xf3) | (0x2a, 0xf2) => {
+                let mut mnemonic = format!("cvtsi2{}", kind);
+                // the integer's size is only apparent from a register operand
+                if m.md != 3 {
+                    mnemonic.push(suffix(if self.rex_w() { 8 } else { 4 }));
+                }
+                (mnemonic, gpr, xmm, true)
+            }
+            (0x2b, 0) | (0x2b, 0x66) => (format!("movnt{}", kind), xmm, xmm, false),
+            (0x50, 0) | (0x50, 0x66) => (format!("movmsk{}", kind), xmm, Reg::Gpr(4), true),
+            (0xe7, 0x66) => ("movntdq".to_string(), xmm, xmm, false),
+            (0x71..=0x73, 0x66) if m.md == 3 => {
+                // shifts by an immediate, chosen by the reg field
+                let mnemonic = match (opcode, m.reg) {
+                    (0x71, 2) => "psrlw",
+                    (0x71, 4) => "psraw",
+                    (0x71, 6) => "psllw",
+                    (0x72, 2) => "psrld",
+                    (0x72, 4) => "psrad",
+                    (0x72, 6) => "pslld",
+                    (0x73, 2) => "psrlq",
+                    (0x73, 3) => "psrldq",
+                    (0x73, 6) => "psllq",
+                    (0x73, 7) => "pslldq",
+                    _ => return None,
+                };
+                let e = self.rm_operand(&m, xmm)?;
+                return Some(named(mnemonic, vec![imm_operand(self.signed(1)?, 1), e]));
+            }
+            (0x2c, 0xf3) | (0x2c, 0xf2) => (format!("cvtt{}2si", kind), xmm, gpr, true),
+            (0x2d, 0xf3) | (0x2d, 0xf2) => (format!("cvt{}2si", kind), xmm, gpr, true),
+            (0x2e, 0) | (0x2e, 0x66) => (format!("ucomis{}", &kind[1..]), xmm, xmm, true),
+            (0x2f, 0) | (0x2f, 0x66) => (format!("comis{}", &kind[1..]), xmm, xmm, true),
+            (0x51, _) | (0x58..=0x59, _) | (0x5c..=0x5f, _) => {
+                let operation = match opcode {
+                    0x51 => "sqrt",
+                    0x58 => "add",
+                    0x59 => "mul",
+                    0x5c => "sub",
+                    0x5d => "min",
+                    0x5e => "div",
+                    _ => "max",
+                };
+                (format!("{}{}", operation, kind), xmm, xmm, true)
+            }
+            (0x54..=0x57, 0) | (0x54..=0x57, 0x66) => {
+                let operation = ["and", "andn", "or", "xor"][opcode as usize - 0x54];
+                (format!("{}{}", operation, kind), xmm, xmm, true)
+            }
+            (0x5a, _) => {
+                let mnemonic = ["cvtps2pd", "cvtpd2ps", "cvtss2sd", "cvtsd2ss"];
+                let index = match prefix {
+                    0 => 0,
+                    0x66 => 1,
+                    0xf3 => 2,
+                    _ => 3,
+                };
+                (mnemonic[index].to_string(), xmm, xmm, true)
+            }
+            (0x5b, 0) => ("cvtdq2ps".to_string(), xmm, xmm, true),
+            (0x5b, 0x66) => ("cvtps2dq".to_string(), xmm, xmm, true),
+            (0x5b, 0xf3) => ("cvttps2dq".to_string(), xmm, xmm, true),
+            (0xe6, 0xf3) => ("cvtdq2pd".to_string(), xmm, xmm, true),
+            (0xe6, 0x66) => ("cvttpd2dq".to_string(), xmm, xmm, true),
+            (0xe6, 0xf2) => ("cvtpd2dq".to_string(), xmm, xmm, true),
+            (0x6e, 0x66) | (0x7e, 0x66) => {
+                let mnemonic = if self.rex_w() { "movq" } else { "movd" };
+                (mnemonic.to_string(), gpr, xmm, opcode == 0x6e)
+            }
+            (0x7e, 0xf3) => ("movq".to_string(), xmm, xmm, true),
+            (0xd6, 0x66) => ("movq".to_string(), xmm, xmm, false),
+            (0x6f, 0x66) | (0x7f, 0x66) => ("movdqa".to_string(), xmm, xmm, opcode == 0x6f),
+            (0x6f, 0xf3) | (0x7f, 0xf3) => ("movdqu".to_string(), xmm, xmm, opcode == 0x6f),
+            (0xd7, 0x66) => ("pmovmskb".to_string(), xmm, Reg::Gpr(4), true),
+            (0x70, 0x66) => ("pshufd".to_string(), xmm, xmm, true),
+            (0xc6, 0) | (0xc6, 0x66) => (format!("shuf{}", kind), xmm, xmm, true),
+            (_, 0x66) => {
+                let mnemonic = match opcode {
+                    0x60 => "punpcklbw",
+                    0x61 => "punpcklwd",
+                    0x62 => "punpckldq",
+                    0x64 => "pcmpgtb",
+                    0x66 => "pcmpgtd",
+                    0x6a => "punpckhdq",
+                    0x6c => "punpcklqdq",
+                    0x6d => "punpckhqdq",
+                    0x74 => "pcmpeqb",
+                    0x75 => "pcmpeqw",
+                    0x76 => "pcmpeqd",
+                    0xd4 => "paddq",
+                    0xda => "pminub",
+                    0xdb => "pand",
+                    0xde => "pmaxub",
+                    0xdf => "pandn",
+                    0xeb => "por",
+                    0xef => "pxor",
+                    0xf8 => "psubb",
+                    0xfa => "psubd",
+                    0xfb => "psubq",
+                    0xfc => "paddb",
+                    0xfe => "paddd",
+                    _ => return None,
+                };
+                (mnemonic.to_string(), xmm, xmm, true)
+            }
+            _ => return None,
+        };
+        let e = self.rm_operand(&m, rm_reg)?;
+        let g = self.reg_operand(&m, reg_reg);
+        let mut operands = if load { vec![e, g] } else { vec![g, e] };
+        if opcode == 0x70 || opcode == 0xc6 {
+            operands.insert(0, imm_operand(self.signed(1)?, 1));
+        }
+        Some((mnemonic, operands))
+    }
+
+    /// Decodes movs, cmps, stos, lods and scas, with any repeat prefix.
+    fn string_op(&mut self, opcode: u8, size: usize) -> Decoded {
+        let accumulator = self.reg_name(0, Reg::Gpr(size));
+        let (mnemonic, operands) = match opcode & !1 {
+            0xa4 => ("movs", vec!["%ds:(%rsi)".to_string(), "%es:(%rdi)".to_string()]),
+            0xa6 => ("cmps", vec!["%es:(%rdi)".to_string(), "%ds:(%rsi)".to_string()]),
+            0xaa => ("stos", vec![accumulator, "%es:(%rdi)".to_string()]),
+            0xac => ("lods", vec!["%ds:(%rsi)".to_string(), accumulator]),
+            _ => ("scas", vec!["%es:(%rdi)".to_string(), accumulator]),
+        };
+        let (mnemonic, operands) = sized(mnemonic, size, operands);
+        // cmps and scas stop repeating on a mismatch or match
+        let compares = opcode & !1 == 0xa6 || opcode & !1 == 0xae;
+        let prefix = match (self.rep, compares) {
+            (Some(0xf3), true) => "repz ",
+            (Some(0xf3), false) => "rep ",
+            (Some(_), _) => "repnz ",
+            (None, _) => "",
+        };
+        (format!("{}{}", prefix, mnemonic), operands)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        if self.pos >= MAX_INSTRUCTION_LEN {
+            return None;
+        }
+        let byte = *self.bytes.get(self.pos)?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    /// Reads a little-endian displacement or immediate of len bytes and sign-extends it.
+    fn signed(&mut self, len: usize) -> Option<i64> {
+        let mut value: u64 = 0;
+        for index in 0..len {
+            value |= (self.byte()? as u64) << (8 * index);
+        }
+        let shift = 64 - 8 * len as u32;
+        Some(((value << shift) as i64) >> shift)
+    }
+
+    /// Reads an immediate for an operand of the given size. Immediates for 64-bit operands are
+    /// 32 bits, sign-extended.
+    fn imm(&mut self, size: usize) -> Option<i64> {
+        self.signed(size.min(4))
+    }
+
+    fn rex_w(&self) -> bool {
+        self.rex & 0b1000 != 0
+    }
+
+    /// The REX bits that extend the ModRM reg field, the SIB index and the ModRM r/m field or
+    /// SIB base, shifted to be or'ed into register numbers.
+    fn rex_r(&self) -> u8 {
+        (self.rex & 0b100) << 1
+    }
+
+    fn rex_x(&self) -> u8 {
+        (self.rex & 0b10) << 2
+    }
+
+    fn rex_b(&self) -> u8 {
+        (self.rex & 0b1) << 3
+    }
+
+    fn operand_size(&self) -> usize {
+        if self.rex_w() {
+            8
+        } else if self.operand_16 {
+            2
+        } else {
+            4
+        }
+    }
+
+    fn reg_name(&self, number: u8, reg: Reg) -> String {
+        let number = number as usize;
+        let name = match reg {
+            Reg::Xmm => return format!("%xmm{}", number),
+            Reg::Gpr(8) => REGS_64[number],
+            Reg::Gpr(4) => REGS_32[number],
+            Reg::Gpr(2) => REGS_16[number],
+            Reg::Gpr(_) if self.rex == 0 && (4..8).contains(&number) => REGS_8_LEGACY[number - 4],
+            Reg::Gpr(_) => REGS_8[number],
+        };
+        format!("%{}", name)
+    }
+
+    /// Formats a jump or call to rel bytes past the end of the instruction, which must have been
+    /// read completely.
+    fn branch(&mut self, rel: i64) -> String {
+        let target = (self.addr as i64 + self.pos as i64 + rel) as usize;
+        self.branch_target = Some(target);
+        format!("{:#x}", target)
+    }
+
+    fn modrm(&mut self) -> Option<ModRm> {
+        let byte = self.byte()?;
+        Some(ModRm {
+            md: byte >> 6,
+            reg: (byte >> 3) & 7,
+            rm: byte & 7,
+        })
+    }
+
+    /// Reads a ModRM byte and returns its register operand and its register or memory operand.
+    fn modrm_operands(&mut self, reg: Reg) -> Option<(String, String)> {
+        let m = self.modrm()?;
+        let e = self.rm_operand(&m, reg)?;
+        Some((self.reg_operand(&m, reg), e))
+    }
+
+    /// The register operand chosen by the reg field.
+    fn reg_operand(&self, m: &ModRm, reg: Reg) -> String {
+        self.reg_name(m.reg | self.rex_r(), reg)
+    }
+
+    /// The register or memory operand chosen by the mod and r/m fields, reading any SIB byte and
+    /// displacement that follows.
+    fn rm_operand(&mut self, m: &ModRm, reg: Reg) -> Option<String> {
+        if m.md == 3 {
+            return Some(self.reg_name(m.rm | self.rex_b(), reg));
+        }
+        let address_reg = Reg::Gpr(if self.address_32 { 4 } else { 8 });
+        let mut base = None;
+        let mut index = None;
+        let mut rip_relative = false;
+        if m.rm == 4 {
+            let sib = self.byte()?;
+            let index_number = (sib >> 3) & 7 | self.rex_x();
+            if index_number != 4 {
+                index = Some((index_number, 1 << (sib >> 6)));
+            }
+            if sib & 7 != 5 || m.md != 0 {
+                base = Some(sib & 7 | self.rex_b());
+            }
+        } else if m.rm == 5 && m.md == 0 {
+            rip_relative = true;
+        } else {
+            base = Some(m.rm | self.rex_b());
+        }
+        let disp = match m.md {
+            1 => self.signed(1)?,
+            2 => self.signed(4)?,
+            _ if base.is_none() => self.signed(4)?,
+            _ => 0,
+        };
+        let mut operand = self.segment.map_or(String::new(), |segment| format!("%{}:", segment));
+        if m.md != 0 || base.is_none() {
+            operand += &if disp < 0 {
+                format!("-{:#x}", -disp)
+            } else {
+                format!("{:#x}", disp)
+            };
+        }
+        if rip_relative {
+            self.rip_disp = Some(disp);
+            operand += "(%rip)";
+        } else if base.is_some() || index.is_some() {
+            operand += "(";
+            if let Some(base) = base {
+                operand += &self.reg_name(base, address_reg);
+            }
+            if let Some((index, scale)) = index {
+                operand += &format!(",{},{}", self.reg_name(index, address_reg), scale);
+            }
+            operand += ")";
+        }
+        Some(operand)
+    }
+}
+
+fn named(mnemonic: &str, operands: Vec<String>) -> Decoded {
+    (mnemonic.to_string(), operands)
+}
+
+/// Adds a size suffix to the mnemonic if no register operand shows the operation's size, as in
+/// "movl $0x0,-0x4(%rbp)".
+fn sized(mnemonic: &str, size: usize, operands: Vec<String>) -> Decoded {
+    // %fs:0x28 is a memory operand
+    let has_register = operands
+        .iter()
+        .any(|operand| operand.starts_with('%') && !operand.contains(':'));
+    if has_register {
+        (mnemonic.to_string(), operands)
+    } else {
+        (format!("{}{}", mnemonic, suffix(size)), operands)
+    }
+}
+
+fn suffix(size: usize) -> char {
+    match size {
+        1 => 'b',
+        2 => 'w',
+        4 => 'l',
+        _ => 'q',
+    }
+}
+
+/// Formats an immediate as the unsigned value it has in an operand of the given size.
+fn imm_operand(value: i64, size: usize) -> String {
+    let value = if size >= 8 {
+        value as u64
+    } else {
+        value as u64 & ((1 << (8 * size)) - 1)
+    };
+    format!("${:#x}", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodes bytes at addr and returns the instruction with its spacing collapsed.
+    fn disassemble(bytes: &[u8], addr: usize) -> String {
+        let text = decode(bytes, addr).to_string();
+        text.split_whitespace().collect::<Vec<&str>>().join(" ")
+    }
+
+    #[test]
+    fn decodes_prologue() {
+        assert_eq!(disassemble(&[0x55], 0), "push %rbp");
+        assert_eq!(disassemble(&[0x48, 0x89, 0xe5], 0), "mov %rsp,%rbp");
+        assert_eq!(disassemble(&[0x48, 0x83, 0xec, 0x10], 0), "sub $0x10,%rsp");
+        assert_eq!(disassemble(&[0xf3, 0x0f, 0x1e, 0xfa], 0), "endbr64");
+        assert_eq!(disassemble(&[0xc3], 0), "ret");
+    }
+
+    #[test]
+    fn decodes_modrm_displacements() {
+        assert_eq!(disassemble(&[0x89, 0x7d, 0xec], 0), "mov %edi,-0x14(%rbp)");
+        assert_eq!(disassemble(&[0x0f, 0xb6, 0x07], 0), "movzbl (%rdi),%eax");
+        assert_eq!(disassemble(&[0x31, 0xc0], 0), "xor %eax,%eax");
+    }
+
+    #[test]
+    fn decodes_sib() {
+        assert_eq!(disassemble(&[0x48, 0x8d, 0x4c, 0x98, 0x08], 0), "lea 0x8(%rax,%rbx,4),%rcx");
+        assert_eq!(disassemble(&[0x48, 0x8b, 0x04, 0x24], 0), "mov (%rsp),%rax");
+        assert_eq!(
+            disassemble(&[0x4f, 0x8b, 0x74, 0xec, 0x10], 0),
+            "mov 0x10(%r12,%r13,8),%r14"
+        );
+        assert_eq!(
+            disassemble(&[0x80, 0x7c, 0x35, 0xff, 0x00], 0),
+            "cmpb $0x0,-0x1(%rbp,%rsi,1)"
+        );
+    }
+
+    #[test]
+    fn decodes_rip_relative() {
+        let instruction = decode(&[0x8b, 0x05, 0xe5, 0x2e, 0x00, 0x00], 0xb);
+        assert_eq!(instruction.len, 6);
+        assert_eq!(instruction.memory_target, Some(0x2ef6));
+        let instruction = decode(&[0xf2, 0x0f, 0x10, 0x05, 0x00, 0x01, 0x00, 0x00], 0x2b);
+        assert_eq!(instruction.mnemonic, "movsd");
+        assert_eq!(instruction.memory_target, Some(0x133));
+    }
+
+    #[test]
+    fn decodes_branches() {
+        let call = decode(&[0xe8, 0x1b, 0x00, 0x00, 0x00], 0x1f);
+        assert!(call.is_call());
+        assert_eq!((call.len, call.branch_target), (5, Some(0x3f)));
+        let jump = decode(&[0x75, 0xee], 0x24);
+        assert_eq!(jump.mnemonic, "jne");
+        assert_eq!(jump.branch_target, Some(0x14));
+    }
+
+    #[test]
+    fn decodes_byte_registers() {
+        // without a REX prefix, registers 4-7 are the high bytes of the first four
+        assert_eq!(disassemble(&[0x88, 0xe3], 0), "mov %ah,%bl");
+        assert_eq!(disassemble(&[0x40, 0x88, 0xf0], 0), "mov %sil,%al");
+    }
+
+    #[test]
+    fn decodes_immediates() {
+        assert_eq!(disassemble(&[0x48, 0xc1, 0xe2, 0x03], 0), "shl $0x3,%rdx");
+        assert_eq!(disassemble(&[0x6b, 0xc6, 0x64], 0), "imul $0x64,%esi,%eax");
+    }
+
+    #[test]
+    fn unknown_bytes_are_bad() {
+        let instruction = decode(&[0xd9, 0xe8], 0);
+        assert_eq!((instruction.mnemonic.as_str(), instruction.len), ("(bad)", 1));
+    }
+}
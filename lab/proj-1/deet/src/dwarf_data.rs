@@ -1,8 +1,10 @@
 use crate::gimli_wrapper;
 use addr2line::Context;
-use object::Object;
+use object::{Object, ObjectSection, ObjectSegment, SymbolKind};
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
-use std::{fmt, fs};
+use std::path::Path;
+use std::{fmt, fs, iter};
 
 #[derive(Debug)]
 pub enum Error {
@@ -10,9 +12,80 @@ pub enum Error {
     DwarfFormatError(gimli_wrapper::Error),
 }
 
+/// Why a breakpoint location couldn't be resolved to an address.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LocationError {
+    NoSuchFile(String),
+    /// Contains the file name given and the compilation units it could refer to.
+    AmbiguousFile(String, Vec<String>),
+    NoSuchLine(String, usize),
+    NoSuchFunction(String),
+    /// Contains the function name and the files that each define a function by that name.
+    AmbiguousFunction(String, Vec<String>),
+}
+
+impl fmt::Display for LocationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LocationError::NoSuchFile(file) => write!(f, "No source file named {}.", file),
+            LocationError::AmbiguousFile(file, matches) => write!(
+                f,
+                "Source file name \"{}\" is ambiguous, use one of: {}",
+                file,
+                matches.join(", ")
+            ),
+            LocationError::NoSuchLine(file, line) => {
+                write!(f, "No line {} in file \"{}\".", line, file)
+            }
+            LocationError::NoSuchFunction(func) => write!(f, "Function \"{}\" not defined.", func),
+            LocationError::AmbiguousFunction(func, files) => {
+                let choices: Vec<String> =
+                    files.iter().map(|file| format!("{}:{}", file, func)).collect();
+                write!(
+                    f,
+                    "Function \"{}\" is defined in several files, use one of: {}",
+                    func,
+                    choices.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// Types the debugger adds, such as pointer types for &x, get offsets counting up from here,
+/// past any real .debug_info offset.
+const ADDED_TYPE_OFFSET: usize = 1 << 48;
+/// The types of each shared library are moved up by a multiple of this, so that their offsets
+/// don't clash with the program's or other libraries' ones.
+const LIBRARY_TYPE_OFFSET: usize = 1 << 40;
+
 pub struct DwarfData {
+    path: String,
+    /// Where separate debug files are looked for, for the shared libraries loaded later.
+    debug_dir: String,
     files: Vec<File>,
+    types: HashMap<usize, Type>,
+    /// Number of types added with intern_type.
+    added_types: usize,
     addr2line: Context<addr2line::gimli::EndianRcSlice<addr2line::gimli::RunTimeEndian>>,
+    /// Difference between the addresses the file is loaded at and the ones it was linked at,
+    /// which all addresses in files have been moved by.
+    bias: usize,
+    /// Lowest (page aligned) and highest link-time addresses of the file's loadable segments.
+    link_range: (usize, usize),
+    /// Link-time start and end of the .text section.
+    text_range: (usize, usize),
+    /// Link-time address of the program's entry point.
+    entry: usize,
+    /// Link-time address of the .dynamic section, through which the dynamic linker's list of
+    /// loaded libraries is found.
+    dynamic: Option<usize>,
+    /// Whether the file has DWARF line information, rather than just a symbol table.
+    has_debug_info: bool,
+    /// The shared libraries the inferior has loaded, relocated to where they were loaded.
+    libraries: Vec<DwarfData>,
+    /// Number of libraries loaded so far, which decides where each library's types go.
+    loaded_libraries: usize,
 }
 
 impl fmt::Debug for DwarfData {
@@ -21,6 +94,14 @@ impl fmt::Debug for DwarfData {
     }
 }
 
+/// Describes a shared library for info sharedlibrary.
+pub struct LibraryInfo {
+    pub path: String,
+    /// Runtime start and end of the library's code.
+    pub text_range: (usize, usize),
+    pub has_debug_info: bool,
+}
+
 impl From<gimli_wrapper::Error> for Error {
     fn from(err: gimli_wrapper::Error) -> Self {
         Error::DwarfFormatError(err)
@@ -28,7 +109,11 @@ impl From<gimli_wrapper::Error> for Error {
 }
 
 impl DwarfData {
-    pub fn from_file(path: &str) -> Result<DwarfData, Error> {
+    /// Loads the symbols of a program or shared library. The DWARF data of a stripped file is
+    /// looked for in a separate debug file, next to it or under debug_dir. Functions are also
+    /// read from the symbol tables, so that files without DWARF data, like the C library, can
+    /// still be broken on and show up in backtraces.
+    pub fn from_file(path: &str, debug_dir: &str) -> Result<DwarfData, Error> {
         let file = fs::File::open(path).or(Err(Error::ErrorOpeningFile))?;
         let mmap = unsafe { memmap::Mmap::map(&file).or(Err(Error::ErrorOpeningFile))? };
         let object = object::File::parse(&*mmap)
@@ -38,60 +123,322 @@ impl DwarfData {
         } else {
             gimli::RunTimeEndian::Big
         };
+        let debug_file = match object.section_by_name(".debug_info") {
+            Some(_) => None,
+            None => find_debug_file(&object, path, debug_dir),
+        };
+        let debug_mmap = debug_file
+            .and_then(|debug_path| fs::File::open(debug_path).ok())
+            .and_then(|debug_file| unsafe { memmap::Mmap::map(&debug_file).ok() });
+        let debug_object = debug_mmap.as_ref().and_then(|data| object::File::parse(&**data).ok());
+        let dwarf_object = debug_object.as_ref().unwrap_or(&object);
+
+        let (mut files, types) = match gimli_wrapper::load_file(dwarf_object, endian) {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                output!("warning: could not read DWARF data from {}: {:?}", path, err);
+                (Vec::new(), HashMap::new())
+            }
+        };
+        let has_debug_info = files.iter().any(|file| !file.lines.is_empty());
+        let mut symbol_objects = vec![&object];
+        symbol_objects.extend(debug_object.as_ref());
+        if let Some(symbols) = symbol_file(&symbol_objects, path, &files) {
+            files.push(symbols);
+        }
+        let segments: Vec<(usize, usize)> = object
+            .segments()
+            .map(|segment| (segment.address() as usize, (segment.address() + segment.size()) as usize))
+            .filter(|(start, end)| end > start)
+            .collect();
+        let link_range = (
+            segments.iter().map(|(start, _)| start & !0xfff).min().unwrap_or(0),
+            segments.iter().map(|(_, end)| *end).max().unwrap_or(0),
+        );
+        let text_range = object.section_by_name(".text").map_or((0, 0), |text| {
+            (text.address() as usize, (text.address() + text.size()) as usize)
+        });
         Ok(DwarfData {
-            files: gimli_wrapper::load_file(&object, endian)?,
-            addr2line: Context::new(&object).or_else(|e| Err(gimli_wrapper::Error::from(e)))?,
+            path: path.to_string(),
+            debug_dir: debug_dir.to_string(),
+            files,
+            types,
+            added_types: 0,
+            addr2line: Context::new(dwarf_object)
+                .or_else(|e| Err(gimli_wrapper::Error::from(e)))?,
+            bias: 0,
+            link_range,
+            text_range,
+            entry: object.entry() as usize,
+            dynamic: object.section_by_name(".dynamic").map(|dynamic| dynamic.address() as usize),
+            has_debug_info,
+            libraries: Vec::new(),
+            loaded_libraries: 0,
         })
     }
 
-    #[allow(dead_code)]
-    fn get_target_file(&self, file: &str) -> Option<&File> {
-        self.files.iter().find(|f| {
-            f.name == file || (!file.contains("/") && f.name.ends_with(&format!("/{}", file)))
-        })
+    /// Returns the compilation units, for listing files and functions.
+    pub fn get_files(&self) -> &[File] {
+        &self.files
     }
 
-    #[allow(dead_code)]
-    pub fn get_addr_for_line(&self, file: Option<&str>, line_number: usize) -> Option<usize> {
+    /// Returns the program followed by the shared libraries loaded into it.
+    fn objects(&self) -> impl Iterator<Item = &DwarfData> {
+        iter::once(self).chain(self.libraries.iter())
+    }
+
+    /// Returns the program or library whose loadable segments contain addr.
+    fn object_at(&self, addr: usize) -> &DwarfData {
+        self.libraries
+            .iter()
+            .find(|library| {
+                let link_addr = addr.wrapping_sub(library.bias);
+                link_addr >= library.link_range.0 && link_addr < library.link_range.1
+            })
+            .unwrap_or(self)
+    }
+
+    /// Returns the lowest address the file was linked at, which its first mapping is loaded at
+    /// plus the bias.
+    pub fn link_base(&self) -> usize {
+        self.link_range.0
+    }
+
+    /// Returns the link-time address of the program's entry point.
+    pub fn entry(&self) -> usize {
+        self.entry
+    }
+
+    /// Returns the runtime address of the .dynamic section, if the program has one.
+    pub fn dynamic_address(&self) -> Option<usize> {
+        self.dynamic.map(|addr| addr.wrapping_add(self.bias))
+    }
+
+    pub fn bias(&self) -> usize {
+        self.bias
+    }
+
+    /// Returns whether the file has DWARF line information, rather than just a symbol table.
+    pub fn has_debug_info(&self) -> bool {
+        self.has_debug_info
+    }
+
+    /// Moves every address to account for the file being loaded bias bytes away from where it
+    /// was linked. Position independent executables and shared libraries can be loaded anywhere.
+    pub fn relocate(&mut self, bias: usize) {
+        let delta = bias.wrapping_sub(self.bias);
+        self.bias = bias;
+        let relocate_variable = |var: &mut Variable| {
+            if let Location::Address(addr) = var.location {
+                var.location = Location::Address(addr.wrapping_add(delta));
+            }
+        };
+        for file in &mut self.files {
+            file.global_variables.iter_mut().for_each(relocate_variable);
+            for func in &mut file.functions {
+                func.address = func.address.wrapping_add(delta);
+                func.variables.iter_mut().for_each(relocate_variable);
+            }
+            for line in &mut file.lines {
+                line.address = line.address.wrapping_add(delta);
+            }
+        }
+    }
+
+    /// Moves all type offsets up by base.
+    fn rebase_types(&mut self, base: usize) {
+        self.types = self
+            .types
+            .drain()
+            .map(|(offset, mut rebased)| {
+                rebased.rebase(base);
+                (offset + base, rebased)
+            })
+            .collect();
+        for file in &mut self.files {
+            let variables = file
+                .functions
+                .iter_mut()
+                .flat_map(|func| func.variables.iter_mut())
+                .chain(file.global_variables.iter_mut());
+            for var in variables {
+                var.entity_type.rebase(base);
+            }
+            for func in &mut file.functions {
+                func.return_type = func.return_type.map(|offset| offset + base);
+            }
+        }
+    }
+
+    /// Brings the shared libraries up to date with the ones the inferior has loaded, given as
+    /// paths with the bias each was loaded at. Libraries that are still loaded in the same place
+    /// are kept. Returns whether anything changed.
+    pub fn update_libraries(&mut self, loaded: &[(String, usize)]) -> bool {
+        let previous = self.libraries.len();
+        self.libraries
+            .retain(|library| loaded.contains(&(library.path.clone(), library.bias)));
+        let mut changed = self.libraries.len() != previous;
+        for (path, bias) in loaded {
+            if self.libraries.iter().any(|library| library.path == *path) {
+                continue;
+            }
+            match DwarfData::from_file(path, &self.debug_dir) {
+                Ok(mut library) => {
+                    self.loaded_libraries += 1;
+                    library.rebase_types(self.loaded_libraries * LIBRARY_TYPE_OFFSET);
+                    library.relocate(*bias);
+                    self.libraries.push(library);
+                    changed = true;
+                }
+                Err(err) => output!("Could not load symbols for {}: {:?}", path, err),
+            }
+        }
+        changed
+    }
+
+    /// Describes the shared libraries that have been loaded.
+    pub fn get_libraries(&self) -> Vec<LibraryInfo> {
+        self.libraries
+            .iter()
+            .map(|library| LibraryInfo {
+                path: library.path.clone(),
+                text_range: (
+                    library.text_range.0.wrapping_add(library.bias),
+                    library.text_range.1.wrapping_add(library.bias),
+                ),
+                has_debug_info: library.has_debug_info,
+            })
+            .collect()
+    }
+
+    /// Finds the compilation unit for a file name in the program or, failing that, in one of the
+    /// shared libraries.
+    fn get_target_file(&self, file: &str) -> Result<&File, LocationError> {
+        let mut result = self.find_file(file);
+        for library in &self.libraries {
+            match result {
+                Err(LocationError::NoSuchFile(_)) => result = library.find_file(file),
+                _ => break,
+            }
+        }
+        result
+    }
+
+    /// Finds the compilation unit for a file name, which can be the full name recorded in the
+    /// DWARF data, a trailing part of it such as "foo.c", or a path ending in it.
+    fn find_file(&self, file: &str) -> Result<&File, LocationError> {
+        if let Some(exact) = self.files.iter().find(|f| f.name == file) {
+            return Ok(exact);
+        }
+        let matches: Vec<&File> = self
+            .files
+            .iter()
+            .filter(|f| {
+                f.name.ends_with(&format!("/{}", file)) || file.ends_with(&format!("/{}", f.name))
+            })
+            .collect();
+        match matches.len() {
+            0 => Err(LocationError::NoSuchFile(file.to_string())),
+            1 => Ok(matches[0]),
+            _ => Err(LocationError::AmbiguousFile(
+                file.to_string(),
+                matches.iter().map(|f| f.name.clone()).collect(),
+            )),
+        }
+    }
+
+    /// Returns the path of a source file as the line table records it, which unlike the
+    /// compilation unit name includes the compilation directory.
+    pub fn get_source_path(&self, file: &str) -> Result<String, LocationError> {
+        let file = self.get_target_file(file)?;
+        Ok(file
+            .lines
+            .first()
+            .and_then(|line| self.get_line_from_addr(line.address))
+            .map_or(file.name.clone(), |line| line.file))
+    }
+
+    /// Returns the first address of the given line, or of the next line after it that has code.
+    /// Without a file, the first compilation unit is used.
+    pub fn get_addr_for_line(
+        &self,
+        file: Option<&str>,
+        line_number: usize,
+    ) -> Result<usize, LocationError> {
         let target_file = match file {
             Some(filename) => self.get_target_file(filename)?,
-            None => self.files.get(0)?,
+            None => self
+                .files
+                .get(0)
+                .ok_or(LocationError::NoSuchFile("<none>".to_string()))?,
         };
-        Some(
-            target_file
-                .lines
-                .iter()
-                .find(|line| line.number >= line_number)?
-                .address,
-        )
+        let no_line = || LocationError::NoSuchLine(target_file.name.clone(), line_number);
+        let number = target_file
+            .lines
+            .iter()
+            .map(|line| line.number)
+            .filter(|number| *number >= line_number)
+            .min()
+            .ok_or_else(no_line)?;
+        target_file
+            .lines
+            .iter()
+            .filter(|line| line.number == number)
+            .map(|line| line.address)
+            .min()
+            .ok_or_else(no_line)
     }
 
-    #[allow(dead_code)]
-    pub fn get_addr_for_function(&self, file: Option<&str>, func_name: &str) -> Option<usize> {
-        match file {
-            Some(filename) => Some(
-                self.get_target_file(filename)?
-                    .functions
-                    .iter()
-                    .find(|func| func.name == func_name)?
-                    .address,
-            ),
-            None => {
-                for file in &self.files {
-                    if let Some(func) = file.functions.iter().find(|func| func.name == func_name) {
-                        return Some(func.address);
-                    }
+    /// Returns the address of a function. Without a file, the function must only be defined in
+    /// one compilation unit of the first object (the program, then each shared library) that
+    /// defines it.
+    pub fn get_addr_for_function(
+        &self,
+        file: Option<&str>,
+        func_name: &str,
+    ) -> Result<usize, LocationError> {
+        let searched: Vec<Vec<&File>> = match file {
+            Some(filename) => vec![vec![self.get_target_file(filename)?]],
+            None => self.objects().map(|object| object.files.iter().collect()).collect(),
+        };
+        for files in searched {
+            // Declarations of functions defined elsewhere have no address
+            let defined: Vec<(&File, &Function)> = files
+                .iter()
+                .flat_map(|file| file.functions.iter().map(move |func| (*file, func)))
+                .filter(|(_, func)| func.text_length > 0)
+                .collect();
+            let mut matches: Vec<(&File, &Function)> =
+                defined.iter().filter(|(_, func)| func.name == func_name).cloned().collect();
+            // a Rust or C++ function can also be given without the path it's in, e.g. add for
+            // crate::module::add
+            if matches.is_empty() {
+                let suffix = format!("::{}", func_name);
+                matches = defined
+                    .into_iter()
+                    .filter(|(_, func)| func.name.ends_with(&suffix))
+                    .collect();
+            }
+            match matches.len() {
+                0 => continue,
+                1 => return Ok(matches[0].1.address),
+                _ => {
+                    return Err(LocationError::AmbiguousFunction(
+                        func_name.to_string(),
+                        matches.iter().map(|(file, _)| file.name.clone()).collect(),
+                    ))
                 }
-                None
             }
         }
+        Err(LocationError::NoSuchFunction(func_name.to_string()))
     }
 
     #[allow(dead_code)]
     pub fn get_line_from_addr(&self, curr_addr: usize) -> Option<Line> {
-        let location = self
+        let object = self.object_at(curr_addr);
+        let location = object
             .addr2line
-            .find_location(curr_addr.try_into().unwrap())
+            .find_location(curr_addr.wrapping_sub(object.bias).try_into().unwrap())
             .ok()??;
         Some(Line {
             file: location.file?.to_string(),
@@ -102,63 +449,316 @@ impl DwarfData {
 
     #[allow(dead_code)]
     pub fn get_function_from_addr(&self, curr_addr: usize) -> Option<String> {
-        let frame = self
+        let object = self.object_at(curr_addr);
+        let frame = object
             .addr2line
-            .find_frames(curr_addr.try_into().unwrap())
-            .ok()?
-            .next()
-            .ok()??;
-        Some(frame.function?.raw_name().ok()?.to_string())
+            .find_frames(curr_addr.wrapping_sub(object.bias).try_into().unwrap())
+            .ok()
+            .and_then(|mut frames| frames.next().ok()?);
+        match frame.and_then(|frame| frame.function) {
+            Some(function) => {
+                let raw_name = function.raw_name().ok()?;
+                Some(demangle(&raw_name).unwrap_or_else(|| raw_name.to_string()))
+            }
+            // functions only known from a symbol table
+            None => self.get_function(curr_addr).map(|func| func.name.clone()),
+        }
+    }
+
+    /// Returns the function whose code contains curr_addr.
+    pub fn get_function(&self, curr_addr: usize) -> Option<&Function> {
+        self.objects()
+            .flat_map(|object| object.files.iter())
+            .flat_map(|file| file.functions.iter())
+            .find(|func| curr_addr >= func.address && curr_addr < func.address + func.text_length)
+    }
+
+    /// Returns the first address in the function containing curr_addr that starts a new source
+    /// line after the function's entry, i.e. the address just past the prologue.
+    pub fn get_function_body_addr(&self, curr_addr: usize) -> Option<usize> {
+        let func = self.get_function(curr_addr)?;
+        self.object_at(curr_addr)
+            .files
+            .iter()
+            .flat_map(|file| file.lines.iter())
+            .filter(|line| {
+                line.address > func.address && line.address < func.address + func.text_length
+            })
+            .map(|line| line.address)
+            .min()
+    }
+
+    /// Looks up a variable visible from curr_addr: locals and parameters of the enclosing
+    /// function take precedence over globals.
+    pub fn get_variable(&self, curr_addr: usize, name: &str) -> Option<&Variable> {
+        if let Some(func) = self.get_function(curr_addr) {
+            if let Some(var) = func.variables.iter().find(|var| var.name == name) {
+                return Some(var);
+            }
+        }
+        self.objects()
+            .flat_map(|object| object.files.iter())
+            .flat_map(|file| file.global_variables.iter())
+            .find(|var| var.name == name)
+    }
+
+    /// Returns the global variable whose storage contains addr.
+    pub fn get_global_variable(&self, addr: usize) -> Option<&Variable> {
+        self.objects()
+            .flat_map(|object| object.files.iter())
+            .flat_map(|file| file.global_variables.iter())
+            .find(|var| match var.location {
+                Location::Address(start) => {
+                    addr >= start && addr < start + var.entity_type.size.max(1)
+                }
+                _ => false,
+            })
+    }
+
+    /// Returns the type whose DIE is at the given .debug_info offset.
+    pub fn get_type(&self, offset: usize) -> Option<&Type> {
+        self.objects().find_map(|object| object.types.get(&offset))
+    }
+
+    /// Returns the types of the program and its shared libraries.
+    fn all_types(&self) -> impl Iterator<Item = &Type> {
+        self.objects().flat_map(|object| object.types.values())
+    }
+
+    /// Finds a type by its name as C would write it, such as "int" or "struct point".
+    pub fn get_type_by_name(&self, name: &str) -> Option<&Type> {
+        // declarations of incomplete types have no size
+        self.all_types().find(|t| t.name == name && t.size > 0)
+    }
+
+    /// Returns the type with the same name and kind as new_type, adding new_type if the program
+    /// has none, e.g. a pointer type that it never uses.
+    pub fn intern_type(&mut self, mut new_type: Type) -> Type {
+        if let Some(existing) = self
+            .all_types()
+            .find(|t| t.name == new_type.name && t.kind == new_type.kind)
+        {
+            return existing.clone();
+        }
+        let offset = ADDED_TYPE_OFFSET + self.added_types;
+        self.added_types += 1;
+        new_type.offset = Some(offset);
+        self.types.insert(offset, new_type.clone());
+        new_type
+    }
+
+    /// Returns the type of pointers to target_type.
+    pub fn pointer_to(&mut self, target_type: &Type) -> Type {
+        let target = match target_type.offset {
+            Some(offset) => offset,
+            None => self.intern_type(target_type.clone()).offset.unwrap(),
+        };
+        let name = match target_type.kind {
+            TypeKind::Function(_) => {
+                format!("{} (*)()", target_type.name.trim_end_matches(" ()"))
+            }
+            // e.g. "int (*)[3]" for pointers to "int [3]"
+            TypeKind::Array(_, _) if target_type.name.contains(" [") => {
+                let split = target_type.name.find(" [").unwrap();
+                let (element, dimensions) = target_type.name.split_at(split);
+                format!("{} (*){}", element, &dimensions[1..])
+            }
+            _ => format!("{} *", target_type.name),
+        };
+        self.intern_type(Type::new(name, 8, TypeKind::Pointer(Some(target))))
+    }
+
+    /// Finds an enumeration constant, returning its enum type and value.
+    pub fn get_enumerator(&self, name: &str) -> Option<(&Type, i64)> {
+        self.all_types().find_map(|t| match &t.kind {
+            TypeKind::Enum(variants) => variants
+                .iter()
+                .find(|(variant, _)| variant == name)
+                .map(|(_, value)| (t, *value)),
+            _ => None,
+        })
+    }
+
+    /// Follows typedefs and const/volatile qualifiers to the underlying type.
+    pub fn strip_typedefs<'a>(&'a self, mut var_type: &'a Type) -> &'a Type {
+        for _ in 0..16 {
+            match var_type.kind {
+                TypeKind::Typedef(Some(target)) | TypeKind::Qualified(_, Some(target)) => {
+                    match self.get_type(target) {
+                        Some(target_type) => var_type = target_type,
+                        None => break,
+                    }
+                }
+                _ => break,
+            }
+        }
+        var_type
     }
 
     #[allow(dead_code)]
     pub fn print(&self) {
         for file in &self.files {
-            println!("------");
-            println!("{}", file.name);
-            println!("------");
+            output!("------");
+            output!("{}", file.name);
+            output!("------");
 
-            println!("Global variables:");
+            output!("Global variables:");
             for var in &file.global_variables {
-                println!(
+                output!(
                     "  * {} ({}, located at {}, declared at line {})",
                     var.name, var.entity_type.name, var.location, var.line_number
                 );
             }
 
-            println!("Functions:");
+            output!("Functions:");
             for func in &file.functions {
-                println!(
+                output!(
                     "  * {} (declared on line {}, located at {:#x}, {} bytes long)",
                     func.name, func.line_number, func.address, func.text_length
                 );
                 for var in &func.variables {
-                    println!(
+                    output!(
                         "    * Variable: {} ({}, located at {}, declared at line {})",
                         var.name, var.entity_type.name, var.location, var.line_number
                     );
                 }
             }
 
-            println!("Line numbers:");
+            output!("Line numbers:");
             for line in &file.lines {
-                println!("  * {} (at {:#x})", line.number, line.address);
+                output!("  * {} (at {:#x})", line.number, line.address);
             }
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BaseEncoding {
+    Signed,
+    Unsigned,
+    SignedChar,
+    UnsignedChar,
+    Float,
+    Boolean,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Qualifier {
+    Const,
+    Volatile,
+}
+
+/// A field of a struct or union.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub name: String,
+    pub type_offset: usize,
+    /// Byte offset of the member from the start of the struct.
+    pub offset: usize,
+}
+
+/// One of the variants of a Rust enum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    /// The discriminant value that selects the variant, or None for the variant that is selected
+    /// when no other one's value matches.
+    pub discriminant: Option<u64>,
+    /// The member holding the variant's fields, whose type is a struct named after the variant.
+    pub member: Member,
+}
+
+/// Types refer to other types by the offset of their DIE, which can be looked up with
+/// DwarfData::get_type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeKind {
+    Base(BaseEncoding),
+    /// Contains the offset of the pointed-to type, or None for void pointers.
+    Pointer(Option<usize>),
+    Struct(Vec<Member>),
+    Union(Vec<Member>),
+    /// A Rust enum with data. Contains the member holding the discriminant, which enums with
+    /// only one variant don't have, and the variants.
+    Variants(Option<Member>, Vec<Variant>),
+    /// Contains the element type and the length of each dimension.
+    Array(usize, Vec<usize>),
+    /// Contains the name and value of each variant.
+    Enum(Vec<(String, i64)>),
+    Typedef(Option<usize>),
+    Qualified(Qualifier, Option<usize>),
+    /// Contains the return type, or None for void functions.
+    Function(Option<usize>),
+    Unknown,
+}
+
+impl Default for TypeKind {
+    fn default() -> Self {
+        TypeKind::Unknown
+    }
+}
+
+/// The language a type was defined in, which decides how its values are printed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    C,
+    Rust,
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Language::C
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Type {
     pub name: String,
     pub size: usize,
+    pub kind: TypeKind,
+    /// Where DwarfData keeps the type, so that other types can refer to it.
+    pub offset: Option<usize>,
+    pub language: Language,
+    /// Offsets of the type parameters of a generic type, such as T in Vec<T>.
+    pub params: Vec<usize>,
 }
 
 impl Type {
-    pub fn new(name: String, size: usize) -> Self {
+    pub fn new(name: String, size: usize, kind: TypeKind) -> Self {
         Type {
             name: name,
             size: size,
+            kind: kind,
+            offset: None,
+            language: Language::C,
+            params: Vec::new(),
+        }
+    }
+
+    /// Moves the offsets of the type and the types it refers to up by base.
+    fn rebase(&mut self, base: usize) {
+        self.offset = self.offset.map(|offset| offset + base);
+        match &mut self.kind {
+            TypeKind::Pointer(Some(target))
+            | TypeKind::Typedef(Some(target))
+            | TypeKind::Qualified(_, Some(target))
+            | TypeKind::Function(Some(target))
+            | TypeKind::Array(target, _) => *target += base,
+            TypeKind::Struct(members) | TypeKind::Union(members) => {
+                for member in members {
+                    member.type_offset += base;
+                }
+            }
+            TypeKind::Variants(discriminant, variants) => {
+                let members = discriminant
+                    .iter_mut()
+                    .chain(variants.iter_mut().map(|variant| &mut variant.member));
+                for member in members {
+                    member.type_offset += base;
+                }
+            }
+            _ => {}
+        }
+        for param in &mut self.params {
+            *param += base;
         }
     }
 }
@@ -191,6 +791,8 @@ pub struct Variable {
     pub entity_type: Type,
     pub location: Location,
     pub line_number: usize, // Line number in source file
+    /// Whether this is one of a function's parameters rather than a local variable.
+    pub is_parameter: bool,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -199,7 +801,13 @@ pub struct Function {
     pub address: usize,
     pub text_length: usize,
     pub line_number: usize, // Line number in source file
+    /// Offset of the type the function returns, or None for void functions.
+    pub return_type: Option<usize>,
     pub variables: Vec<Variable>,
+    /// The DWARF number of the register that the function's variables are found relative to,
+    /// e.g. %rsp in Rust programs, or None if they are relative to the canonical frame address
+    /// as gcc has it.
+    pub frame_base: Option<u16>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -224,3 +832,162 @@ impl fmt::Display for Line {
 }
 
 
+
+/// Makes a compilation unit, named after the file, out of the functions in the symbol tables of
+/// the file and its debug file that the DWARF data doesn't already describe.
+fn symbol_file(objects: &[&object::File], path: &str, files: &[File]) -> Option<File> {
+    let described: HashSet<&str> = files
+        .iter()
+        .flat_map(|file| file.functions.iter())
+        .map(|func| func.name.as_str())
+        .collect();
+    let mut functions: Vec<Function> = objects
+        .iter()
+        .flat_map(|object| object.symbols().chain(object.dynamic_symbols()))
+        .map(|(_, symbol)| symbol)
+        .filter(|symbol| symbol.kind() == SymbolKind::Text && !symbol.is_undefined())
+        .filter(|symbol| symbol.size() > 0)
+        .filter_map(|symbol| {
+            let name = symbol.name()?;
+            Some(Function {
+                name: demangle(name).unwrap_or_else(|| name.to_string()),
+                address: symbol.address() as usize,
+                text_length: symbol.size() as usize,
+                ..Default::default()
+            })
+        })
+        .filter(|func| !described.contains(func.name.as_str()))
+        .collect();
+    if functions.is_empty() {
+        return None;
+    }
+    // .symtab and .dynsym list many of the same functions. Of several names for the same code,
+    // the public one (puts rather than _IO_puts) comes first, so that it's the one shown.
+    functions.sort_by_key(|func| (func.address, func.name.starts_with('_'), func.name.clone()));
+    functions.dedup_by(|a, b| a.address == b.address && a.name == b.name);
+    Some(File {
+        name: path.to_string(),
+        functions,
+        ..Default::default()
+    })
+}
+
+/// Demangles the name of a Rust function, in either the legacy or the v0 scheme, or of a C++
+/// one. The hash at the end of legacy Rust names and the parameter types of C++ ones are left
+/// out, giving names like crate::module::func. Returns None for names that aren't mangled.
+pub fn demangle(name: &str) -> Option<String> {
+    if !name.starts_with("_Z") && !name.starts_with("_R") {
+        return None;
+    }
+    if let Some(demangled) = addr2line::demangle(name, addr2line::gimli::DW_LANG_Rust) {
+        return Some(demangled);
+    }
+    let demangled = addr2line::demangle(name, addr2line::gimli::DW_LANG_C_plus_plus)?;
+    // drop the parameter list, e.g. "(int, char*) const"
+    let mut depth = 0;
+    for (index, c) in demangled.char_indices().rev() {
+        match c {
+            ')' => depth += 1,
+            '(' if depth == 1 => return Some(demangled[..index].to_string()),
+            '(' => depth -= 1,
+            _ => {}
+        }
+    }
+    Some(demangled)
+}
+
+/// Finds the separate debug file of a stripped file the way gdb does: by the build ID under
+/// debug_dir/.build-id, or by the name in .gnu_debuglink next to the file, in a .debug directory
+/// beside it, or under debug_dir followed by the file's directory. A debug link also records
+/// the CRC-32 of the debug file, which has to match.
+fn find_debug_file(object: &object::File, path: &str, debug_dir: &str) -> Option<String> {
+    if let Some(note) = object.section_data_by_name(".note.gnu.build-id") {
+        // namesz, descsz and type, then the name "GNU\0", then the ID
+        let id = note.get(16..)?;
+        if id.len() > 1 {
+            let hex: Vec<String> = id.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let candidate =
+                format!("{}/.build-id/{}/{}.debug", debug_dir, hex[0], hex[1..].concat());
+            if Path::new(&candidate).is_file() {
+                return Some(candidate);
+            }
+        }
+    }
+    let link = object.section_data_by_name(".gnu_debuglink")?;
+    let name_end = link.iter().position(|byte| *byte == 0)?;
+    let name = String::from_utf8_lossy(&link[..name_end]).to_string();
+    // the CRC follows the name, aligned to 4 bytes
+    let crc_start = (name_end + 4) & !3;
+    let crc = u32::from_le_bytes(link.get(crc_start..crc_start + 4)?.try_into().ok()?);
+    let dir = fs::canonicalize(path).ok()?.parent()?.to_string_lossy().to_string();
+    let candidates = vec![
+        format!("{}/{}", dir, name),
+        format!("{}/.debug/{}", dir, name),
+        format!("{}{}/{}", debug_dir, dir, name),
+    ];
+    candidates.into_iter().find(|candidate| {
+        // the link usually names a file other than the stripped one, but not always
+        fs::canonicalize(candidate).ok() != fs::canonicalize(path).ok()
+            && fs::read(candidate).map_or(false, |data| crc32(&data) == crc)
+    })
+}
+
+/// Computes the CRC-32 that .gnu_debuglink sections record.
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (index, entry) in table.iter_mut().enumerate() {
+        *entry = (0..8).fold(index as u32, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            }
+        });
+    }
+    !data.iter().fold(!0u32, |crc, byte| {
+        table[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demangles_legacy_rust_names() {
+        assert_eq!(
+            demangle("_ZN4core3fmt5write17h0123456789abcdefE").as_deref(),
+            Some("core::fmt::write")
+        );
+        assert_eq!(
+            demangle("_ZN3std2rt10lang_start28_$u7b$$u7b$closure$u7d$$u7d$17h7e4b6fbd9a3b3a28E")
+                .as_deref(),
+            Some("std::rt::lang_start::{{closure}}")
+        );
+    }
+
+    #[test]
+    fn demangles_v0_rust_names() {
+        assert_eq!(demangle("_RNvCs1234_7mycrate4main").as_deref(), Some("mycrate::main"));
+        assert_eq!(
+            demangle("_RNvNtCs1234_7mycrate6module4func").as_deref(),
+            Some("mycrate::module::func")
+        );
+    }
+
+    #[test]
+    fn demangles_cpp_names_without_parameters() {
+        assert_eq!(demangle("_Z3addii").as_deref(), Some("add"));
+        assert_eq!(demangle("_ZN3foo3Bar6methodEPKc").as_deref(), Some("foo::Bar::method"));
+        assert_eq!(demangle("_ZNK3foo3Bar4sizeEv").as_deref(), Some("foo::Bar::size"));
+        // the parameter list of a function pointer parameter doesn't end the name early
+        assert_eq!(demangle("_Z5applyPFiiEi").as_deref(), Some("apply"));
+    }
+
+    #[test]
+    fn leaves_plain_names_alone() {
+        assert_eq!(demangle("main"), None);
+        assert_eq!(demangle("print_entity"), None);
+        assert_eq!(demangle("_start"), None);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::json::Json;
+use crate::syscalls;
+use std::cell::RefCell;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Emits a line of text for the user, formatted like println!.
+macro_rules! output {
+    ($($arg:tt)*) => {
+        crate::events::emit(crate::events::Event::Output(format!($($arg)*)))
+    };
+}
+
+/// Something deet tells whoever is driving it. On the terminal every event is a line of text,
+/// while --interpreter=json turns each into a JSON object.
+pub enum Event {
+    /// A line of text for the user: results of commands, messages and errors.
+    Output(String),
+    /// A line the inferior wrote, when deet is capturing its output.
+    TargetOutput(String),
+    BreakpointSet {
+        id: usize,
+        addr: usize,
+        function: Option<String>,
+        file: Option<String>,
+        line: Option<usize>,
+        /// The message of a logpoint.
+        message: Option<String>,
+    },
+    /// A logpoint was hit. Contains its number, the seconds since the inferior started and the
+    /// message with its expressions filled in.
+    Log {
+        logpoint: usize,
+        time: f64,
+        message: String,
+    },
+    /// The inferior stopped. Contains the breakpoints it stopped at, if any.
+    Stopped {
+        signal: String,
+        addr: usize,
+        thread: Option<usize>,
+        breakpoints: Vec<usize>,
+        function: Option<String>,
+        file: Option<String>,
+        line: Option<usize>,
+    },
+    /// The inferior stopped at the entry or exit of a syscall that a catchpoint caught. The
+    /// function, file and line are those of the code that made the syscall.
+    Syscall {
+        catchpoint: usize,
+        number: usize,
+        /// The syscall with its arguments decoded.
+        call: String,
+        /// What the syscall returned, or None at its entry.
+        result: Option<i64>,
+        addr: usize,
+        thread: Option<usize>,
+        function: Option<String>,
+        file: Option<String>,
+        line: Option<usize>,
+    },
+    Exited(i32),
+    /// The inferior was killed by the given signal.
+    Signaled(String),
+    /// A command could not be carried out. This finishes the request under --interpreter=json.
+    Error(String),
+}
+
+/// Whether events are written as JSON objects rather than as text.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// The id of the JSON request being carried out, which is attached to the events it causes.
+    /// None once its result has been sent.
+    static REQUEST_ID: RefCell<Option<Json>> = RefCell::new(None);
+}
+
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::SeqCst);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::SeqCst)
+}
+
+/// Starts a JSON request with the given id.
+pub fn begin_request(id: Json) {
+    REQUEST_ID.with(|request| *request.borrow_mut() = Some(id));
+}
+
+/// Sends the result of the current JSON request, unless it has already failed.
+pub fn finish_request() {
+    if let Some(id) = REQUEST_ID.with(|request| request.borrow_mut().take()) {
+        write_json(vec![("type", "result".into()), ("id", id), ("status", "done".into())]);
+    }
+}
+
+/// Reports an event to the user or the program driving deet.
+pub fn emit(event: Event) {
+    if is_json() {
+        emit_json(event);
+        return;
+    }
+    match event {
+        Event::Output(text) | Event::TargetOutput(text) | Event::Error(text) => {
+            println!("{}", text)
+        }
+        Event::BreakpointSet { id, addr, message: None, .. } => {
+            println!("Set breakpoint {} at {:#x}", id, addr)
+        }
+        Event::BreakpointSet { id, addr, .. } => println!("Set logpoint {} at {:#x}", id, addr),
+        Event::Log { time, message, .. } => println!("[{:.6}] {}", time, message),
+        Event::Stopped { signal, .. } => println!("Child stopped (signal {})", signal),
+        Event::Syscall { catchpoint, number, call, result: None, .. } => println!(
+            "Catchpoint {} (call to syscall {}), {}",
+            catchpoint,
+            syscalls::describe(number),
+            call
+        ),
+        Event::Syscall { catchpoint, number, call, result: Some(result), .. } => println!(
+            "Catchpoint {} (returned from syscall {}), {} = {}",
+            catchpoint,
+            syscalls::describe(number),
+            call,
+            syscalls::format_result(number, result)
+        ),
+        Event::Exited(status) => println!("Child exited (status {})", status),
+        Event::Signaled(signal) => println!("Child exited (signal {})", signal),
+    }
+}
+
+fn emit_json(event: Event) {
+    let id = REQUEST_ID.with(|request| request.borrow().clone());
+    let mut members: Vec<(&str, Json)> = match event {
+        Event::Output(text) => vec![("type", "output".into()), ("text", text.into())],
+        Event::TargetOutput(text) => {
+            // the inferior's output isn't a response to anything
+            return write_json(vec![("type", "target-output".into()), ("text", text.into())]);
+        }
+        Event::BreakpointSet { id, addr, function, file, line, message } => vec![
+            ("type", "breakpoint-set".into()),
+            ("number", id.into()),
+            ("address", format!("{:#x}", addr).into()),
+            ("function", function.into()),
+            ("file", file.into()),
+            ("line", line.into()),
+            ("message", message.into()),
+        ],
+        Event::Log { logpoint, time, message } => vec![
+            ("type", "log".into()),
+            ("logpoint", logpoint.into()),
+            ("time", Json::Number(time)),
+            ("message", message.into()),
+        ],
+        Event::Stopped { signal, addr, thread, breakpoints, function, file, line } => vec![
+            ("type", "stopped".into()),
+            ("signal", signal.into()),
+            ("address", format!("{:#x}", addr).into()),
+            ("thread", thread.into()),
+            ("breakpoints", Json::Array(breakpoints.into_iter().map(Json::from).collect())),
+            ("function", function.into()),
+            ("file", file.into()),
+            ("line", line.into()),
+        ],
+        Event::Syscall {
+            catchpoint,
+            number,
+            call,
+            result,
+            addr,
+            thread,
+            function,
+            file,
+            line,
+        } => vec![
+            ("type", "syscall".into()),
+            ("catchpoint", catchpoint.into()),
+            ("syscall", syscalls::describe(number).into()),
+            ("number", number.into()),
+            ("call", call.into()),
+            ("result", result.into()),
+            ("address", format!("{:#x}", addr).into()),
+            ("thread", thread.into()),
+            ("function", function.into()),
+            ("file", file.into()),
+            ("line", line.into()),
+        ],
+        Event::Exited(status) => vec![("type", "exited".into()), ("status", status.into())],
+        Event::Signaled(signal) => vec![("type", "exited".into()), ("signal", signal.into())],
+        Event::Error(message) => {
+            // an error is the result of the request
+            REQUEST_ID.with(|request| request.borrow_mut().take());
+            vec![
+                ("type", "result".into()),
+                ("id", id.clone().into()),
+                ("status", "error".into()),
+                ("message", message.into()),
+            ]
+        }
+    };
+    if !members.iter().any(|(key, _)| *key == "id") {
+        members.insert(1, ("id", id.into()));
+    }
+    write_json(members);
+}
+
+/// Writes an object on a line of its own.
+fn write_json(members: Vec<(&str, Json)>) {
+    let object = Json::Object(members.into_iter().map(|(k, v)| (k.to_string(), v)).collect());
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    let _ = writeln!(out, "{}", object);
+    let _ = out.flush();
+}
@@ -0,0 +1,1667 @@
+use crate::dwarf_data::{BaseEncoding, DwarfData, LocationError, Type, TypeKind};
+use crate::registers;
+use crate::target::Target;
+use crate::value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+/// Operators and punctuation, longest first so that e.g. "<=" isn't read as "<".
+const OPERATORS: &[&str] = &[
+    "->", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "&", "|", "^",
+    "~", "!", "<", ">", "=", "(", ")", "[", "]", "{", "}", ".", "?", ":", ",",
+];
+
+/// Binary operators from lowest to highest precedence.
+const BINARY_LEVELS: [&[&str]; 10] = [
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+/// Words that can start a type name in a cast or sizeof.
+const TYPE_KEYWORDS: &[&str] = &[
+    "struct", "union", "enum", "unsigned", "signed", "char", "short", "int", "long", "float",
+    "double", "void", "_Bool", "const", "volatile",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Integer(u64),
+    Float(f64),
+    Char(u8),
+    Identifier(String),
+    /// A $register, value history entry or convenience variable, without the $.
+    Dollar(String),
+    Operator(&'static str),
+}
+
+/// A C expression.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Integer(u64),
+    Float(f64),
+    Char(u8),
+    /// A variable, function or enumeration constant.
+    Name(String),
+    /// A $register, value history entry or convenience variable, without the $.
+    Dollar(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    /// The condition, then the values if it holds and if it doesn't.
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    Assign(Box<Expr>, Box<Expr>),
+    Member(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Cast(TypeName, Box<Expr>),
+    SizeofType(TypeName),
+    SizeofExpr(Box<Expr>),
+    /// A call of a function in the program, with its arguments.
+    Call(Box<Expr>, Vec<Expr>),
+    /// A value already worked out, such as what a function call returned.
+    Value(Value),
+}
+
+impl Expr {
+    /// Returns the expressions this one is made of.
+    pub fn operands_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Expr::Unary(_, operand)
+            | Expr::Member(operand, _)
+            | Expr::Cast(_, operand)
+            | Expr::SizeofExpr(operand) => vec![operand],
+            Expr::Binary(_, lhs, rhs) | Expr::Assign(lhs, rhs) | Expr::Index(lhs, rhs) => {
+                vec![lhs, rhs]
+            }
+            Expr::Conditional(condition, if_true, if_false) => vec![condition, if_true, if_false],
+            Expr::Call(func, args) => {
+                let mut operands: Vec<&mut Expr> = vec![func];
+                operands.extend(args.iter_mut());
+                operands
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// A type written in a cast or sizeof, e.g. "struct node *".
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeName {
+    /// The type without its pointers, such as "struct node", or a base type spelled the way gcc
+    /// names it, e.g. "long unsigned int" for "unsigned long".
+    pub base: String,
+    pub pointers: usize,
+}
+
+/// Splits an expression into tokens. Expressions are ASCII, so byte offsets are used throughout.
+/// Each token comes with the offset it starts at, for error messages.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, String> {
+    let bytes = text.as_bytes();
+    let is_word = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        let token = if c.is_ascii_digit()
+            || (c == b'.' && bytes.get(i + 1).map_or(false, |b| b.is_ascii_digit()))
+        {
+            while i < bytes.len() && (is_word(bytes[i]) || bytes[i] == b'.') {
+                // a sign in an exponent, e.g. 1e-5
+                let exponent = matches!(bytes[i], b'e' | b'E') && !text[start..].starts_with("0x");
+                i += 1;
+                if exponent && i < bytes.len() && matches!(bytes[i], b'+' | b'-') {
+                    i += 1;
+                }
+            }
+            parse_number(&text[start..i])?
+        } else if is_word(c) {
+            while i < bytes.len() && is_word(bytes[i]) {
+                i += 1;
+            }
+            Token::Identifier(text[start..i].to_string())
+        } else if c == b'$' {
+            i += 1;
+            while i < bytes.len() && (is_word(bytes[i]) || bytes[i] == b'$') {
+                i += 1;
+            }
+            Token::Dollar(text[start + 1..i].to_string())
+        } else if c == b'\'' {
+            let (value, len) = match (bytes.get(i + 1), bytes.get(i + 2)) {
+                (Some(b'\\'), Some(escaped)) => (unescape(*escaped)?, 2),
+                (Some(value), _) => (*value, 1),
+                (None, _) => return Err("Unmatched single quote.".to_string()),
+            };
+            i += 1 + len;
+            if bytes.get(i) != Some(&b'\'') {
+                return Err("Unmatched single quote.".to_string());
+            }
+            i += 1;
+            Token::Char(value)
+        } else if let Some(op) = OPERATORS.iter().find(|op| text[i..].starts_with(*op)) {
+            i += op.len();
+            Token::Operator(op)
+        } else {
+            return Err(format!(
+                "Invalid character '{}' in expression.",
+                text[i..].chars().next().unwrap()
+            ));
+        };
+        tokens.push((token, start));
+    }
+    Ok(tokens)
+}
+
+fn unescape(escaped: u8) -> Result<u8, String> {
+    Ok(match escaped {
+        b'n' => b'\n',
+        b't' => b'\t',
+        b'r' => b'\r',
+        b'0' => 0,
+        b'\\' | b'\'' | b'"' => escaped,
+        _ => return Err(format!("Unknown escape sequence \\{}.", escaped as char)),
+    })
+}
+
+/// Parses an integer (decimal, 0x hex or 0 octal, with optional u and l suffixes) or a float.
+fn parse_number(text: &str) -> Result<Token, String> {
+    let invalid = || format!("Invalid number \"{}\".", text);
+    let lower = text.to_lowercase();
+    if lower.starts_with("0x") {
+        let digits = lower[2..].trim_end_matches(|c| c == 'u' || c == 'l');
+        return u64::from_str_radix(digits, 16).map(Token::Integer).map_err(|_| invalid());
+    }
+    if lower.contains('.') || lower.contains('e') {
+        return lower
+            .trim_end_matches('f')
+            .parse()
+            .map(Token::Float)
+            .map_err(|_| invalid());
+    }
+    let digits = lower.trim_end_matches(|c| c == 'u' || c == 'l');
+    let radix = if digits.len() > 1 && digits.starts_with('0') { 8 } else { 10 };
+    u64::from_str_radix(digits, radix).map(Token::Integer).map_err(|_| invalid())
+}
+
+/// Spells a base type the way gcc names it in the debugging information, e.g. "unsigned long"
+/// as "long unsigned int".
+fn canonical_base_type(words: &[String]) -> String {
+    let has = |word: &str| words.iter().any(|w| w == word);
+    let unsigned = has("unsigned");
+    let longs = words.iter().filter(|w| *w == "long").count();
+    let name = if has("void") {
+        "void"
+    } else if has("_Bool") {
+        "_Bool"
+    } else if has("float") {
+        "float"
+    } else if has("double") {
+        if longs > 0 {
+            "long double"
+        } else {
+            "double"
+        }
+    } else if has("char") {
+        if unsigned {
+            "unsigned char"
+        } else if has("signed") {
+            "signed char"
+        } else {
+            "char"
+        }
+    } else if has("short") {
+        if unsigned {
+            "short unsigned int"
+        } else {
+            "short int"
+        }
+    } else if longs >= 2 {
+        if unsigned {
+            "long long unsigned int"
+        } else {
+            "long long int"
+        }
+    } else if longs == 1 {
+        if unsigned {
+            "long unsigned int"
+        } else {
+            "long int"
+        }
+    } else if unsigned {
+        "unsigned int"
+    } else {
+        "int"
+    };
+    name.to_string()
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// Whether a name is a typedef, which decides if "(name)" starts a cast.
+    is_type: &'a dyn Fn(&str) -> bool,
+}
+
+/// Parses a C expression. is_type says whether a name is a typedef.
+pub fn parse(text: &str, is_type: &dyn Fn(&str) -> bool) -> Result<Expr, String> {
+    let mut parser = Parser {
+        text,
+        tokens: tokenize(text)?,
+        pos: 0,
+        is_type,
+    };
+    if parser.tokens.is_empty() {
+        return Err("Argument required (expression to compute).".to_string());
+    }
+    let expr = parser.assignment()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(_) => Err(parser.syntax_error()),
+    }
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn peek_operator(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Operator(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Skips over the given operator if it comes next.
+    fn accept(&mut self, op: &str) -> bool {
+        if self.peek_operator() == Some(op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.accept(op) {
+            Ok(())
+        } else {
+            Err(self.syntax_error())
+        }
+    }
+
+    fn syntax_error(&self) -> String {
+        let rest = match self.tokens.get(self.pos) {
+            Some((_, start)) => &self.text[*start..],
+            None => "",
+        };
+        format!("A syntax error in expression, near `{}'.", rest)
+    }
+
+    fn identifier(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Identifier(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.syntax_error()),
+        }
+    }
+
+    fn assignment(&mut self) -> Result<Expr, String> {
+        let lhs = self.conditional()?;
+        if self.accept("=") {
+            let rhs = self.assignment()?;
+            return Ok(Expr::Assign(Box::new(lhs), Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn conditional(&mut self) -> Result<Expr, String> {
+        let condition = self.binary(0)?;
+        if !self.accept("?") {
+            return Ok(condition);
+        }
+        let if_true = self.assignment()?;
+        self.expect(":")?;
+        let if_false = self.conditional()?;
+        Ok(Expr::Conditional(
+            Box::new(condition),
+            Box::new(if_true),
+            Box::new(if_false),
+        ))
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level == BINARY_LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(op) = self.peek_operator().filter(|op| BINARY_LEVELS[level].contains(op)) {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if let Some(op) = self.peek_operator() {
+            match op {
+                "-" | "+" | "!" | "~" | "*" | "&" => {
+                    self.pos += 1;
+                    return Ok(Expr::Unary(op, Box::new(self.unary()?)));
+                }
+                "(" if self.starts_type_name(1) => {
+                    self.pos += 1;
+                    let type_name = self.type_name()?;
+                    self.expect(")")?;
+                    return Ok(Expr::Cast(type_name, Box::new(self.unary()?)));
+                }
+                // gdb's {type} addr, the object of that type at addr
+                "{" => {
+                    self.pos += 1;
+                    let mut type_name = self.type_name()?;
+                    self.expect("}")?;
+                    type_name.pointers += 1;
+                    let addr = Expr::Cast(type_name, Box::new(self.unary()?));
+                    return Ok(Expr::Unary("*", Box::new(addr)));
+                }
+                _ => {}
+            }
+        }
+        if self.peek() == Some(&Token::Identifier("sizeof".to_string())) {
+            self.pos += 1;
+            if self.peek_operator() == Some("(") && self.starts_type_name(1) {
+                self.pos += 1;
+                let type_name = self.type_name()?;
+                self.expect(")")?;
+                return Ok(Expr::SizeofType(type_name));
+            }
+            return Ok(Expr::SizeofExpr(Box::new(self.unary()?)));
+        }
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.accept("[") {
+                let index = self.assignment()?;
+                self.expect("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else if self.accept(".") {
+                expr = Expr::Member(Box::new(expr), self.identifier()?);
+            } else if self.accept("->") {
+                let target = Expr::Unary("*", Box::new(expr));
+                expr = Expr::Member(Box::new(target), self.identifier()?);
+            } else if self.accept("(") {
+                let mut args = Vec::new();
+                while !self.accept(")") {
+                    if !args.is_empty() {
+                        self.expect(",")?;
+                    }
+                    args.push(self.assignment()?);
+                }
+                expr = Expr::Call(Box::new(expr), args);
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Integer(value)) => Ok(Expr::Integer(value)),
+            Some(Token::Float(value)) => Ok(Expr::Float(value)),
+            Some(Token::Char(value)) => Ok(Expr::Char(value)),
+            Some(Token::Identifier(name)) => Ok(Expr::Name(name)),
+            Some(Token::Dollar(name)) => Ok(Expr::Dollar(name)),
+            Some(Token::Operator("(")) => {
+                let expr = self.assignment()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            _ => {
+                self.pos -= 1;
+                Err(self.syntax_error())
+            }
+        }
+    }
+
+    /// Returns whether the token that many places ahead starts a type name.
+    fn starts_type_name(&self, ahead: usize) -> bool {
+        match self.tokens.get(self.pos + ahead) {
+            Some((Token::Identifier(name), _)) => {
+                TYPE_KEYWORDS.contains(&name.as_str()) || (self.is_type)(name)
+            }
+            _ => false,
+        }
+    }
+
+    /// Parses a type name such as "unsigned long", "struct node *" or a typedef.
+    fn type_name(&mut self) -> Result<TypeName, String> {
+        let mut words: Vec<String> = Vec::new();
+        // a struct, union or enum tag or typedef, which can't be combined with other words
+        let mut named = None;
+        while let Some(Token::Identifier(word)) = self.peek() {
+            let word = word.clone();
+            match word.as_str() {
+                "const" | "volatile" => self.pos += 1,
+                "struct" | "union" | "enum" if named.is_none() && words.is_empty() => {
+                    self.pos += 1;
+                    named = Some(format!("{} {}", word, self.identifier()?));
+                }
+                _ if TYPE_KEYWORDS.contains(&word.as_str()) && named.is_none() => {
+                    self.pos += 1;
+                    words.push(word);
+                }
+                _ if (self.is_type)(&word) && named.is_none() && words.is_empty() => {
+                    self.pos += 1;
+                    named = Some(word);
+                }
+                _ => return Err(self.syntax_error()),
+            }
+        }
+        let base = match named {
+            Some(name) => name,
+            None if words.is_empty() => return Err(self.syntax_error()),
+            None => canonical_base_type(&words),
+        };
+        let mut pointers = 0;
+        loop {
+            if self.accept("*") {
+                pointers += 1;
+            } else if !(self.accept_word("const") || self.accept_word("volatile")) {
+                break;
+            }
+        }
+        Ok(TypeName { base, pointers })
+    }
+
+    fn accept_word(&mut self, word: &str) -> bool {
+        match self.peek() {
+            Some(Token::Identifier(next)) if next == word => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The result of evaluating an expression.
+#[derive(Debug, Clone)]
+pub struct Value {
+    pub value_type: Type,
+    pub location: ValueLocation,
+}
+
+/// Returns whether a value is what a function returning void returned.
+pub fn is_void(value: &Value) -> bool {
+    value.value_type.name == "void" && value.value_type.kind == TypeKind::Unknown
+}
+
+/// An argument of a function call, as it is passed in a register or on the stack.
+#[derive(Debug, Clone, Copy)]
+pub enum Argument {
+    /// An integer or pointer, extended to 64 bits.
+    Integer(u64),
+    /// The bits of a float or double, which go in the low end of an %xmm register.
+    Float(u64),
+}
+
+/// A call of a function in the program, worked out from the function's debugging information.
+pub struct FunctionCall {
+    /// The function's name, or its address if it has no debugging information.
+    pub name: String,
+    pub addr: usize,
+    pub args: Vec<Argument>,
+    /// The type the function returns, or None for void functions.
+    pub return_type: Option<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ValueLocation {
+    /// In the program's memory at this address.
+    Memory(usize),
+    /// In a register of the frame the expression was evaluated in.
+    Register(String),
+    /// Computed by the debugger rather than stored anywhere in the program.
+    Computed(Vec<u8>),
+}
+
+/// A scalar value, as the evaluator does arithmetic on it.
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Signed(i64),
+    Unsigned(u64),
+    Float(f64),
+}
+
+impl Number {
+    fn as_i64(self) -> i64 {
+        match self {
+            Number::Signed(value) => value,
+            Number::Unsigned(value) => value as i64,
+            Number::Float(value) => value as i64,
+        }
+    }
+
+    fn as_u64(self) -> u64 {
+        match self {
+            Number::Unsigned(value) => value,
+            _ => self.as_i64() as u64,
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::Signed(value) => value as f64,
+            Number::Unsigned(value) => value as f64,
+            Number::Float(value) => value,
+        }
+    }
+}
+
+/// Returns whether $name refers to a convenience variable rather than a register or the value
+/// history.
+pub fn is_convenience_variable(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('$')
+        && !name.chars().all(|c| c.is_ascii_digit())
+        && !registers::is_register(name)
+}
+
+/// Evaluates expressions in a stack frame of the examined program.
+pub struct Evaluator<'a> {
+    pub target: &'a dyn Target,
+    pub debug_data: &'a mut DwarfData,
+    /// An address in the frame's function, for finding its variables.
+    pub pc: usize,
+    /// The address the frame's variables are found relative to.
+    pub frame_base: usize,
+    /// The frame's registers, or None if there is no program.
+    pub regs: Option<libc::user_regs_struct>,
+    /// Values print has shown, for $, $$ and $N.
+    pub history: &'a [Value],
+    /// Variables set with "set $name = ...".
+    pub convenience: &'a HashMap<String, Value>,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, String> {
+        match expr {
+            Expr::Integer(value) => {
+                let type_name = if *value <= i32::MAX as u64 {
+                    "int"
+                } else if *value <= i64::MAX as u64 {
+                    "long int"
+                } else {
+                    "long unsigned int"
+                };
+                self.computed(type_name, Number::Unsigned(*value))
+            }
+            Expr::Float(value) => self.computed("double", Number::Float(*value)),
+            Expr::Char(value) => self.computed("char", Number::Signed(*value as i64)),
+            Expr::Name(name) => self.lookup(name),
+            Expr::Dollar(name) => self.dollar(name),
+            Expr::Unary(op, operand) => {
+                let operand = self.evaluate(operand)?;
+                self.unary(op, &operand)
+            }
+            Expr::Binary("&&", lhs, rhs) | Expr::Binary("||", lhs, rhs) => {
+                let lhs = self.evaluate(lhs)?;
+                let lhs_true = self.is_true(&lhs)?;
+                // only evaluate rhs if it decides the result
+                let result = match expr {
+                    Expr::Binary("&&", _, _) if !lhs_true => false,
+                    Expr::Binary("||", _, _) if lhs_true => true,
+                    _ => {
+                        let rhs = self.evaluate(rhs)?;
+                        self.is_true(&rhs)?
+                    }
+                };
+                self.computed("int", Number::Signed(result as i64))
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.evaluate(lhs)?;
+                let rhs = self.evaluate(rhs)?;
+                self.binary(op, &lhs, &rhs)
+            }
+            Expr::Conditional(condition, if_true, if_false) => {
+                let condition = self.evaluate(condition)?;
+                if self.is_true(&condition)? {
+                    self.evaluate(if_true)
+                } else {
+                    self.evaluate(if_false)
+                }
+            }
+            Expr::Assign(_, _) => {
+                Err("Assignments can only be made at the outermost level.".to_string())
+            }
+            Expr::Member(base, field) => {
+                let base = self.evaluate(base)?;
+                self.member(&base, field)
+            }
+            Expr::Index(base, index) => {
+                let base = self.evaluate(base)?;
+                let index = self.evaluate(index)?;
+                self.index(&base, &index)
+            }
+            Expr::Cast(type_name, operand) => {
+                let to = self.resolve_type(type_name)?;
+                let operand = self.evaluate(operand)?;
+                self.cast(&operand, &to)
+            }
+            Expr::SizeofType(type_name) => {
+                let size = self.resolve_type(type_name)?.size;
+                self.computed("long unsigned int", Number::Unsigned(size as u64))
+            }
+            Expr::SizeofExpr(operand) => {
+                // as in C, the calls in the operand aren't made, only their types matter
+                let mut operand = operand.clone();
+                self.skip_calls(&mut operand)?;
+                let size = self.evaluate(&operand)?.value_type.size;
+                self.computed("long unsigned int", Number::Unsigned(size as u64))
+            }
+            // the debugger makes the calls that get evaluated before evaluating the rest, as
+            // they run the program
+            Expr::Call(_, _) => Err("You can't do that without a process to debug.".to_string()),
+            Expr::Value(value) => Ok(value.clone()),
+        }
+    }
+
+    /// Replaces the calls in an expression with zero values of the types they return, for sizeof.
+    fn skip_calls(&mut self, expr: &mut Expr) -> Result<(), String> {
+        for operand in expr.operands_mut() {
+            self.skip_calls(operand)?;
+        }
+        if let Expr::Call(func, args) = expr {
+            let func = self.evaluate(func)?;
+            let args = args
+                .iter()
+                .map(|arg| self.evaluate(arg))
+                .collect::<Result<Vec<Value>, String>>()?;
+            let call = self.prepare_call(&func, &args)?;
+            *expr = Expr::Value(self.returned(&call, 0, 0));
+        }
+        Ok(())
+    }
+
+    /// Returns the bytes of a value.
+    pub fn bytes(&self, value: &Value) -> Result<Vec<u8>, String> {
+        match &value.location {
+            ValueLocation::Memory(addr) => self
+                .target
+                .read_bytes(*addr, value.value_type.size)
+                .map_err(|_| format!("Cannot access memory at address {:#x}", addr)),
+            ValueLocation::Register(name) => {
+                let regs = self.regs.as_ref().ok_or("No registers.")?;
+                let register = registers::get(regs, name).ok_or(format!("Invalid register `{}'", name))?;
+                Ok(register.to_le_bytes()[..value.value_type.size.min(8)].to_vec())
+            }
+            ValueLocation::Computed(bytes) => Ok(bytes.clone()),
+        }
+    }
+
+    /// Formats a value the way print shows it.
+    pub fn format(&self, value: &Value) -> Result<String, String> {
+        if is_void(value) {
+            return Ok("void".to_string());
+        }
+        let value_type = self.debug_data.strip_typedefs(&value.value_type);
+        if let (TypeKind::Function(_), ValueLocation::Memory(addr)) = (&value_type.kind, &value.location) {
+            let name = self.debug_data.get_function(*addr).map_or(String::new(), |func| {
+                format!(" <{}>", func.name)
+            });
+            return Ok(format!("{{{}}} {:#x}{}", value.value_type.name, addr, name));
+        }
+        let bytes = self.bytes(value)?;
+        Ok(value::format_value(self.target, self.debug_data, &value.value_type, &bytes))
+    }
+
+    /// Returns a copy of a value that doesn't change as the program runs, for the value history.
+    pub fn record(&self, value: &Value) -> Result<Value, String> {
+        if let TypeKind::Function(_) = self.debug_data.strip_typedefs(&value.value_type).kind {
+            return Ok(value.clone());
+        }
+        Ok(Value {
+            value_type: value.value_type.clone(),
+            location: ValueLocation::Computed(self.bytes(value)?),
+        })
+    }
+
+    /// Returns whether a value is nonzero, as a condition.
+    pub fn is_true(&mut self, value: &Value) -> Result<bool, String> {
+        Ok(match self.number(value)? {
+            Number::Float(value) => value != 0.0,
+            number => number.as_i64() != 0,
+        })
+    }
+
+    /// Returns the address a value refers to: the value of a pointer or integer, or where an
+    /// array, struct or function is.
+    pub fn as_address(&mut self, value: &Value) -> Result<usize, String> {
+        match self.strip(&value.value_type).kind {
+            TypeKind::Array(_, _)
+            | TypeKind::Struct(_)
+            | TypeKind::Union(_)
+            | TypeKind::Variants(_, _)
+            | TypeKind::Function(_) => lvalue_address(value),
+            _ => Ok(self.number(value)?.as_u64() as usize),
+        }
+    }
+
+    /// Converts a value to the given type the way C assignment does, returning the bytes of the
+    /// result.
+    pub fn convert(&mut self, value: &Value, to: &Type) -> Result<Vec<u8>, String> {
+        if self.is_scalar(to) {
+            let number = self.number(value)?;
+            return self.encode(number, to);
+        }
+        let (from, to) = (self.strip(&value.value_type), self.strip(to));
+        if from.name == to.name && from.size == to.size {
+            self.bytes(value)
+        } else {
+            Err("Invalid cast.".to_string())
+        }
+    }
+
+    /// Works out how to call the function func refers to with the given arguments. The
+    /// arguments are converted to the types of the function's parameters, or given C's default
+    /// argument promotions if the function has no debugging information.
+    pub fn prepare_call(&mut self, func: &Value, args: &[Value]) -> Result<FunctionCall, String> {
+        let func_type = match self.strip(&func.value_type).kind {
+            TypeKind::Pointer(Some(target)) => self.debug_data.get_type(target).cloned(),
+            _ => Some(func.value_type.clone()),
+        };
+        let return_offset = match func_type.map(|t| self.strip(&t).kind) {
+            Some(TypeKind::Function(return_offset)) => return_offset,
+            _ => return Err("Cannot perform a function call on a non-function value.".to_string()),
+        };
+        let addr = self.as_address(func)?;
+        let function = self.debug_data.get_function(addr).filter(|f| f.address == addr);
+        let (name, params) = match function {
+            Some(function) => {
+                let params: Vec<Type> = function
+                    .variables
+                    .iter()
+                    .filter(|var| var.is_parameter)
+                    .map(|var| var.entity_type.clone())
+                    .collect();
+                (function.name.clone(), Some(params))
+            }
+            None => (format!("{:#x}", addr), None),
+        };
+        if let Some(params) = &params {
+            if args.len() < params.len() {
+                return Err("Too few arguments in function call.".to_string());
+            } else if args.len() > params.len() {
+                return Err("Too many arguments in function call.".to_string());
+            }
+        }
+        let mut converted = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            let param = params.as_ref().map(|params| params[i].clone());
+            converted.push(self.argument(arg, param)?);
+        }
+        let return_type = match return_offset {
+            Some(offset) => {
+                let return_type = self
+                    .debug_data
+                    .get_type(offset)
+                    .ok_or(format!("'{}' has unknown return type.", name))?
+                    .clone();
+                if !self.is_scalar(&return_type) {
+                    return Err(format!(
+                        "Calling functions that return {} is not supported.",
+                        return_type.name
+                    ));
+                }
+                Some(return_type)
+            }
+            None => None,
+        };
+        Ok(FunctionCall {
+            name,
+            addr,
+            args: converted,
+            return_type,
+        })
+    }
+
+    /// Returns what a function call returned, which is in %rax, or %xmm0 for floating point
+    /// types.
+    pub fn returned(&mut self, call: &FunctionCall, rax: u64, xmm0: u64) -> Value {
+        let value_type = match &call.return_type {
+            Some(return_type) => return_type.clone(),
+            None => {
+                let void = Type::new("void".to_string(), 0, TypeKind::Unknown);
+                let void = self.debug_data.intern_type(void);
+                return Value {
+                    value_type: void,
+                    location: ValueLocation::Computed(Vec::new()),
+                };
+            }
+        };
+        let register = match self.strip(&value_type).kind {
+            TypeKind::Base(BaseEncoding::Float) => xmm0,
+            _ => rax,
+        };
+        let size = value_type.size.min(8);
+        Value {
+            value_type,
+            location: ValueLocation::Computed(register.to_le_bytes()[..size].to_vec()),
+        }
+    }
+
+    /// Converts a value to be passed for a parameter of the given type, or with C's default
+    /// argument promotions if the type isn't known.
+    fn argument(&mut self, value: &Value, param_type: Option<Type>) -> Result<Argument, String> {
+        let value = self.decay(value)?;
+        let param_type = match param_type {
+            Some(param_type) => param_type,
+            None => {
+                let value_type = self.strip(&value.value_type);
+                match value_type.kind {
+                    TypeKind::Base(BaseEncoding::Float) => self.base_type("double")?,
+                    TypeKind::Base(_) | TypeKind::Enum(_) if value_type.size < 4 => {
+                        self.base_type("int")?
+                    }
+                    _ => value.value_type.clone(),
+                }
+            }
+        };
+        if !self.is_scalar(&param_type) {
+            return Err(format!(
+                "Passing arguments of type {} is not supported.",
+                param_type.name
+            ));
+        }
+        let bytes = self.convert(&value, &param_type)?;
+        match self.decode(&bytes, &param_type)? {
+            Number::Float(_) => {
+                let mut word = [0; 8];
+                word[..bytes.len()].copy_from_slice(&bytes);
+                Ok(Argument::Float(u64::from_le_bytes(word)))
+            }
+            number => Ok(Argument::Integer(number.as_u64())),
+        }
+    }
+
+    fn strip(&self, value_type: &Type) -> Type {
+        self.debug_data.strip_typedefs(value_type).clone()
+    }
+
+    fn is_scalar(&self, value_type: &Type) -> bool {
+        matches!(
+            self.debug_data.strip_typedefs(value_type).kind,
+            TypeKind::Base(_) | TypeKind::Pointer(_) | TypeKind::Enum(_)
+        )
+    }
+
+    /// Returns the base type that gcc calls name, adding it if the program doesn't use it.
+    fn base_type(&mut self, name: &str) -> Result<Type, String> {
+        if let Some(base) = self
+            .debug_data
+            .get_type_by_name(name)
+            .filter(|t| matches!(t.kind, TypeKind::Base(_)))
+        {
+            return Ok(base.clone());
+        }
+        let (size, encoding) = match name {
+            "char" | "signed char" => (1, BaseEncoding::SignedChar),
+            "unsigned char" => (1, BaseEncoding::UnsignedChar),
+            "short int" => (2, BaseEncoding::Signed),
+            "short unsigned int" => (2, BaseEncoding::Unsigned),
+            "int" => (4, BaseEncoding::Signed),
+            "unsigned int" => (4, BaseEncoding::Unsigned),
+            "long int" | "long long int" => (8, BaseEncoding::Signed),
+            "long unsigned int" | "long long unsigned int" => (8, BaseEncoding::Unsigned),
+            "float" => (4, BaseEncoding::Float),
+            "double" => (8, BaseEncoding::Float),
+            "long double" => (16, BaseEncoding::Float),
+            "_Bool" => (1, BaseEncoding::Boolean),
+            _ => return Err(format!("No symbol \"{}\" in current context.", name)),
+        };
+        Ok(self
+            .debug_data
+            .intern_type(Type::new(name.to_string(), size, TypeKind::Base(encoding))))
+    }
+
+    fn void_pointer(&mut self) -> Type {
+        self.debug_data
+            .intern_type(Type::new("void *".to_string(), 8, TypeKind::Pointer(None)))
+    }
+
+    /// Returns the type of functions returning the type at return_type.
+    fn function_type(&mut self, return_type: Option<usize>) -> Type {
+        let name = return_type
+            .and_then(|offset| self.debug_data.get_type(offset))
+            .map_or("void".to_string(), |t| t.name.clone());
+        self.debug_data.intern_type(Type::new(
+            format!("{} ()", name),
+            1,
+            TypeKind::Function(return_type),
+        ))
+    }
+
+    fn resolve_type(&mut self, type_name: &TypeName) -> Result<Type, String> {
+        let (mut resolved, pointers) = if type_name.base == "void" {
+            if type_name.pointers == 0 {
+                return Err("Attempt to use a type name as an expression".to_string());
+            }
+            (self.void_pointer(), type_name.pointers - 1)
+        } else {
+            let base = match self.base_type(&type_name.base) {
+                Ok(base) => base,
+                Err(err) => self.debug_data.get_type_by_name(&type_name.base).ok_or(err)?.clone(),
+            };
+            (base, type_name.pointers)
+        };
+        for _ in 0..pointers {
+            resolved = self.debug_data.pointer_to(&resolved);
+        }
+        Ok(resolved)
+    }
+
+    /// Makes a value of the base type that gcc calls type_name.
+    fn computed(&mut self, type_name: &str, number: Number) -> Result<Value, String> {
+        let value_type = self.base_type(type_name)?;
+        let bytes = self.encode(number, &value_type)?;
+        Ok(Value {
+            value_type,
+            location: ValueLocation::Computed(bytes),
+        })
+    }
+
+    /// Looks up a variable, function or enumeration constant.
+    fn lookup(&mut self, name: &str) -> Result<Value, String> {
+        if let Some(var) = self.debug_data.get_variable(self.pc, name) {
+            return Ok(Value {
+                value_type: var.entity_type.clone(),
+                location: ValueLocation::Memory(value::variable_address(var, self.frame_base)),
+            });
+        }
+        match self.debug_data.get_addr_for_function(None, name) {
+            Ok(addr) => {
+                let return_type = self.debug_data.get_function(addr).and_then(|f| f.return_type);
+                return Ok(Value {
+                    value_type: self.function_type(return_type),
+                    location: ValueLocation::Memory(addr),
+                });
+            }
+            Err(LocationError::NoSuchFunction(_)) => {}
+            Err(err) => return Err(err.to_string()),
+        }
+        if let Some((enum_type, number)) = self.debug_data.get_enumerator(name) {
+            let enum_type = enum_type.clone();
+            let bytes = self.encode(Number::Signed(number), &enum_type)?;
+            return Ok(Value {
+                value_type: enum_type,
+                location: ValueLocation::Computed(bytes),
+            });
+        }
+        Err(format!("No symbol \"{}\" in current context.", name))
+    }
+
+    /// Looks up a $register, a value history entry ($, $$, $N or $$N) or a convenience variable.
+    fn dollar(&mut self, name: &str) -> Result<Value, String> {
+        if registers::is_register(name) {
+            if self.regs.is_none() {
+                return Err("No registers.".to_string());
+            }
+            let value_type = match name {
+                "rip" | "pc" => {
+                    let function_type = self.function_type(None);
+                    self.debug_data.pointer_to(&function_type)
+                }
+                "rsp" | "sp" | "rbp" | "fp" => self.void_pointer(),
+                _ => self.base_type("long int")?,
+            };
+            return Ok(Value {
+                value_type,
+                location: ValueLocation::Register(name.to_string()),
+            });
+        }
+        if is_convenience_variable(name) {
+            return self
+                .convenience
+                .get(name)
+                .cloned()
+                .ok_or(format!("Convenience variable ${} has not been set.", name));
+        }
+        let (relative, number) = match name.strip_prefix('$') {
+            Some(number) => (true, number),
+            None => (false, name),
+        };
+        let index = match number.parse::<usize>() {
+            Ok(number) if relative => self.history.len().checked_sub(number + 1),
+            Ok(number) => number.checked_sub(1),
+            // $ is the last value and $$ the one before it
+            Err(_) => self.history.len().checked_sub(if relative { 2 } else { 1 }),
+        };
+        match index.and_then(|index| self.history.get(index)) {
+            Some(value) => Ok(value.clone()),
+            None if self.history.is_empty() => Err("History is empty.".to_string()),
+            None => Err(format!("History has not yet reached ${}.", name)),
+        }
+    }
+
+    /// Reads a scalar value, with arrays and functions standing for their addresses.
+    fn number(&mut self, value: &Value) -> Result<Number, String> {
+        let value = self.decay(value)?;
+        let bytes = self.bytes(&value)?;
+        self.decode(&bytes, &value.value_type)
+    }
+
+    fn decode(&self, bytes: &[u8], value_type: &Type) -> Result<Number, String> {
+        let value_type = self.debug_data.strip_typedefs(value_type);
+        match value_type.kind {
+            TypeKind::Base(BaseEncoding::Float) => match bytes.len() {
+                4 => Ok(Number::Float(f32::from_le_bytes(bytes.try_into().unwrap()) as f64)),
+                8 => Ok(Number::Float(f64::from_le_bytes(bytes.try_into().unwrap()))),
+                _ => Err(format!("Cannot do arithmetic on {}.", value_type.name)),
+            },
+            TypeKind::Base(BaseEncoding::Signed)
+            | TypeKind::Base(BaseEncoding::SignedChar)
+            | TypeKind::Enum(_) => Ok(Number::Signed(value::read_int(bytes))),
+            TypeKind::Base(_) | TypeKind::Pointer(_) => Ok(Number::Unsigned(value::read_uint(bytes))),
+            _ => Err("Argument to arithmetic operation not a number or boolean.".to_string()),
+        }
+    }
+
+    fn encode(&self, number: Number, value_type: &Type) -> Result<Vec<u8>, String> {
+        let value_type = self.debug_data.strip_typedefs(value_type);
+        let size = value_type.size;
+        match value_type.kind {
+            TypeKind::Base(BaseEncoding::Float) => match size {
+                4 => Ok((number.as_f64() as f32).to_le_bytes().to_vec()),
+                8 => Ok(number.as_f64().to_le_bytes().to_vec()),
+                _ => Err(format!("Cannot do arithmetic on {}.", value_type.name)),
+            },
+            TypeKind::Base(BaseEncoding::Boolean) => {
+                let mut bytes = vec![0; size];
+                bytes[0] = (number.as_f64() != 0.0) as u8;
+                Ok(bytes)
+            }
+            TypeKind::Base(_) | TypeKind::Pointer(_) | TypeKind::Enum(_) if size <= 8 => {
+                Ok(number.as_i64().to_le_bytes()[..size].to_vec())
+            }
+            _ => Err("Invalid cast.".to_string()),
+        }
+    }
+
+    /// Converts a number to the given type and back, e.g. truncating it to an int.
+    fn convert_number(&self, number: Number, value_type: &Type) -> Result<Number, String> {
+        let bytes = self.encode(number, value_type)?;
+        self.decode(&bytes, value_type)
+    }
+
+    /// Works out the type of the result of arithmetic on values of types a and b, following C's
+    /// usual arithmetic conversions.
+    fn arithmetic_type(&mut self, a: &Type, b: &Type) -> Result<Type, String> {
+        let (a, b) = (self.strip(a), self.strip(b));
+        let is_float = |t: &Type| matches!(t.kind, TypeKind::Base(BaseEncoding::Float));
+        if is_float(&a) || is_float(&b) {
+            return self.base_type("double");
+        }
+        let is_unsigned = |t: &Type| {
+            matches!(
+                t.kind,
+                TypeKind::Base(BaseEncoding::Unsigned)
+                    | TypeKind::Base(BaseEncoding::UnsignedChar)
+                    | TypeKind::Pointer(_)
+            )
+        };
+        let size = a.size.max(b.size).max(4);
+        let unsigned = (is_unsigned(&a) && a.size >= size) || (is_unsigned(&b) && b.size >= size);
+        self.base_type(match (size > 4, unsigned) {
+            (true, true) => "long unsigned int",
+            (true, false) => "long int",
+            (false, true) => "unsigned int",
+            (false, false) => "int",
+        })
+    }
+
+    /// Turns arrays and functions into pointers to their first element or code.
+    fn decay(&mut self, value: &Value) -> Result<Value, String> {
+        let pointer_type = match self.strip(&value.value_type).kind {
+            TypeKind::Array(_, _) => {
+                let element_type = self.element_type(&value.value_type)?;
+                self.debug_data.pointer_to(&element_type)
+            }
+            TypeKind::Function(_) => self.debug_data.pointer_to(&value.value_type),
+            _ => return Ok(value.clone()),
+        };
+        let addr = lvalue_address(value)?;
+        Ok(Value {
+            value_type: pointer_type,
+            location: ValueLocation::Computed(addr.to_le_bytes().to_vec()),
+        })
+    }
+
+    /// Returns the type of the elements of an array, which for a multidimensional array is an
+    /// array of the remaining dimensions.
+    fn element_type(&mut self, array_type: &Type) -> Result<Type, String> {
+        let (element, dimensions) = match self.strip(array_type).kind {
+            TypeKind::Array(element, dimensions) => (element, dimensions),
+            _ => return Err(format!("cannot subscript something of type `{}'", array_type.name)),
+        };
+        let element_type = self
+            .debug_data
+            .get_type(element)
+            .ok_or("Unknown array element type.")?
+            .clone();
+        if dimensions.len() <= 1 {
+            return Ok(element_type);
+        }
+        let stride = element_type.size * dimensions[1..].iter().product::<usize>();
+        let names: Vec<String> = dimensions[1..].iter().map(|d| format!("[{}]", d)).collect();
+        Ok(self.debug_data.intern_type(Type::new(
+            format!("{} {}", element_type.name, names.join("")),
+            stride,
+            TypeKind::Array(element, dimensions[1..].to_vec()),
+        )))
+    }
+
+    fn pointee_size(&self, pointer_type: &Type) -> usize {
+        match self.debug_data.strip_typedefs(pointer_type).kind {
+            TypeKind::Pointer(Some(target)) => self
+                .debug_data
+                .get_type(target)
+                .map_or(1, |target_type| target_type.size.max(1)),
+            // like gcc, treat void pointers as pointing to bytes
+            _ => 1,
+        }
+    }
+
+    fn unary(&mut self, op: &str, operand: &Value) -> Result<Value, String> {
+        match op {
+            "*" => self.dereference(operand),
+            "&" => self.address_of(operand),
+            "!" => {
+                let result = !self.is_true(operand)?;
+                self.computed("int", Number::Signed(result as i64))
+            }
+            _ => {
+                let operand = self.decay(operand)?;
+                let result_type = self.arithmetic_type(&operand.value_type, &operand.value_type)?;
+                let number = self.number(&operand)?;
+                let result = match (op, self.convert_number(number, &result_type)?) {
+                    ("-", Number::Float(value)) => Number::Float(-value),
+                    ("-", number) => Number::Signed(number.as_i64().wrapping_neg()),
+                    ("~", Number::Float(_)) => {
+                        return Err("Argument to complement operation not an integer.".to_string())
+                    }
+                    ("~", number) => Number::Signed(!number.as_i64()),
+                    (_, number) => number,
+                };
+                Ok(Value {
+                    location: ValueLocation::Computed(self.encode(result, &result_type)?),
+                    value_type: result_type,
+                })
+            }
+        }
+    }
+
+    fn dereference(&mut self, value: &Value) -> Result<Value, String> {
+        match self.strip(&value.value_type).kind {
+            TypeKind::Pointer(Some(target)) => {
+                let addr = self.number(value)?.as_u64() as usize;
+                let target_type = self
+                    .debug_data
+                    .get_type(target)
+                    .ok_or("Attempt to take contents of a pointer to an unknown type.")?
+                    .clone();
+                Ok(Value {
+                    value_type: target_type,
+                    location: ValueLocation::Memory(addr),
+                })
+            }
+            TypeKind::Array(_, _) => {
+                let pointer = self.decay(value)?;
+                self.dereference(&pointer)
+            }
+            TypeKind::Function(_) => Ok(value.clone()),
+            // like gdb, treat an integer as the address of an int
+            TypeKind::Base(BaseEncoding::Signed) | TypeKind::Base(BaseEncoding::Unsigned) => {
+                let addr = self.number(value)?.as_u64() as usize;
+                Ok(Value {
+                    value_type: self.base_type("int")?,
+                    location: ValueLocation::Memory(addr),
+                })
+            }
+            _ => Err("Attempt to take contents of a non-pointer value.".to_string()),
+        }
+    }
+
+    fn address_of(&mut self, value: &Value) -> Result<Value, String> {
+        let addr = lvalue_address(value)?;
+        Ok(Value {
+            value_type: self.debug_data.pointer_to(&value.value_type),
+            location: ValueLocation::Computed(addr.to_le_bytes().to_vec()),
+        })
+    }
+
+    fn binary(&mut self, op: &str, lhs: &Value, rhs: &Value) -> Result<Value, String> {
+        let lhs = self.decay(lhs)?;
+        let rhs = self.decay(rhs)?;
+        let is_pointer = |t: &Type| matches!(t.kind, TypeKind::Pointer(_));
+        let lhs_pointer = is_pointer(&self.strip(&lhs.value_type));
+        let rhs_pointer = is_pointer(&self.strip(&rhs.value_type));
+        match op {
+            "+" | "-" if lhs_pointer && !rhs_pointer => {
+                let offset = self.number(&rhs)?.as_i64();
+                self.pointer_offset(&lhs, if op == "-" { offset.wrapping_neg() } else { offset })
+            }
+            "+" if rhs_pointer && !lhs_pointer => {
+                let offset = self.number(&lhs)?.as_i64();
+                self.pointer_offset(&rhs, offset)
+            }
+            "-" if lhs_pointer && rhs_pointer => {
+                let size = self.pointee_size(&lhs.value_type);
+                if size != self.pointee_size(&rhs.value_type) {
+                    return Err("First argument of `-' is a pointer and second argument is neither\nan integer nor a pointer of the same type.".to_string());
+                }
+                let difference =
+                    self.number(&lhs)?.as_i64().wrapping_sub(self.number(&rhs)?.as_i64());
+                self.computed("long int", Number::Signed(difference / size as i64))
+            }
+            "==" | "!=" | "<" | "<=" | ">" | ">=" => {
+                let compare_type = self.arithmetic_type(&lhs.value_type, &rhs.value_type)?;
+                let a = self.number(&lhs)?;
+                let b = self.number(&rhs)?;
+                let a = self.convert_number(a, &compare_type)?;
+                let b = self.convert_number(b, &compare_type)?;
+                let ordering = match a {
+                    Number::Float(a) => a.partial_cmp(&b.as_f64()),
+                    Number::Unsigned(a) => Some(a.cmp(&b.as_u64())),
+                    Number::Signed(a) => Some(a.cmp(&b.as_i64())),
+                };
+                let result = match ordering {
+                    // comparisons with NaN are false
+                    None => op == "!=",
+                    Some(ordering) => match op {
+                        "==" => ordering == Ordering::Equal,
+                        "!=" => ordering != Ordering::Equal,
+                        "<" => ordering == Ordering::Less,
+                        "<=" => ordering != Ordering::Greater,
+                        ">" => ordering == Ordering::Greater,
+                        _ => ordering != Ordering::Less,
+                    },
+                };
+                self.computed("int", Number::Signed(result as i64))
+            }
+            _ => self.arithmetic(op, &lhs, &rhs),
+        }
+    }
+
+    /// Carries out +, -, *, /, %, <<, >>, &, | or ^ on numbers.
+    fn arithmetic(&mut self, op: &str, lhs: &Value, rhs: &Value) -> Result<Value, String> {
+        let shift = op == "<<" || op == ">>";
+        // the result of a shift has the type of its left operand
+        let result_type = match shift {
+            true => self.arithmetic_type(&lhs.value_type, &lhs.value_type)?,
+            false => self.arithmetic_type(&lhs.value_type, &rhs.value_type)?,
+        };
+        let a = self.number(lhs)?;
+        let b = self.number(rhs)?;
+        let a = self.convert_number(a, &result_type)?;
+        let b = if shift { b } else { self.convert_number(b, &result_type)? };
+        let result = match a {
+            Number::Float(a) => {
+                let b = b.as_f64();
+                Number::Float(match op {
+                    "+" => a + b,
+                    "-" => a - b,
+                    "*" => a * b,
+                    "/" => a / b,
+                    _ => return Err(format!("Integer only operation {}.", op)),
+                })
+            }
+            _ => {
+                let unsigned = matches!(a, Number::Unsigned(_));
+                let (a, b) = (a.as_i64(), b.as_i64());
+                Number::Signed(match op {
+                    "+" => a.wrapping_add(b),
+                    "-" => a.wrapping_sub(b),
+                    "*" => a.wrapping_mul(b),
+                    "&" => a & b,
+                    "|" => a | b,
+                    "^" => a ^ b,
+                    "<<" => a.wrapping_shl(b as u32),
+                    "/" | "%" if b == 0 => return Err("Division by zero".to_string()),
+                    "/" if unsigned => ((a as u64) / (b as u64)) as i64,
+                    "%" if unsigned => ((a as u64) % (b as u64)) as i64,
+                    ">>" if unsigned => (a as u64).wrapping_shr(b as u32) as i64,
+                    "/" => a.wrapping_div(b),
+                    "%" => a.wrapping_rem(b),
+                    _ => a.wrapping_shr(b as u32),
+                })
+            }
+        };
+        Ok(Value {
+            location: ValueLocation::Computed(self.encode(result, &result_type)?),
+            value_type: result_type,
+        })
+    }
+
+    /// Adds offset elements to a pointer.
+    fn pointer_offset(&mut self, pointer: &Value, offset: i64) -> Result<Value, String> {
+        let size = self.pointee_size(&pointer.value_type) as i64;
+        let addr = self.number(pointer)?.as_i64().wrapping_add(offset.wrapping_mul(size));
+        Ok(Value {
+            value_type: pointer.value_type.clone(),
+            location: ValueLocation::Computed(addr.to_le_bytes().to_vec()),
+        })
+    }
+
+    fn member(&mut self, base: &Value, field: &str) -> Result<Value, String> {
+        let members = match self.strip(&base.value_type).kind {
+            TypeKind::Struct(members) | TypeKind::Union(members) => members,
+            // like gdb, let . follow pointers
+            TypeKind::Pointer(_) => {
+                let target = self.dereference(base)?;
+                return self.member(&target, field);
+            }
+            _ => {
+                return Err(
+                    "Attempt to extract a component of a value that is not a structure."
+                        .to_string(),
+                )
+            }
+        };
+        let member = members
+            .iter()
+            .find(|member| member.name == field)
+            .ok_or(format!("There is no member named {}.", field))?;
+        let member_type = self
+            .debug_data
+            .get_type(member.type_offset)
+            .ok_or(format!("Unknown type for member {}", field))?
+            .clone();
+        let location = match &base.location {
+            ValueLocation::Memory(addr) => ValueLocation::Memory(addr + member.offset),
+            ValueLocation::Computed(bytes) => ValueLocation::Computed(
+                bytes
+                    .get(member.offset..member.offset + member_type.size)
+                    .ok_or(format!("Unknown type for member {}", field))?
+                    .to_vec(),
+            ),
+            ValueLocation::Register(_) => return Err("Invalid register value.".to_string()),
+        };
+        Ok(Value {
+            value_type: member_type,
+            location,
+        })
+    }
+
+    fn index(&mut self, base: &Value, index: &Value) -> Result<Value, String> {
+        let index_number = match self.number(index)? {
+            Number::Float(_) => return Err("Array subscript is not an integer.".to_string()),
+            number => number.as_i64(),
+        };
+        match self.strip(&base.value_type).kind {
+            TypeKind::Array(_, _) => {
+                let element_type = self.element_type(&base.value_type)?;
+                let size = element_type.size;
+                let location = match &base.location {
+                    ValueLocation::Memory(addr) => ValueLocation::Memory(
+                        (*addr as i64).wrapping_add(index_number.wrapping_mul(size as i64)) as usize,
+                    ),
+                    ValueLocation::Computed(bytes) => {
+                        let start = index_number as usize * size;
+                        ValueLocation::Computed(
+                            bytes
+                                .get(start..start + size)
+                                .filter(|_| index_number >= 0)
+                                .ok_or("no such vector element")?
+                                .to_vec(),
+                        )
+                    }
+                    ValueLocation::Register(_) => return Err("Invalid register value.".to_string()),
+                };
+                Ok(Value {
+                    value_type: element_type,
+                    location,
+                })
+            }
+            TypeKind::Pointer(_) => {
+                let element = self.pointer_offset(base, index_number)?;
+                self.dereference(&element)
+            }
+            _ => Err(format!("cannot subscript something of type `{}'", base.value_type.name)),
+        }
+    }
+
+    fn cast(&mut self, value: &Value, to: &Type) -> Result<Value, String> {
+        if self.is_scalar(to) {
+            return Ok(Value {
+                location: ValueLocation::Computed(self.convert(value, to)?),
+                value_type: to.clone(),
+            });
+        }
+        match &value.location {
+            // reinterpret the memory as the new type
+            ValueLocation::Memory(addr) => Ok(Value {
+                value_type: to.clone(),
+                location: ValueLocation::Memory(*addr),
+            }),
+            _ => Err("Invalid cast.".to_string()),
+        }
+    }
+}
+
+/// Returns where a value is stored in memory.
+fn lvalue_address(value: &Value) -> Result<usize, String> {
+    match value.location {
+        ValueLocation::Memory(addr) => Ok(addr),
+        _ => Err("Attempt to take address of value not located in memory.".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::NoProcess;
+    use std::process::Command;
+
+    /// Writes an expression back out with every operation in parentheses, to show how it was
+    /// grouped.
+    fn show(expr: &Expr) -> String {
+        match expr {
+            Expr::Integer(value) => value.to_string(),
+            Expr::Float(value) => value.to_string(),
+            Expr::Char(value) => format!("'{}'", *value as char),
+            Expr::Name(name) => name.clone(),
+            Expr::Dollar(name) => format!("${}", name),
+            Expr::Unary(op, operand) => format!("({}{})", op, show(operand)),
+            Expr::Binary(op, lhs, rhs) => format!("({} {} {})", show(lhs), op, show(rhs)),
+            Expr::Conditional(condition, if_true, if_false) => {
+                format!("({} ? {} : {})", show(condition), show(if_true), show(if_false))
+            }
+            Expr::Assign(lhs, rhs) => format!("({} = {})", show(lhs), show(rhs)),
+            Expr::Member(base, field) => format!("({}.{})", show(base), field),
+            Expr::Index(base, index) => format!("({}[{}])", show(base), show(index)),
+            Expr::Cast(type_name, operand) => {
+                let pointers = "*".repeat(type_name.pointers);
+                format!("(({}{}) {})", type_name.base, pointers, show(operand))
+            }
+            Expr::SizeofType(type_name) => format!("sizeof({})", type_name.base),
+            Expr::SizeofExpr(operand) => format!("sizeof {}", show(operand)),
+            Expr::Call(func, args) => {
+                let args: Vec<String> = args.iter().map(show).collect();
+                format!("{}({})", show(func), args.join(", "))
+            }
+            Expr::Value(_) => "<value>".to_string(),
+        }
+    }
+
+    fn parsed(text: &str) -> String {
+        match parse(text, &|name| name == "ulong_t") {
+            Ok(expr) => show(&expr),
+            Err(err) => err,
+        }
+    }
+
+    /// Loads the debugging information of samples/structs, building it if need be.
+    fn structs_debug_data() -> DwarfData {
+        let dir = env!("CARGO_MANIFEST_DIR");
+        let status = Command::new("make").args(&["-s", "-C", dir, "samples/structs"]).status();
+        assert!(status.map_or(false, |status| status.success()), "could not build samples/structs");
+        DwarfData::from_file(&format!("{}/samples/structs", dir), "/usr/lib/debug").unwrap()
+    }
+
+    /// Evaluates an expression without a program, the way print does before run.
+    fn evaluated(debug_data: &mut DwarfData, text: &str) -> String {
+        let convenience = HashMap::new();
+        let expr = match parse(text, &|name| debug_data.get_type_by_name(name).is_some()) {
+            Ok(expr) => expr,
+            Err(err) => return err,
+        };
+        let mut evaluator = Evaluator {
+            target: &NoProcess,
+            debug_data,
+            pc: 0,
+            frame_base: 0,
+            regs: None,
+            history: &[],
+            convenience: &convenience,
+        };
+        match evaluator.evaluate(&expr).and_then(|value| evaluator.format(&value)) {
+            Ok(formatted) => formatted,
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn tokenizes() {
+        let tokens: Vec<Token> = tokenize("p->next[0x1f] <= 'a' + 1.5e-3 + $rip")
+            .unwrap()
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Identifier("p".to_string()),
+                Token::Operator("->"),
+                Token::Identifier("next".to_string()),
+                Token::Operator("["),
+                Token::Integer(0x1f),
+                Token::Operator("]"),
+                Token::Operator("<="),
+                Token::Char(b'a'),
+                Token::Operator("+"),
+                Token::Float(1.5e-3),
+                Token::Operator("+"),
+                Token::Dollar("rip".to_string()),
+            ]
+        );
+        // octal, an escaped character and suffixes, with where each token starts
+        assert_eq!(
+            tokenize("010 '\\n' 7ul").unwrap(),
+            vec![(Token::Integer(8), 0), (Token::Char(b'\n'), 4), (Token::Integer(7), 9)]
+        );
+        assert_eq!(tokenize("a @ b"), Err("Invalid character '@' in expression.".to_string()));
+        assert_eq!(tokenize("'ab'"), Err("Unmatched single quote.".to_string()));
+    }
+
+    #[test]
+    fn parses_with_c_precedence() {
+        assert_eq!(parsed("1 + 2 * 3"), "(1 + (2 * 3))");
+        assert_eq!(parsed("1 - 2 - 3"), "((1 - 2) - 3)");
+        assert_eq!(parsed("a || b && c | d ^ e & f"), "(a || (b && (c | (d ^ (e & f)))))");
+        assert_eq!(parsed("a == b < c << 1"), "(a == (b < (c << 1)))");
+        assert_eq!(parsed("-*p + ~x"), "((-(*p)) + (~x))");
+        assert_eq!(parsed("!a->b[2].c"), "(!((((*a).b)[2]).c))");
+        assert_eq!(parsed("a ? b : c ? d : e"), "(a ? b : (c ? d : e))");
+        assert_eq!(parsed("x = y = 3"), "(x = (y = 3))");
+        assert_eq!(parsed("f(1, g(x), y + 1)(2)"), "f(1, g(x), (y + 1))(2)");
+    }
+
+    #[test]
+    fn parses_casts_and_sizeof() {
+        assert_eq!(parsed("(unsigned long)-1"), "((long unsigned int) (-1))");
+        assert_eq!(parsed("(ulong_t)x"), "((ulong_t) x)");
+        // a parenthesized name that isn't a type is just grouping
+        assert_eq!(parsed("(x)-1"), "(x - 1)");
+        assert_eq!(parsed("(struct node *)p"), "((struct node*) p)");
+        assert_eq!(parsed("{int} 0x1000"), "(*((int*) 4096))");
+        assert_eq!(parsed("sizeof(short)"), "sizeof(short int)");
+        assert_eq!(parsed("sizeof x + 1"), "(sizeof x + 1)");
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        assert_eq!(parsed(""), "Argument required (expression to compute).");
+        assert_eq!(parsed("1 +"), "A syntax error in expression, near `'.");
+        assert_eq!(parsed("(1 + 2"), "A syntax error in expression, near `'.");
+        assert_eq!(parsed("f(1 2)"), "A syntax error in expression, near `2)'.");
+    }
+
+    #[test]
+    fn evaluates_arithmetic() {
+        let mut debug_data = structs_debug_data();
+        let mut eval = |text| evaluated(&mut debug_data, text);
+        assert_eq!(eval("1 + 2 * 3"), "7");
+        assert_eq!(eval("7 / 2"), "3");
+        assert_eq!(eval("-7 % 3"), "-1");
+        assert_eq!(eval("1 << 4 | 1"), "17");
+        assert_eq!(eval("10 / 4.0"), "2.5");
+        assert_eq!(eval("3 > 2 ? 10 : 20"), "10");
+        assert_eq!(eval("0 && 1 / 0"), "0");
+        assert_eq!(eval("1 / 0"), "Division by zero");
+    }
+
+    #[test]
+    fn evaluates_casts_and_types() {
+        let mut debug_data = structs_debug_data();
+        let mut eval = |text| evaluated(&mut debug_data, text);
+        assert_eq!(eval("(unsigned char)300"), "44 ','");
+        assert_eq!(eval("(ulong_t)-1"), "18446744073709551615");
+        assert_eq!(eval("(int)3.9"), "3");
+        assert_eq!(eval("sizeof(struct point)"), "8");
+        assert_eq!(eval("sizeof(entity)"), "32");
+        assert_eq!(eval("GREEN + 1"), "6");
+        assert_eq!(eval("(enum color)6"), "BLUE");
+        assert_eq!(eval("nosuch"), "No symbol \"nosuch\" in current context.");
+    }
+
+    #[test]
+    fn evaluates_only_the_calls_c_would_make() {
+        let mut debug_data = structs_debug_data();
+        let mut eval = |text| evaluated(&mut debug_data, text);
+        let no_process = "You can't do that without a process to debug.";
+        assert_eq!(eval("main()"), no_process);
+        assert_eq!(eval("1 && main()"), no_process);
+        assert_eq!(eval("0 && main()"), "0");
+        assert_eq!(eval("1 || main()"), "1");
+        assert_eq!(eval("1 ? 2 : main()"), "2");
+        assert_eq!(eval("0 ? main() : 3"), "3");
+        // sizeof only needs the type main returns
+        assert_eq!(eval("sizeof(main())"), "4");
+        assert_eq!(eval("sizeof(main() + 1.0)"), "8");
+    }
+}
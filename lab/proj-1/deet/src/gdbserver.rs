@@ -0,0 +1,821 @@
+use crate::debugger::Breakpoint;
+use crate::inferior::{Inferior, Status};
+use crate::registers;
+use nix::sys::ptrace;
+use nix::sys::signal::{self, Signal};
+use nix::unistd::Pid;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// The registers of gdb's amd64 target description, in the order the g packet sends them, with
+/// their size in bits and type. The x87 registers are required by gdb but not read from the
+/// inferior, so they are sent as unavailable.
+const REGISTERS: [(&str, usize, &str); 40] = [
+    ("rax", 64, "int64"),
+    ("rbx", 64, "int64"),
+    ("rcx", 64, "int64"),
+    ("rdx", 64, "int64"),
+    ("rsi", 64, "int64"),
+    ("rdi", 64, "int64"),
+    ("rbp", 64, "data_ptr"),
+    ("rsp", 64, "data_ptr"),
+    ("r8", 64, "int64"),
+    ("r9", 64, "int64"),
+    ("r10", 64, "int64"),
+    ("r11", 64, "int64"),
+    ("r12", 64, "int64"),
+    ("r13", 64, "int64"),
+    ("r14", 64, "int64"),
+    ("r15", 64, "int64"),
+    ("rip", 64, "code_ptr"),
+    ("eflags", 32, "int32"),
+    ("cs", 32, "int32"),
+    ("ss", 32, "int32"),
+    ("ds", 32, "int32"),
+    ("es", 32, "int32"),
+    ("fs", 32, "int32"),
+    ("gs", 32, "int32"),
+    ("st0", 80, "i387_ext"),
+    ("st1", 80, "i387_ext"),
+    ("st2", 80, "i387_ext"),
+    ("st3", 80, "i387_ext"),
+    ("st4", 80, "i387_ext"),
+    ("st5", 80, "i387_ext"),
+    ("st6", 80, "i387_ext"),
+    ("st7", 80, "i387_ext"),
+    ("fctrl", 32, "int"),
+    ("fstat", 32, "int"),
+    ("ftag", 32, "int"),
+    ("fiseg", 32, "int"),
+    ("fioff", 32, "int"),
+    ("foseg", 32, "int"),
+    ("fooff", 32, "int"),
+    ("fop", 32, "int"),
+];
+
+/// Linux signals whose number in the remote protocol differs from their own. The rest, SIGHUP
+/// to SIGTERM apart from SIGBUS and SIGUSR1/2, have the same number in both.
+const SIGNAL_NUMBERS: [(Signal, u8); 14] = [
+    (Signal::SIGBUS, 10),
+    (Signal::SIGUSR1, 30),
+    (Signal::SIGUSR2, 31),
+    (Signal::SIGCHLD, 20),
+    (Signal::SIGCONT, 19),
+    (Signal::SIGSTOP, 17),
+    (Signal::SIGTSTP, 18),
+    (Signal::SIGURG, 16),
+    (Signal::SIGIO, 23),
+    (Signal::SIGPWR, 32),
+    (Signal::SIGSYS, 12),
+    (Signal::SIGWINCH, 28),
+    (Signal::SIGVTALRM, 26),
+    // the protocol has no SIGSTKFLT, so it is sent as an unknown signal
+    (Signal::SIGSTKFLT, 143),
+];
+
+/// What the thread reading from the connection passes on.
+enum Message {
+    Packet(Vec<u8>),
+    BadChecksum,
+    Closed,
+}
+
+/// Serves a debugger speaking gdb's remote serial protocol, such as gdb with `target remote`,
+/// which drives an inferior running target with args. address is [host]:port, the host
+/// defaulting to localhost; port 0 picks a free port, which is printed. Returns once the
+/// debugger disconnects or kills the inferior.
+pub fn serve(address: &str, target: &str, args: &[String]) {
+    let address = if address.starts_with(':') {
+        format!("localhost{}", address)
+    } else {
+        address.to_string()
+    };
+    let inferior = match Inferior::new(target, args) {
+        Some(inferior) => inferior,
+        None => {
+            println!("Error starting subprocess");
+            return;
+        }
+    };
+    println!("Process {} created; pid = {}", target, inferior.pid());
+    let listener = match TcpListener::bind(&address) {
+        Ok(listener) => listener,
+        Err(err) => {
+            println!("Can't bind address {}: {}", address, err);
+            return;
+        }
+    };
+    if let Ok(local) = listener.local_addr() {
+        println!("Listening on port {}", local.port());
+    }
+    let (stream, peer) = match listener.accept() {
+        Ok(connection) => connection,
+        Err(err) => {
+            println!("Error accepting connection: {}", err);
+            return;
+        }
+    };
+    println!("Remote debugging from host {}", peer.ip());
+    let packets = match read_packets(&stream, inferior.pid()) {
+        Ok(packets) => packets,
+        Err(err) => {
+            println!("Error reading from connection: {}", err);
+            return;
+        }
+    };
+    let mut server = GdbServer {
+        inferior: Some(inferior),
+        breakpoints: HashMap::new(),
+        watchpoints: [None; 4],
+        stream,
+        ack: true,
+        last_stop: "S05".to_string(),
+    };
+    server.run(packets);
+}
+
+/// Starts a thread that splits what the debugger sends into packets. An interrupt (a 0x03 byte)
+/// is passed straight on to the inferior as SIGINT, since the server may be waiting for it to
+/// stop.
+fn read_packets(stream: &TcpStream, pid: Pid) -> std::io::Result<Receiver<Message>> {
+    let mut stream = stream.try_clone()?;
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let len = match stream.read(&mut buf) {
+                Ok(0) | Err(_) => {
+                    let _ = sender.send(Message::Closed);
+                    return;
+                }
+                Ok(len) => len,
+            };
+            bytes.extend_from_slice(&buf[..len]);
+            while let Some((message, used)) = parse_message(&bytes) {
+                bytes.drain(..used);
+                match message {
+                    Some(message) => {
+                        if sender.send(message).is_err() {
+                            return;
+                        }
+                    }
+                    None => {
+                        let _ = signal::kill(pid, Signal::SIGINT);
+                    }
+                }
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+/// Takes the first packet or interrupt off bytes, returning it (None for an interrupt) and how
+/// many bytes it took up. Acknowledgements and noise are skipped. Returns None if bytes doesn't
+/// hold a whole packet yet.
+fn parse_message(bytes: &[u8]) -> Option<(Option<Message>, usize)> {
+    let start = bytes.iter().position(|byte| *byte == b'$' || *byte == 0x03)?;
+    if bytes[start] == 0x03 {
+        return Some((None, start + 1));
+    }
+    let end = start + bytes[start..].iter().position(|byte| *byte == b'#')?;
+    let checksum = bytes.get(end + 1..end + 3)?;
+    let data = &bytes[start + 1..end];
+    let expected = std::str::from_utf8(checksum)
+        .ok()
+        .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+    let message = if expected == Some(checksum_of(data)) {
+        Message::Packet(data.to_vec())
+    } else {
+        Message::BadChecksum
+    };
+    Some((Some(message), end + 3))
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Wraps data in a packet, escaping the characters that have a meaning in the protocol.
+fn frame(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len());
+    for byte in data {
+        if b"#$}*".contains(byte) {
+            escaped.push(b'}');
+            escaped.push(byte ^ 0x20);
+        } else {
+            escaped.push(*byte);
+        }
+    }
+    let checksum = checksum_of(&escaped);
+    let mut packet = vec![b'$'];
+    packet.extend_from_slice(&escaped);
+    packet.extend_from_slice(format!("#{:02x}", checksum).as_bytes());
+    packet
+}
+
+struct GdbServer {
+    /// The inferior, until it exits or is killed or detached.
+    inferior: Option<Inferior>,
+    /// The software breakpoints the debugger has inserted, in the form Inferior expects.
+    breakpoints: HashMap<usize, Option<Breakpoint>>,
+    /// The watchpoint in each debug register slot: its address, length and whether it also
+    /// triggers on reads.
+    watchpoints: [Option<(usize, usize, bool)>; 4],
+    stream: TcpStream,
+    /// Whether packets are acknowledged, which the debugger can turn off with QStartNoAckMode.
+    ack: bool,
+    /// The reply to the last stop, which ? asks for again.
+    last_stop: String,
+}
+
+impl GdbServer {
+    /// Answers packets until the debugger disconnects or the session ends.
+    fn run(&mut self, packets: Receiver<Message>) {
+        loop {
+            let packet = match packets.recv() {
+                Ok(Message::Packet(packet)) => packet,
+                Ok(Message::BadChecksum) => {
+                    let _ = self.stream.write_all(b"-");
+                    continue;
+                }
+                Ok(Message::Closed) | Err(_) => break,
+            };
+            if self.ack && self.stream.write_all(b"+").is_err() {
+                break;
+            }
+            let packet = String::from_utf8_lossy(&packet).to_string();
+            let (reply, done) = self.handle(&packet);
+            if let Some(reply) = reply {
+                if self.send(&reply).is_err() {
+                    break;
+                }
+            }
+            if done {
+                break;
+            }
+        }
+        if let Some(mut inferior) = self.inferior.take() {
+            let _ = inferior.kill();
+        }
+        println!("Remote side has terminated connection.");
+    }
+
+    /// Sends a packet.
+    fn send(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.stream.write_all(&frame(data))
+    }
+
+    /// Carries out a packet, returning the reply to send, if any, and whether the session is
+    /// over.
+    fn handle(&mut self, packet: &str) -> (Option<Vec<u8>>, bool) {
+        if packet.is_empty() || !packet.is_char_boundary(1) {
+            return (Some(Vec::new()), false);
+        }
+        if packet == "k" || packet.starts_with("vKill") {
+            if let Some(mut inferior) = self.inferior.take() {
+                let _ = inferior.kill();
+            }
+            let reply = if packet == "k" { None } else { Some(b"OK".to_vec()) };
+            return (reply, true);
+        }
+        if packet == "D" || packet.starts_with("D;") {
+            return (Some(self.detach().into_bytes()), true);
+        }
+        if packet.starts_with("qXfer:") {
+            return (Some(self.transfer(packet)), false);
+        }
+        let reply = match packet {
+            "?" => self.last_stop.clone(),
+            "QStartNoAckMode" => {
+                self.ack = false;
+                "OK".to_string()
+            }
+            "qAttached" => "0".to_string(),
+            "qfThreadInfo" => match &self.inferior {
+                Some(inferior) => {
+                    let tids: Vec<String> = inferior
+                        .threads()
+                        .iter()
+                        .map(|(_, tid)| format!("{:x}", tid.as_raw()))
+                        .collect();
+                    format!("m{}", tids.join(","))
+                }
+                None => "l".to_string(),
+            },
+            "qsThreadInfo" => "l".to_string(),
+            "qC" => match &self.inferior {
+                Some(inferior) => format!("QC{:x}", inferior.tid().as_raw()),
+                None => String::new(),
+            },
+            "vCont?" => "vCont;c;C;s;S".to_string(),
+            _ if packet.starts_with("qSupported") => "PacketSize=4000;QStartNoAckMode+;\
+                 qXfer:features:read+;qXfer:auxv:read+;swbreak+;vContSupported+"
+                .to_string(),
+            _ if self.inferior.is_some() => self.handle_with_inferior(packet),
+            // the inferior has exited
+            _ if "gGmMcsCSZzHT".contains(&packet[..1]) || packet.starts_with("vCont;") => {
+                "E01".to_string()
+            }
+            _ => String::new(),
+        };
+        (Some(reply.into_bytes()), false)
+    }
+
+    /// Carries out a packet that needs the inferior, which must be running.
+    fn handle_with_inferior(&mut self, packet: &str) -> String {
+        let (command, args) = packet.split_at(1);
+        let result = match command {
+            "g" => self.read_registers(),
+            "G" => self.write_registers(args),
+            "p" => self.read_register(args),
+            "P" => self.write_register(args),
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "c" | "s" | "C" | "S" => {
+                let (signal, address) = match command {
+                    "C" | "S" => {
+                        let mut parts = args.splitn(2, ';');
+                        (parts.next().and_then(from_gdb_signal), parts.next())
+                    }
+                    _ => (None, Some(args).filter(|args| !args.is_empty())),
+                };
+                if let Some(address) = address {
+                    if let Err(err) = self.set_pc(address) {
+                        return err;
+                    }
+                }
+                self.inferior.as_mut().unwrap().set_pending_signal(signal);
+                return self.resume(command == "s" || command == "S");
+            }
+            "Z" | "z" => self.update_stoppoint(command == "Z", args),
+            "H" => match thread_id(args) {
+                Some(tid) => self.select_thread(tid),
+                None => Err(()),
+            },
+            "T" => match self.thread_number(args) {
+                Some(_) => Ok("OK".to_string()),
+                None => Err(()),
+            },
+            "v" if packet.starts_with("vCont;") => return self.resume_threads(&packet[6..]),
+            _ => Ok(String::new()),
+        };
+        result.unwrap_or_else(|_| "E01".to_string())
+    }
+
+    /// Resumes the inferior according to the actions of a vCont packet, each of them c, s, Csig
+    /// or Ssig, optionally followed by :thread-id. Only one thread can be stepped; the other
+    /// threads stay stopped while it steps.
+    fn resume_threads(&mut self, actions: &str) -> String {
+        let mut step = false;
+        let mut resumed = Vec::new();
+        for action in actions.split(';') {
+            let mut parts = action.splitn(2, ':');
+            let command = parts.next().unwrap_or("");
+            // -1 means every thread and 0 any of them
+            match parts.next().filter(|tid| *tid != "-1" && *tid != "0") {
+                Some(tid) => match self.thread_number(tid) {
+                    Some(number) => {
+                        self.inferior.as_mut().unwrap().select_thread(number);
+                    }
+                    None => continue,
+                },
+                // an action without a thread applies to the threads no other action named
+                None if resumed.contains(&self.inferior.as_ref().unwrap().tid()) => continue,
+                None => {}
+            }
+            let inferior = self.inferior.as_mut().unwrap();
+            let signal = command.get(1..).and_then(from_gdb_signal);
+            inferior.set_pending_signal(signal);
+            resumed.push(inferior.tid());
+            if command.starts_with('s') || command.starts_with('S') {
+                step = true;
+                break;
+            }
+        }
+        self.resume(step)
+    }
+
+    /// Continues or steps the inferior, and returns the reply describing how it stopped.
+    fn resume(&mut self, step: bool) -> String {
+        let inferior = self.inferior.as_mut().unwrap();
+        let result = if step {
+            inferior.step_instruction(&self.breakpoints)
+        } else {
+            inferior.continue_exec(&self.breakpoints)
+        };
+        let reply = match result {
+            Ok(status) => self.stop_reply(status),
+            Err(_) => "E01".to_string(),
+        };
+        self.last_stop = reply.clone();
+        reply
+    }
+
+    /// Describes a stop of the inferior as a stop reply packet.
+    fn stop_reply(&mut self, status: Status) -> String {
+        let inferior = self.inferior.as_mut().unwrap();
+        match status {
+            Status::Stopped(signal, rip) => {
+                let mut reply =
+                    format!("T{:02x}thread:{:x};", to_gdb_signal(signal), inferior.tid().as_raw());
+                if signal == Signal::SIGTRAP {
+                    let hits = inferior.watchpoint_hits().unwrap_or_default();
+                    let _ = inferior.clear_watchpoint_hits();
+                    let watchpoint = hits.iter().find_map(|slot| self.watchpoints[*slot]);
+                    if let Some((addr, _, read)) = watchpoint {
+                        let kind = if read { "awatch" } else { "watch" };
+                        reply += &format!("{}:{:x};", kind, addr);
+                    } else if matches!(self.breakpoints.get(&rip), Some(Some(_))) {
+                        reply += "swbreak:;";
+                    }
+                }
+                reply
+            }
+            Status::Syscall(stop, _) => {
+                let kind = if stop.result.is_none() { "syscall_entry" } else { "syscall_return" };
+                format!("T05thread:{:x};{}:{:x};", inferior.tid().as_raw(), kind, stop.number)
+            }
+            Status::Exec(_) => {
+                // nothing of the old program is left, so the debugger has to start over
+                self.breakpoints.clear();
+                self.watchpoints = [None; 4];
+                format!("T05thread:{:x};", inferior.tid().as_raw())
+            }
+            Status::Exited(code) => {
+                self.inferior = None;
+                format!("W{:02x}", code as u8)
+            }
+            Status::Signaled(signal) => {
+                self.inferior = None;
+                format!("X{:02x}", to_gdb_signal(signal))
+            }
+        }
+    }
+
+    /// Takes the breakpoints and watchpoints out of the inferior and lets it run on.
+    fn detach(&mut self) -> String {
+        let mut inferior = match self.inferior.take() {
+            Some(inferior) => inferior,
+            None => return "E01".to_string(),
+        };
+        for (addr, breakpoint) in &self.breakpoints {
+            if let Some(bp) = breakpoint {
+                let _ = inferior.write_byte(*addr, bp.orig_byte);
+            }
+        }
+        let _ = inferior.write_debug_register(7, 0);
+        match inferior.detach() {
+            Ok(()) => "OK".to_string(),
+            Err(_) => "E01".to_string(),
+        }
+    }
+
+    fn read_registers(&self) -> Result<String, ()> {
+        let inferior = self.inferior.as_ref().unwrap();
+        let regs = ptrace::getregs(inferior.tid()).map_err(drop)?;
+        Ok(REGISTERS
+            .iter()
+            .map(|(name, bits, _)| encode_register(registers::get(&regs, name), bits / 8))
+            .collect())
+    }
+
+    fn write_registers(&mut self, data: &str) -> Result<String, ()> {
+        let tid = self.inferior.as_ref().unwrap().tid();
+        let mut regs = ptrace::getregs(tid).map_err(drop)?;
+        let mut offset = 0;
+        for (name, bits, _) in REGISTERS.iter() {
+            let len = bits / 8 * 2;
+            if let (Some(register), Some(value)) =
+                (registers::get_mut(&mut regs, name), data.get(offset..offset + len))
+            {
+                if let Some(value) = decode_register(value) {
+                    *register = value;
+                }
+            }
+            offset += len;
+        }
+        ptrace::setregs(tid, regs).map_err(drop)?;
+        Ok("OK".to_string())
+    }
+
+    /// Reads register number n of the g packet.
+    fn read_register(&self, n: &str) -> Result<String, ()> {
+        let (name, bits, _) = REGISTERS.get(parse_hex(n).ok_or(())?).ok_or(())?;
+        let regs = ptrace::getregs(self.inferior.as_ref().unwrap().tid()).map_err(drop)?;
+        Ok(encode_register(registers::get(&regs, name), bits / 8))
+    }
+
+    /// Writes a register given as n=value.
+    fn write_register(&mut self, args: &str) -> Result<String, ()> {
+        let mut parts = args.splitn(2, '=');
+        let n = parse_hex(parts.next().ok_or(())?).ok_or(())?;
+        let value = decode_register(parts.next().ok_or(())?).ok_or(())?;
+        let (name, _, _) = REGISTERS.get(n).ok_or(())?;
+        let tid = self.inferior.as_ref().unwrap().tid();
+        let mut regs = ptrace::getregs(tid).map_err(drop)?;
+        // the x87 registers can't be written
+        *registers::get_mut(&mut regs, name).ok_or(())? = value;
+        ptrace::setregs(tid, regs).map_err(drop)?;
+        Ok("OK".to_string())
+    }
+
+    fn set_pc(&mut self, address: &str) -> Result<(), String> {
+        let rip = parse_hex(address).ok_or("E01")?;
+        let tid = self.inferior.as_ref().unwrap().tid();
+        let mut regs = ptrace::getregs(tid).map_err(|_| "E01")?;
+        regs.rip = rip as u64;
+        ptrace::setregs(tid, regs).map_err(|_| "E01".to_string())
+    }
+
+    /// Reads memory given as addr,length. The original bytes are shown in place of our
+    /// breakpoints.
+    fn read_memory(&self, args: &str) -> Result<String, ()> {
+        let (addr, len) = parse_range(args).ok_or(())?;
+        let mut bytes = self.inferior.as_ref().unwrap().read_bytes(addr, len).map_err(drop)?;
+        for bp in self.breakpoints.values().flatten() {
+            if bp.addr >= addr && bp.addr < addr + len {
+                bytes[bp.addr - addr] = bp.orig_byte;
+            }
+        }
+        Ok(to_hex(&bytes))
+    }
+
+    /// Writes memory given as addr,length:bytes, keeping any breakpoints in the range in place.
+    fn write_memory(&mut self, args: &str) -> Result<String, ()> {
+        let mut parts = args.splitn(2, ':');
+        let (addr, len) = parse_range(parts.next().ok_or(())?).ok_or(())?;
+        let mut bytes = from_hex(parts.next().ok_or(())?).ok_or(())?;
+        if bytes.len() != len {
+            return Err(());
+        }
+        for bp in self.breakpoints.values_mut().flatten() {
+            if bp.addr >= addr && bp.addr < addr + len {
+                bp.orig_byte = bytes[bp.addr - addr];
+                bytes[bp.addr - addr] = 0xcc;
+            }
+        }
+        let inferior = self.inferior.as_mut().unwrap();
+        inferior.write_bytes(addr, &bytes).map_err(drop)?;
+        Ok("OK".to_string())
+    }
+
+    /// Inserts or removes a stoppoint given as type,addr,kind. Software breakpoints (type 0),
+    /// write watchpoints (2) and access watchpoints (4) are supported.
+    fn update_stoppoint(&mut self, insert: bool, args: &str) -> Result<String, ()> {
+        let mut parts = args.splitn(3, ',');
+        let kind = parts.next().ok_or(())?;
+        let addr = parse_hex(parts.next().ok_or(())?).ok_or(())?;
+        let len = parse_hex(parts.next().ok_or(())?.split(';').next().unwrap_or("")).ok_or(())?;
+        let inferior = self.inferior.as_mut().unwrap();
+        match kind {
+            "0" if insert => {
+                if !self.breakpoints.contains_key(&addr) {
+                    let orig_byte = inferior.write_byte(addr, 0xcc).map_err(drop)?;
+                    self.breakpoints.insert(addr, Some(Breakpoint { addr, orig_byte }));
+                }
+            }
+            "0" => {
+                if let Some(Some(bp)) = self.breakpoints.remove(&addr) {
+                    inferior.write_byte(addr, bp.orig_byte).map_err(drop)?;
+                }
+            }
+            "2" | "4" if insert => {
+                let read = kind == "4";
+                // the debug registers watch 1, 2, 4 or 8 aligned bytes, and the debugger
+                // splits anything else up
+                if ![1, 2, 4, 8].contains(&len) || addr % len != 0 {
+                    return Err(());
+                }
+                let slot = self.watchpoints.iter().position(|slot| slot.is_none()).ok_or(())?;
+                inferior.set_hardware_watchpoint(slot, addr, len, read).map_err(drop)?;
+                self.watchpoints[slot] = Some((addr, len, read));
+            }
+            "2" | "4" => {
+                let watchpoint = Some((addr, len, kind == "4"));
+                if let Some(slot) = self.watchpoints.iter().position(|slot| *slot == watchpoint) {
+                    inferior.clear_hardware_watchpoint(slot).map_err(drop)?;
+                    self.watchpoints[slot] = None;
+                }
+            }
+            _ => return Ok(String::new()),
+        }
+        Ok("OK".to_string())
+    }
+
+    /// Makes the thread given in an Hg or Hc packet current. 0 and -1 leave the current thread
+    /// alone.
+    fn select_thread(&mut self, tid: &str) -> Result<String, ()> {
+        if tid == "0" || tid == "-1" {
+            return Ok("OK".to_string());
+        }
+        let number = self.thread_number(tid).ok_or(())?;
+        self.inferior.as_mut().unwrap().select_thread(number);
+        Ok("OK".to_string())
+    }
+
+    /// Returns the number of the thread with the given hex id.
+    fn thread_number(&self, tid: &str) -> Option<usize> {
+        let tid = parse_hex(tid)? as i32;
+        let inferior = self.inferior.as_ref()?;
+        inferior.threads().iter().find(|(_, t)| t.as_raw() == tid).map(|(number, _)| *number)
+    }
+
+    /// Answers a qXfer:object:read:annex:offset,length packet, for the target description or
+    /// the inferior's auxiliary vector.
+    fn transfer(&self, packet: &str) -> Vec<u8> {
+        let fields: Vec<&str> = packet.splitn(5, ':').collect();
+        if fields.len() != 5 || fields[2] != "read" {
+            return Vec::new();
+        }
+        let data = match (fields[1], fields[3]) {
+            ("features", "target.xml") => target_description().into_bytes(),
+            ("auxv", "") => match &self.inferior {
+                Some(inferior) => match fs::read(format!("/proc/{}/auxv", inferior.pid())) {
+                    Ok(auxv) => auxv,
+                    Err(_) => return b"E01".to_vec(),
+                },
+                None => return b"E01".to_vec(),
+            },
+            ("features", _) => return b"E00".to_vec(),
+            _ => return Vec::new(),
+        };
+        let (offset, len) = match parse_range(fields[4]) {
+            Some(range) => range,
+            None => return b"E01".to_vec(),
+        };
+        let start = offset.min(data.len());
+        let end = (offset + len).min(data.len());
+        let mut reply = vec![if end == data.len() { b'l' } else { b'm' }];
+        reply.extend_from_slice(&data[start..end]);
+        reply
+    }
+}
+
+/// Returns the target description that tells the debugger which registers the g packet holds.
+fn target_description() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n\
+         <target version=\"1.0\">\n  <architecture>i386:x86-64</architecture>\n  \
+         <osabi>GNU/Linux</osabi>\n  <feature name=\"org.gnu.gdb.i386.core\">\n",
+    );
+    for (name, bits, reg_type) in REGISTERS.iter() {
+        let group = if *reg_type == "int" { " group=\"float\"" } else { "" };
+        xml += &format!(
+            "    <reg name=\"{}\" bitsize=\"{}\" type=\"{}\"{}/>\n",
+            name, bits, reg_type, group
+        );
+    }
+    xml + "  </feature>\n</target>\n"
+}
+
+/// Encodes a register of len bytes in target byte order, or as unavailable if value is None.
+fn encode_register(value: Option<u64>, len: usize) -> String {
+    match value {
+        Some(value) => {
+            let mut bytes = value.to_le_bytes().to_vec();
+            bytes.resize(len, 0);
+            to_hex(&bytes)
+        }
+        None => "xx".repeat(len),
+    }
+}
+
+/// Decodes a register sent in target byte order. Returns None if it is marked unavailable.
+fn decode_register(hex: &str) -> Option<u64> {
+    let mut bytes = from_hex(hex)?;
+    bytes.resize(8, 0);
+    Some(u64::from_le_bytes([
+        bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+    ]))
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+/// Parses an address and a length given as addr,length in hex. Returns None if the range runs
+/// past the end of the address space, so that addr + length can't overflow.
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let mut parts = text.splitn(2, ',');
+    let (addr, len) = (parse_hex(parts.next()?)?, parse_hex(parts.next()?)?);
+    addr.checked_add(len)?;
+    Some((addr, len))
+}
+
+/// Returns the thread id of an H packet, which follows the operation it applies to, g or c.
+fn thread_id(args: &str) -> Option<&str> {
+    args.get(1..)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Returns the number the remote protocol uses for a signal.
+fn to_gdb_signal(signal: Signal) -> u8 {
+    SIGNAL_NUMBERS
+        .iter()
+        .find(|(s, _)| *s == signal)
+        .map_or(signal as u8, |(_, number)| *number)
+}
+
+/// Parses a signal number from the remote protocol, given in hex.
+fn from_gdb_signal(text: &str) -> Option<Signal> {
+    let number = u8::from_str_radix(text, 16).ok()?;
+    match SIGNAL_NUMBERS.iter().find(|(_, n)| *n == number) {
+        Some((signal, _)) => Some(*signal),
+        None => Signal::try_from(number as i32)
+            .ok()
+            .filter(|signal| SIGNAL_NUMBERS.iter().all(|(s, _)| s != signal)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the data of the packet parse_message takes off bytes and how many bytes it used,
+    /// with "interrupt" and "bad checksum" standing in for the other messages.
+    fn parsed(bytes: &[u8]) -> Option<(String, usize)> {
+        let (message, used) = parse_message(bytes)?;
+        let message = match message {
+            Some(Message::Packet(data)) => String::from_utf8(data).unwrap(),
+            Some(Message::BadChecksum) => "bad checksum".to_string(),
+            Some(Message::Closed) => "closed".to_string(),
+            None => "interrupt".to_string(),
+        };
+        Some((message, used))
+    }
+
+    #[test]
+    fn parses_packets() {
+        assert_eq!(parsed(b"$g#67"), Some(("g".to_string(), 5)));
+        assert_eq!(parsed(b"+$m401136,4#fc$c#63"), Some(("m401136,4".to_string(), 14)));
+        assert_eq!(parsed(b"\x03$c#63"), Some(("interrupt".to_string(), 1)));
+        assert_eq!(parsed(b"$g#00"), Some(("bad checksum".to_string(), 5)));
+        assert_eq!(parsed(b"$g#zz"), Some(("bad checksum".to_string(), 5)));
+        // a packet that hasn't fully arrived is left for later
+        assert_eq!(parsed(b"$qSupported"), None);
+        assert_eq!(parsed(b"$g#6"), None);
+        assert_eq!(parsed(b"+++"), None);
+    }
+
+    #[test]
+    fn frames_and_escapes_packets() {
+        assert_eq!(frame(b"OK"), b"$OK#9a".to_vec());
+        assert_eq!(frame(b""), b"$#00".to_vec());
+        assert_eq!(frame(b"a#b$c}d*"), b"$a}\x03b}\x04c}]d}\n#ec".to_vec());
+        let escaped = frame(b"a#b$c}d*");
+        assert_eq!(parsed(&escaped).map(|(_, used)| used), Some(escaped.len()));
+    }
+
+    #[test]
+    fn converts_signal_numbers() {
+        assert_eq!(from_gdb_signal("05"), Some(Signal::SIGTRAP));
+        assert_eq!(from_gdb_signal("0b"), Some(Signal::SIGSEGV));
+        assert_eq!(from_gdb_signal("0a"), Some(Signal::SIGBUS));
+        assert_eq!(from_gdb_signal("1e"), Some(Signal::SIGUSR1));
+        assert_eq!(from_gdb_signal("11"), Some(Signal::SIGSTOP));
+        // 7 is SIGEMT in the protocol, which Linux doesn't have, and 0 is no signal
+        assert_eq!(from_gdb_signal("07"), None);
+        assert_eq!(from_gdb_signal("00"), None);
+        assert_eq!(from_gdb_signal("zz"), None);
+        for signal in Signal::iterator().filter(|signal| *signal != Signal::SIGSTKFLT) {
+            assert_eq!(from_gdb_signal(&format!("{:02x}", to_gdb_signal(signal))), Some(signal));
+        }
+    }
+
+    #[test]
+    fn rejects_ranges_that_overflow() {
+        assert_eq!(parse_range("401136,10"), Some((0x401136, 0x10)));
+        assert_eq!(parse_range("ffffffffffffffff,10"), None);
+        assert_eq!(parse_range("ffffffffffffffe0,10"), Some((0xffffffffffffffe0, 0x10)));
+        assert_eq!(parse_range("401136"), None);
+    }
+
+    #[test]
+    fn rejects_malformed_thread_selections() {
+        assert_eq!(thread_id("g1a2b"), Some("1a2b"));
+        assert_eq!(thread_id("c-1"), Some("-1"));
+        assert_eq!(thread_id("g"), Some(""));
+        assert_eq!(thread_id(""), None);
+        // the operation is a single byte, so a multibyte character can't be split
+        assert_eq!(thread_id("\u{e9}1"), None);
+    }
+}
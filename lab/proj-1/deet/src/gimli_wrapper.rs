@@ -10,13 +10,19 @@ use gimli::{UnitOffset, UnitSectionOffset};
 use object::Object;
 use std::borrow;
 //use std::io::{BufWriter, Write};
-use crate::dwarf_data::{File, Function, Line, Location, Type, Variable};
+use crate::dwarf_data::{
+    self, BaseEncoding, File, Function, Language, Line, Location, Member, Qualifier, Type,
+    TypeKind, Variable, Variant,
+};
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::fmt::Write;
 use std::{io, path};
 
-pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<Vec<File>, Error> {
+pub fn load_file(
+    object: &object::File,
+    endian: gimli::RunTimeEndian,
+) -> Result<(Vec<File>, HashMap<usize, Type>), Error> {
     // Load a section and return as `Cow<[u8]>`.
     let load_section = |id: gimli::SectionId| -> Result<borrow::Cow<[u8]>, gimli::Error> {
         Ok(object
@@ -41,6 +47,17 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
 
     // Define a mapping from type offsets to type structs
     let mut offset_to_type: HashMap<usize, Type> = HashMap::new();
+    // (file index, function index, variable index, type offset) for each variable loaded
+    let mut variable_types: Vec<(usize, Option<usize>, usize, usize)> = Vec::new();
+    // (depth, offset) of the struct, union, enum and array types whose children are being read
+    let mut parent_types: Vec<(isize, usize)> = Vec::new();
+    // Depth of the function whose children are being read, which variables are local to
+    let mut function_depth: Option<isize> = None;
+    // Offsets of the members that hold Rust enum discriminants, and the discriminant value of
+    // the enum variant whose member comes next
+    let mut discriminants: Vec<usize> = Vec::new();
+    let mut discriminant_value: Option<u64> = None;
+    let mut language = Language::C;
 
     let mut compilation_units: Vec<File> = Vec::new();
 
@@ -54,11 +71,24 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
         let mut entries = unit.entries();
         while let Some((delta_depth, entry)) = entries.next_dfs()? {
             depth += delta_depth;
+            // Types at this depth or deeper have had all their children read
+            while parent_types.last().map_or(false, |(d, _)| *d >= depth) {
+                parent_types.pop();
+            }
+            if function_depth.map_or(false, |d| d >= depth) {
+                function_depth = None;
+            }
             // Update the offset_to_type mapping for types
             // Update the variable list for formal params/variables
             match entry.tag() {
                 gimli::DW_TAG_compile_unit => {
-                    let name = if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_name) {
+                    language = match entry.attr_value(gimli::DW_AT_language)? {
+                        Some(gimli::AttributeValue::Language(gimli::DW_LANG_Rust)) => {
+                            Language::Rust
+                        }
+                        _ => Language::C,
+                    };
+                    let mut name = if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_name) {
                         if let Ok(DebugValue::Str(name)) = get_attr_value(&attr, &unit, &dwarf) {
                             name
                         } else {
@@ -67,6 +97,10 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                     } else {
                         "<unknown>".to_string()
                     };
+                    // rustc names units like "src/main.rs/@/crate.1a2b3c-cgu.0"
+                    if let Some(end) = name.find("/@/") {
+                        name.truncate(end);
+                    }
                     compilation_units.push(File {
                         name,
                         global_variables: Vec::new(),
@@ -75,34 +109,207 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                     });
                 }
                 gimli::DW_TAG_base_type => {
-                    let name = if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_name) {
-                        if let Ok(DebugValue::Str(name)) = get_attr_value(&attr, &unit, &dwarf) {
-                            name
-                        } else {
-                            "<unknown>".to_string()
+                    let name = get_name(entry, &unit, &dwarf);
+                    let byte_size = get_byte_size(entry, &unit, &dwarf);
+                    let encoding = match entry.attr_value(gimli::DW_AT_encoding)? {
+                        Some(gimli::AttributeValue::Encoding(gimli::DW_ATE_signed)) => {
+                            BaseEncoding::Signed
+                        }
+                        Some(gimli::AttributeValue::Encoding(gimli::DW_ATE_signed_char)) => {
+                            BaseEncoding::SignedChar
+                        }
+                        Some(gimli::AttributeValue::Encoding(gimli::DW_ATE_unsigned_char)) => {
+                            BaseEncoding::UnsignedChar
+                        }
+                        Some(gimli::AttributeValue::Encoding(gimli::DW_ATE_float)) => {
+                            BaseEncoding::Float
                         }
+                        Some(gimli::AttributeValue::Encoding(gimli::DW_ATE_boolean)) => {
+                            BaseEncoding::Boolean
+                        }
+                        _ => BaseEncoding::Unsigned,
+                    };
+                    offset_to_type.insert(
+                        get_offset(entry, &unit),
+                        Type::new(name, byte_size, TypeKind::Base(encoding)),
+                    );
+                }
+                gimli::DW_TAG_pointer_type => {
+                    let target = get_type_offset(entry, &unit, &dwarf);
+                    // The name is filled in once the target type has been loaded, except that
+                    // rustc names its pointers, e.g. &mut T or *const T
+                    let mut pointer = Type::new(String::new(), 8, TypeKind::Pointer(target));
+                    if language == Language::Rust && entry.attr(gimli::DW_AT_name)?.is_some() {
+                        pointer.name = get_name(entry, &unit, &dwarf);
+                        pointer.language = language;
+                    }
+                    offset_to_type.insert(get_offset(entry, &unit), pointer);
+                }
+                gimli::DW_TAG_structure_type | gimli::DW_TAG_union_type => {
+                    let keyword = if entry.tag() == gimli::DW_TAG_structure_type {
+                        "struct"
                     } else {
-                        "<unknown>".to_string()
+                        "union"
+                    };
+                    let name = match entry.attr(gimli::DW_AT_name)? {
+                        // Rust has no struct keyword in type names
+                        Some(_) if language == Language::Rust => get_name(entry, &unit, &dwarf),
+                        Some(_) => format!("{} {}", keyword, get_name(entry, &unit, &dwarf)),
+                        None => format!("{} {{...}}", keyword),
+                    };
+                    let kind = if entry.tag() == gimli::DW_TAG_structure_type {
+                        TypeKind::Struct(Vec::new())
+                    } else {
+                        TypeKind::Union(Vec::new())
+                    };
+                    let offset = get_offset(entry, &unit);
+                    let byte_size = get_byte_size(entry, &unit, &dwarf);
+                    let mut struct_type = Type::new(name, byte_size, kind);
+                    struct_type.language = language;
+                    offset_to_type.insert(offset, struct_type);
+                    parent_types.push((depth, offset));
+                }
+                gimli::DW_TAG_variant_part | gimli::DW_TAG_variant => {
+                    // The variants of a Rust enum are children of its struct type, and their
+                    // members are added to it
+                    let parent_offset = match parent_types.last() {
+                        Some((parent_depth, offset)) if *parent_depth == depth - 1 => *offset,
+                        _ => continue,
                     };
-                    let byte_size = if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_byte_size) {
-                        if let Ok(DebugValue::Uint(byte_size)) =
-                            get_attr_value(&attr, &unit, &dwarf)
+                    if entry.tag() == gimli::DW_TAG_variant_part {
+                        if let Some(Ok(DebugValue::Size(discriminant))) = entry
+                            .attr(gimli::DW_AT_discr)?
+                            .map(|attr| get_attr_value(&attr, &unit, &dwarf))
                         {
-                            byte_size
-                        } else {
-                            // TODO: report error?
-                            0
+                            discriminants.push(discriminant);
+                        }
+                        if let Some(parent) = offset_to_type.get_mut(&parent_offset) {
+                            parent.kind = TypeKind::Variants(None, Vec::new());
+                        }
+                    } else {
+                        discriminant_value = entry
+                            .attr(gimli::DW_AT_discr_value)?
+                            .and_then(|attr| attr.udata_value());
+                    }
+                    parent_types.push((depth, parent_offset));
+                }
+                gimli::DW_TAG_template_type_parameter => {
+                    let param = get_type_offset(entry, &unit, &dwarf);
+                    if let (Some(parent), Some(param)) =
+                        (get_parent_type(&parent_types, depth, &mut offset_to_type), param)
+                    {
+                        parent.params.push(param);
+                    }
+                }
+                gimli::DW_TAG_member => {
+                    let member = Member {
+                        name: get_name(entry, &unit, &dwarf),
+                        type_offset: get_type_offset(entry, &unit, &dwarf).unwrap_or(0),
+                        offset: entry
+                            .attr(gimli::DW_AT_data_member_location)?
+                            .and_then(|attr| attr.udata_value())
+                            .unwrap_or(0)
+                            .try_into()
+                            .unwrap(),
+                    };
+                    let is_discriminant = discriminants.contains(&get_offset(entry, &unit));
+                    if let Some(parent) = get_parent_type(&parent_types, depth, &mut offset_to_type) {
+                        match &mut parent.kind {
+                            TypeKind::Struct(members) | TypeKind::Union(members) => {
+                                members.push(member)
+                            }
+                            TypeKind::Variants(discriminant, _) if is_discriminant => {
+                                *discriminant = Some(member)
+                            }
+                            TypeKind::Variants(_, variants) => variants.push(Variant {
+                                discriminant: discriminant_value.take(),
+                                member,
+                            }),
+                            _ => {}
                         }
+                    }
+                }
+                gimli::DW_TAG_array_type => {
+                    let element = get_type_offset(entry, &unit, &dwarf).unwrap_or(0);
+                    let offset = get_offset(entry, &unit);
+                    // The dimensions are filled in from the subrange children
+                    offset_to_type.insert(
+                        offset,
+                        Type::new(String::new(), 0, TypeKind::Array(element, Vec::new())),
+                    );
+                    parent_types.push((depth, offset));
+                }
+                gimli::DW_TAG_subrange_type => {
+                    let length = if let Some(count) = entry.attr(gimli::DW_AT_count)? {
+                        count.udata_value().unwrap_or(0)
+                    } else if let Some(upper_bound) = entry.attr(gimli::DW_AT_upper_bound)? {
+                        upper_bound.udata_value().map(|bound| bound + 1).unwrap_or(0)
                     } else {
-                        // TODO: report error?
+                        // flexible array member
                         0
                     };
-                    let type_offset = entry.offset().0;
-                    offset_to_type
-                        .insert(type_offset, Type::new(name, byte_size.try_into().unwrap()));
+                    if let Some(parent) = get_parent_type(&parent_types, depth, &mut offset_to_type) {
+                        if let TypeKind::Array(_, dimensions) = &mut parent.kind {
+                            dimensions.push(length.try_into().unwrap());
+                        }
+                    }
+                }
+                gimli::DW_TAG_enumeration_type => {
+                    let name = match entry.attr(gimli::DW_AT_name)? {
+                        Some(_) => format!("enum {}", get_name(entry, &unit, &dwarf)),
+                        None => "enum {...}".to_string(),
+                    };
+                    let offset = get_offset(entry, &unit);
+                    let byte_size = get_byte_size(entry, &unit, &dwarf);
+                    offset_to_type.insert(
+                        offset,
+                        Type::new(name, byte_size, TypeKind::Enum(Vec::new())),
+                    );
+                    parent_types.push((depth, offset));
+                }
+                gimli::DW_TAG_enumerator => {
+                    let name = get_name(entry, &unit, &dwarf);
+                    let value = match entry.attr_value(gimli::DW_AT_const_value)? {
+                        Some(gimli::AttributeValue::Sdata(value)) => value,
+                        Some(value) => value.udata_value().unwrap_or(0) as i64,
+                        None => 0,
+                    };
+                    if let Some(parent) = get_parent_type(&parent_types, depth, &mut offset_to_type) {
+                        if let TypeKind::Enum(variants) = &mut parent.kind {
+                            variants.push((name, value));
+                        }
+                    }
+                }
+                gimli::DW_TAG_typedef => {
+                    let name = get_name(entry, &unit, &dwarf);
+                    let target = get_type_offset(entry, &unit, &dwarf);
+                    offset_to_type.insert(
+                        get_offset(entry, &unit),
+                        Type::new(name, 0, TypeKind::Typedef(target)),
+                    );
+                }
+                gimli::DW_TAG_const_type | gimli::DW_TAG_volatile_type => {
+                    let qualifier = if entry.tag() == gimli::DW_TAG_const_type {
+                        Qualifier::Const
+                    } else {
+                        Qualifier::Volatile
+                    };
+                    let target = get_type_offset(entry, &unit, &dwarf);
+                    offset_to_type.insert(
+                        get_offset(entry, &unit),
+                        Type::new(String::new(), 0, TypeKind::Qualified(qualifier, target)),
+                    );
+                }
+                gimli::DW_TAG_subroutine_type => {
+                    let return_type = get_type_offset(entry, &unit, &dwarf);
+                    offset_to_type.insert(
+                        get_offset(entry, &unit),
+                        Type::new(String::new(), 0, TypeKind::Function(return_type)),
+                    );
                 }
                 gimli::DW_TAG_subprogram => {
                     let mut func: Function = Default::default();
+                    let mut linkage_name = None;
                     let mut attrs = entry.attrs();
                     while let Some(attr) = attrs.next()? {
                         let val = get_attr_value(&attr, &unit, &dwarf);
@@ -129,14 +336,33 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                                     func.line_number = line_number.try_into().unwrap();
                                 }
                             }
+                            gimli::DW_AT_type => {
+                                if let Ok(DebugValue::Size(offset)) = val {
+                                    func.return_type = Some(offset);
+                                }
+                            }
+                            gimli::DW_AT_linkage_name | gimli::DW_AT_MIPS_linkage_name => {
+                                if let Ok(DebugValue::Str(name)) = val {
+                                    linkage_name = Some(name);
+                                }
+                            }
+                            gimli::DW_AT_frame_base => {
+                                func.frame_base = get_frame_base(&attr, &unit)
+                            }
                             _ => {}
                         }
                     }
+                    // The linkage name gives the full path of Rust and C++ functions, e.g.
+                    // crate::module::func rather than just func
+                    if let Some(name) = linkage_name.and_then(|name| dwarf_data::demangle(&name)) {
+                        func.name = name;
+                    }
+                    function_depth = Some(depth);
                     compilation_units.last_mut().unwrap().functions.push(func);
                 }
                 gimli::DW_TAG_formal_parameter | gimli::DW_TAG_variable => {
                     let mut name = String::new();
-                    let mut entity_type: Option<Type> = None;
+                    let mut type_offset: Option<usize> = None;
                     let mut location: Option<Location> = None;
                     let mut line_number = 0;
                     let mut attrs = entry.attrs();
@@ -151,9 +377,7 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                             }
                             gimli::DW_AT_type => {
                                 if let Ok(DebugValue::Size(offset)) = val {
-                                    if let Some(dtype) = offset_to_type.get(&offset).clone() {
-                                        entity_type = Some(dtype.clone());
-                                    }
+                                    type_offset = Some(offset);
                                 }
                             }
                             gimli::DW_AT_location => {
@@ -169,28 +393,38 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                             _ => {}
                         }
                     }
-                    if entity_type.is_some() && location.is_some() {
+                    if type_offset.is_some() && location.is_some() {
+                        // The type may not have been loaded yet, so it is filled in once all
+                        // units have been read
                         let var = Variable {
                             name,
-                            entity_type: entity_type.unwrap(),
+                            entity_type: Default::default(),
                             location: location.unwrap(),
                             line_number: line_number.try_into().unwrap(),
+                            is_parameter: entry.tag() == gimli::DW_TAG_formal_parameter,
                         };
-                        if depth == 1 {
-                            compilation_units
-                                .last_mut()
-                                .unwrap()
-                                .global_variables
-                                .push(var);
-                        } else if depth > 1 {
-                            compilation_units
-                                .last_mut()
-                                .unwrap()
-                                .functions
-                                .last_mut()
-                                .unwrap()
-                                .variables
-                                .push(var);
+                        let file_index = compilation_units.len() - 1;
+                        let file = compilation_units.last_mut().unwrap();
+                        // statics in Rust modules are nested in namespaces rather than at the
+                        // top level
+                        if function_depth.is_none() {
+                            file.global_variables.push(var);
+                            variable_types.push((
+                                file_index,
+                                None,
+                                file.global_variables.len() - 1,
+                                type_offset.unwrap(),
+                            ));
+                        } else {
+                            let func_index = file.functions.len() - 1;
+                            let func = file.functions.last_mut().unwrap();
+                            func.variables.push(var);
+                            variable_types.push((
+                                file_index,
+                                Some(func_index),
+                                func.variables.len() - 1,
+                                type_offset.unwrap(),
+                            ));
                         }
                     }
                 }
@@ -205,7 +439,8 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
             // Iterate over the line program rows.
             let mut rows = program.rows();
             while let Some((header, row)) = rows.next_row()? {
-                if !row.end_sequence() {
+                // Only statement boundaries are places a breakpoint on a line should go
+                if !row.end_sequence() && row.is_stmt() {
                     // Determine the path. Real applications should cache this for performance.
                     let mut path = path::PathBuf::new();
                     if let Some(file) = row.file(header) {
@@ -220,10 +455,12 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                         );
                     }
 
-                    // Get the File
-                    let file = compilation_units
-                        .iter_mut()
-                        .find(|f| f.name == path.as_os_str().to_str().unwrap());
+                    // Get the File. The line program joins the compilation directory onto
+                    // the path, while the unit name may be relative to it.
+                    let path = path.as_os_str().to_str().unwrap();
+                    let file = compilation_units.iter_mut().find(|f| {
+                        f.name == path || path.ends_with(&format!("/{}", f.name))
+                    });
 
                     // Determine line/column. DWARF line/column is never 0, so we use that
                     // but other applications may want to display this differently.
@@ -240,7 +477,157 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
             }
         }
     }
-    Ok(compilation_units)
+
+    // Now that every type has been loaded, work out the names and sizes of types that are
+    // derived from other types, and fill in variable types
+    let offsets: Vec<usize> = offset_to_type.keys().cloned().collect();
+    for offset in &offsets {
+        let name = type_name(&offset_to_type, *offset, 0);
+        let size = type_size(&offset_to_type, *offset, 0);
+        let var_type = offset_to_type.get_mut(offset).unwrap();
+        var_type.name = name;
+        var_type.size = size;
+        var_type.offset = Some(*offset);
+    }
+    for (file_index, func_index, var_index, type_offset) in variable_types {
+        let file = &mut compilation_units[file_index];
+        let var = match func_index {
+            Some(func_index) => &mut file.functions[func_index].variables[var_index],
+            None => &mut file.global_variables[var_index],
+        };
+        var.entity_type = match offset_to_type.get(&type_offset) {
+            Some(var_type) => var_type.clone(),
+            None => Type::new("<unknown>".to_string(), 0, TypeKind::Unknown),
+        };
+    }
+    Ok((compilation_units, offset_to_type))
+}
+
+/// Types can refer to themselves through pointers, so stop following references past this depth.
+const MAX_TYPE_DEPTH: usize = 16;
+
+/// Builds the name of the type at the given offset, following references to other types.
+fn type_name(types: &HashMap<usize, Type>, offset: usize, depth: usize) -> String {
+    let target_name = |target: &Option<usize>| match target {
+        Some(_) if depth >= MAX_TYPE_DEPTH => "?".to_string(),
+        Some(target) => type_name(types, *target, depth + 1),
+        None => "void".to_string(),
+    };
+    let var_type = match types.get(&offset) {
+        Some(var_type) => var_type,
+        None => return "<unknown>".to_string(),
+    };
+    match &var_type.kind {
+        TypeKind::Pointer(_) if var_type.language == Language::Rust => var_type.name.clone(),
+        TypeKind::Pointer(target) => match target.and_then(|target| types.get(&target)) {
+            Some(Type { kind: TypeKind::Function(return_type), .. }) => {
+                format!("{} (*)()", target_name(return_type))
+            }
+            _ => format!("{} *", target_name(target)),
+        },
+        TypeKind::Array(element, dimensions) => {
+            let dimensions: Vec<String> = dimensions.iter().map(|d| format!("[{}]", d)).collect();
+            format!("{} {}", target_name(&Some(*element)), dimensions.join(""))
+        }
+        TypeKind::Qualified(qualifier, target) => {
+            let keyword = match qualifier {
+                Qualifier::Const => "const",
+                Qualifier::Volatile => "volatile",
+            };
+            let target_name = target_name(target);
+            if target_name.ends_with('*') {
+                format!("{} {}", target_name, keyword)
+            } else {
+                format!("{} {}", keyword, target_name)
+            }
+        }
+        TypeKind::Function(return_type) => format!("{} ()", target_name(return_type)),
+        _ => var_type.name.clone(),
+    }
+}
+
+/// Works out the size of the type at the given offset. Typedefs, qualifiers and arrays don't
+/// record a size of their own.
+fn type_size(types: &HashMap<usize, Type>, offset: usize, depth: usize) -> usize {
+    let var_type = match types.get(&offset) {
+        Some(var_type) => var_type,
+        None => return 0,
+    };
+    if depth >= MAX_TYPE_DEPTH {
+        return var_type.size;
+    }
+    match &var_type.kind {
+        TypeKind::Typedef(Some(target)) | TypeKind::Qualified(_, Some(target)) => {
+            type_size(types, *target, depth + 1)
+        }
+        TypeKind::Array(element, dimensions) if var_type.size == 0 => {
+            type_size(types, *element, depth + 1) * dimensions.iter().product::<usize>()
+        }
+        _ => var_type.size,
+    }
+}
+
+/// Returns the struct, union, enum or array type that an entry at the given depth belongs to.
+fn get_parent_type<'a>(
+    parent_types: &Vec<(isize, usize)>,
+    depth: isize,
+    types: &'a mut HashMap<usize, Type>,
+) -> Option<&'a mut Type> {
+    match parent_types.last() {
+        Some((parent_depth, parent_offset)) if *parent_depth == depth - 1 => {
+            types.get_mut(parent_offset)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the .debug_info offset of an entry, which is how DW_AT_type attributes refer to it.
+fn get_offset<R: Reader>(entry: &gimli::DebuggingInformationEntry<R>, unit: &gimli::Unit<R>) -> usize {
+    match entry.offset().to_unit_section_offset(unit) {
+        UnitSectionOffset::DebugInfoOffset(goff) => goff.0,
+        UnitSectionOffset::DebugTypesOffset(goff) => goff.0,
+    }
+}
+
+fn get_name<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> String {
+    if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_name) {
+        if let Ok(DebugValue::Str(name)) = get_attr_value(&attr, unit, dwarf) {
+            return name;
+        }
+    }
+    "<unknown>".to_string()
+}
+
+fn get_byte_size<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> usize {
+    if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_byte_size) {
+        if let Ok(DebugValue::Uint(byte_size)) = get_attr_value(&attr, unit, dwarf) {
+            return byte_size.try_into().unwrap();
+        }
+    }
+    // TODO: report error?
+    0
+}
+
+/// Returns the offset of the type referred to by an entry's DW_AT_type, if it has one.
+fn get_type_offset<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> Option<usize> {
+    if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_type) {
+        if let Ok(DebugValue::Size(offset)) = get_attr_value(&attr, unit, dwarf) {
+            return Some(offset);
+        }
+    }
+    None
 }
 
 #[derive(Debug, Clone)]
@@ -312,6 +699,20 @@ fn get_location<R: Reader>(attr: &gimli::Attribute<R>, unit: &gimli::Unit<R>) ->
     None
 }
 
+/// Returns the register that a function's DW_AT_frame_base names, or None for the canonical
+/// frame address or anything else.
+fn get_frame_base<R: Reader>(attr: &gimli::Attribute<R>, unit: &gimli::Unit<R>) -> Option<u16> {
+    if let gimli::AttributeValue::Exprloc(ref data) = attr.value() {
+        let mut pc = data.0.clone();
+        if let Ok(gimli::Operation::Register { register }) =
+            gimli::Operation::parse(&mut pc, unit.encoding())
+        {
+            return Some(register.0);
+        }
+    }
+    None
+}
+
 // based on dwarf_dump.rs
 fn get_attr_value<R: Reader>(
     attr: &gimli::Attribute<R>,
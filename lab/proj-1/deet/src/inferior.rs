@@ -1,16 +1,25 @@
+use nix::errno::Errno;
 use nix::sys::ptrace;
 use nix::sys::signal;
 use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
-use nix::unistd::Pid;
-use std::process::Child;
-
-use std::process::Command;
+use nix::unistd::{self, Pid};
+use std::process::{Command, Stdio};
 use std::os::unix::process::CommandExt;
 use crate::dwarf_data::DwarfData;
+use crate::events::{self, Event};
+use std::io::{BufRead, BufReader};
+use std::thread;
 use std::mem::size_of;
 use crate::debugger::Breakpoint;
+use crate::expr::Argument;
+use crate::signals::{self, SignalTable};
+use crate::syscalls::SyscallStop;
+use crate::target::Target;
+use std::fs;
+use std::path::Path;
 use std::collections::HashMap;
 use std::convert::TryInto;
+use std::sync::atomic::{AtomicI32, Ordering};
 
 pub enum Status {
     /// Indicates inferior stopped. Contains the signal that stopped the process, as well as the
@@ -23,6 +32,84 @@ pub enum Status {
     /// Indicates the inferior exited due to a signal. Contains the signal that killed the
     /// process.
     Signaled(signal::Signal),
+
+    /// Indicates the inferior replaced its program with a new one and stopped at its entry point.
+    /// Contains the instruction pointer. Nothing of the old program, such as breakpoints, is left.
+    Exec(usize),
+
+    /// Indicates the inferior stopped at the entry or exit of a syscall that is being caught.
+    /// Contains the syscall and the instruction pointer.
+    Syscall(SyscallStop, usize),
+}
+
+/// What happens to the two processes after the inferior forks.
+#[derive(Clone, Copy)]
+pub struct ForkPolicy {
+    /// Whether deet goes on debugging the child rather than the parent.
+    pub follow_child: bool,
+    /// Whether the process that isn't followed is detached, rather than kept stopped as another
+    /// inferior.
+    pub detach: bool,
+}
+
+/// The state of a thread from before a function was called in it, for putting back afterwards.
+pub struct SavedRegisters {
+    tid: Pid,
+    regs: libc::user_regs_struct,
+    fpregs: libc::user_fpregs_struct,
+    pending_signal: Option<signal::Signal>,
+}
+
+/// Where step_line has got to in stepping over a source line.
+pub struct LineStep {
+    start_line: Option<(String, usize)>,
+    step_into: bool,
+    /// A call being run to completion: the address it returns to, and %rsp once it has.
+    returning_to: Option<(usize, usize)>,
+    /// The address just past the prologue of a function being stepped into.
+    entering: Option<usize>,
+    /// Whether the step has got where it was going, rather than stopping at a breakpoint on the
+    /// way.
+    pub finished: bool,
+}
+
+/// The registers integer and pointer arguments are passed in, in order.
+const ARGUMENT_REGISTERS: usize = 6;
+/// The number of %xmm registers floating point arguments are passed in.
+const FLOAT_ARGUMENT_REGISTERS: usize = 8;
+/// The bytes below %rsp that a function may use without moving %rsp.
+const RED_ZONE: usize = 128;
+
+impl Target for Inferior {
+    fn registers(&self) -> Result<libc::user_regs_struct, nix::Error> {
+        ptrace::getregs(self.tid())
+    }
+
+    fn read_bytes(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error> {
+        Inferior::read_bytes(self, addr, len)
+    }
+
+    fn memory_map(&self) -> String {
+        fs::read_to_string(format!("/proc/{}/maps", self.pid())).unwrap_or_default()
+    }
+
+    fn entry_point(&self) -> Option<usize> {
+        const AT_ENTRY: usize = 9;
+        let auxv = fs::read(format!("/proc/{}/auxv", self.pid())).ok()?;
+        // pairs of words: a type and a value
+        auxv.chunks_exact(16).find_map(|entry| {
+            let word = |bytes: &[u8]| usize::from_le_bytes(bytes.try_into().unwrap());
+            if word(&entry[..8]) == AT_ENTRY {
+                Some(word(&entry[8..]))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn read_word(&self, addr: usize) -> Result<usize, nix::Error> {
+        Inferior::read_word(self, addr)
+    }
 }
 
 /// This function calls ptrace with PTRACE_TRACEME to enable debugging on a process. You should use
@@ -34,44 +121,379 @@ fn child_traceme() -> Result<(), std::io::Error> {
     )))
 }
 
+/// A thread of the inferior.
+struct Thread {
+    tid: Pid,
+    /// The number the user refers to the thread by, 1 being the main thread.
+    number: usize,
+    /// Whether the thread has been resumed and hasn't been seen to stop since.
+    running: bool,
+    /// Number of SIGSTOPs the thread has yet to report that aren't meant for the user: the ones
+    /// deet sends to stop it, and the one a new thread starts with.
+    expected_stops: usize,
+    /// Whether the thread has yet to stop for the first time.
+    new: bool,
+    /// The signal to deliver the next time the thread resumes.
+    pending_signal: Option<signal::Signal>,
+    /// A reason the thread stopped while the other threads were being stopped, to be reported
+    /// the next time the inferior resumes instead of resuming it.
+    pending_status: Option<Status>,
+    /// Whether the thread's last syscall stop was at an entry, so that the next one is the exit.
+    in_syscall: bool,
+}
+
+impl Thread {
+    fn new(tid: Pid, number: usize) -> Thread {
+        Thread {
+            tid,
+            number,
+            running: false,
+            expected_stops: 0,
+            new: false,
+            pending_signal: None,
+            pending_status: None,
+            in_syscall: false,
+        }
+    }
+}
+
 pub struct Inferior {
-    child: Child,
+    pid: Pid,
+    /// Whether deet attached to an already running process rather than spawning it.
+    attached: bool,
+    /// What to do with each signal the inferior receives.
+    signals: SignalTable,
+    fork_policy: ForkPolicy,
+    /// Processes forked off the inferior that are kept stopped, for the debugger to pick up.
+    held: Vec<Inferior>,
+    /// Forked children whose first stop was seen before the fork was reported.
+    unclaimed_stops: Vec<Pid>,
+    threads: Vec<Thread>,
+    next_thread_number: usize,
+    /// The thread that registers are read from and that steps, as selected by the user or by
+    /// the last stop.
+    current: Pid,
+    /// The thread whose stop was last reported, which may be sitting on a breakpoint it has
+    /// already hit.
+    event_thread: Pid,
+    /// The thread the user was last told about.
+    reported_thread: Pid,
+    /// The debug registers that have been set, which every new thread gets a copy of.
+    debug_registers: [usize; 8],
+    /// The syscalls the inferior stops at the entry and exit of, all of them if empty, or None
+    /// to let syscalls run without stopping.
+    caught_syscalls: Option<Vec<usize>>,
+}
+
+/// The pid of an attached inferior that isn't in deet's process group, or 0. Ctrl-C only sends
+/// SIGINT to the terminal's foreground process group, so interrupt passes it on to this process.
+static INTERRUPT_PID: AtomicI32 = AtomicI32::new(0);
+
+/// SIGINT handler for deet, installed with SA_RESTART so that waiting for the inferior carries on.
+/// A spawned inferior receives Ctrl-C itself; an attached one elsewhere gets it forwarded.
+pub extern "C" fn interrupt(_: libc::c_int) {
+    let pid = INTERRUPT_PID.load(Ordering::SeqCst);
+    if pid != 0 {
+        unsafe { libc::kill(pid, libc::SIGINT) };
+    }
+}
+
+impl Drop for Inferior {
+    fn drop(&mut self) {
+        let pid = self.pid.as_raw();
+        let _ = INTERRUPT_PID.compare_exchange(pid, 0, Ordering::SeqCst, Ordering::SeqCst);
+    }
+}
+
+/// The events deet follows in the inferior: new threads, forks and execs. Syscall stops are told
+/// apart from SIGTRAPs as well.
+fn trace_options() -> ptrace::Options {
+    ptrace::Options::PTRACE_O_TRACECLONE
+        | ptrace::Options::PTRACE_O_TRACEFORK
+        | ptrace::Options::PTRACE_O_TRACEVFORK
+        | ptrace::Options::PTRACE_O_TRACEEXEC
+        | ptrace::Options::PTRACE_O_TRACESYSGOOD
+}
+
+/// Sends signal to thread tid of process pid.
+fn tgkill(pid: Pid, tid: Pid, signal: signal::Signal) -> Result<(), nix::Error> {
+    let ret = unsafe {
+        libc::syscall(libc::SYS_tgkill, pid.as_raw(), tid.as_raw(), signal as libc::c_int)
+    };
+    Errno::result(ret).map(drop)
 }
 
 fn align_addr_to_word(addr: usize) -> usize {
     addr & (-(size_of::<usize>() as isize) as usize )
 }
 
+/// Returns the offset of debug register DR<index> within struct user, which is what
+/// PTRACE_PEEKUSER and PTRACE_POKEUSER take as an address.
+fn debug_register_offset(index: usize) -> usize {
+    let user: libc::user = unsafe { std::mem::zeroed() };
+    let base = &user as *const libc::user as usize;
+    let debug_registers = &user.u_debugreg as *const _ as usize;
+    debug_registers - base + index * size_of::<u64>()
+}
+
+/// Reads debug register DR<index> of thread tid.
+fn peek_debug_register(tid: Pid, index: usize) -> Result<usize, nix::Error> {
+    let value = unsafe {
+        Errno::clear();
+        libc::ptrace(
+            libc::PTRACE_PEEKUSER,
+            tid.as_raw(),
+            debug_register_offset(index) as *mut libc::c_void,
+            std::ptr::null_mut::<libc::c_void>(),
+        )
+    };
+    if value == -1 && Errno::last() != Errno::UnknownErrno {
+        return Err(nix::Error::Sys(Errno::last()));
+    }
+    Ok(value as usize)
+}
+
+/// Writes debug register DR<index> of thread tid.
+fn poke_debug_register(tid: Pid, index: usize, value: usize) -> Result<(), nix::Error> {
+    Errno::result(unsafe {
+        libc::ptrace(
+            libc::PTRACE_POKEUSER,
+            tid.as_raw(),
+            debug_register_offset(index) as *mut libc::c_void,
+            value as *mut libc::c_void,
+        )
+    })?;
+    Ok(())
+}
+
+/// Reads the floating point and SSE registers of thread tid.
+fn get_fpregs(tid: Pid) -> Result<libc::user_fpregs_struct, nix::Error> {
+    let mut fpregs: libc::user_fpregs_struct = unsafe { std::mem::zeroed() };
+    Errno::result(unsafe {
+        libc::ptrace(
+            libc::PTRACE_GETFPREGS,
+            tid.as_raw(),
+            std::ptr::null_mut::<libc::c_void>(),
+            &mut fpregs as *mut libc::user_fpregs_struct,
+        )
+    })?;
+    Ok(fpregs)
+}
+
+/// Writes the floating point and SSE registers of thread tid.
+fn set_fpregs(tid: Pid, fpregs: &libc::user_fpregs_struct) -> Result<(), nix::Error> {
+    Errno::result(unsafe {
+        libc::ptrace(
+            libc::PTRACE_SETFPREGS,
+            tid.as_raw(),
+            std::ptr::null_mut::<libc::c_void>(),
+            fpregs as *const libc::user_fpregs_struct,
+        )
+    })?;
+    Ok(())
+}
+
 impl Inferior {
+    /// Returns an inferior for the stopped process pid, which has just its main thread so far.
+    fn traced(pid: Pid, attached: bool) -> Inferior {
+        Inferior {
+            pid,
+            attached,
+            signals: SignalTable::new(),
+            fork_policy: ForkPolicy {
+                follow_child: false,
+                detach: true,
+            },
+            held: Vec::new(),
+            unclaimed_stops: Vec::new(),
+            threads: vec![Thread::new(pid, 1)],
+            next_thread_number: 2,
+            current: pid,
+            event_thread: pid,
+            reported_thread: pid,
+            debug_registers: [0; 8],
+            caught_syscalls: None,
+        }
+    }
+
     /// Attempts to start a new inferior process. Returns Some(Inferior) if successful, or None if
     /// an error is encountered.
-    pub fn new(target: &str, args: &Vec<String>) -> Option<Inferior> {
-        // TODO: implement me!
-        println!(
-            "Inferior::new not implemented! target={}, args={:?}",
-            target, args
-        );
+    pub fn new(target: &str, args: &[String]) -> Option<Inferior> {
         let mut cmd =Command::new(target);
         cmd.args(args);
+        if events::is_json() {
+            // deet's stdin and stdout carry JSON, so the inferior can't share them
+            cmd.stdin(Stdio::null()).stdout(Stdio::piped());
+        }
         unsafe {
             cmd.pre_exec(child_traceme);
         }
         match cmd.spawn() {
-            Ok(child) => {
-                let mut inferior = Inferior { child };
+            Ok(mut child) => {
+                if let Some(stdout) = child.stdout.take() {
+                    thread::spawn(move || {
+                        for line in BufReader::new(stdout).lines() {
+                            match line {
+                                Ok(line) => events::emit(Event::TargetOutput(line)),
+                                Err(_) => break,
+                            }
+                        }
+                    });
+                }
+                let inferior = Inferior::traced(Pid::from_raw(child.id() as i32), false);
                 match waitpid(inferior.pid(), None) {
                     Ok(_) => {},
                     Err(_) => {},
                 }
+                // follow the threads and processes the program creates
+                ptrace::setoptions(inferior.pid(), trace_options()).ok()?;
                 Some(inferior)
             }
             Err(_) => None,
         }
     }
 
+    /// Attaches to the running process pid and each of its threads, and waits for them to stop.
+    /// Returns the inferior along with the status the main thread stopped in.
+    pub fn attach(pid: Pid) -> Result<(Inferior, Status), nix::Error> {
+        ptrace::attach(pid)?;
+        let mut inferior = Inferior::traced(pid, true);
+        let status = inferior.wait(None)?;
+        ptrace::setoptions(pid, trace_options())?;
+        let tids: Vec<i32> = fs::read_dir(format!("/proc/{}/task", pid))
+            .map(|tasks| {
+                tasks
+                    .filter_map(|task| task.ok()?.file_name().to_str()?.parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        for tid in tids.into_iter().map(Pid::from_raw).filter(|tid| *tid != pid) {
+            // the thread may have exited in the meantime
+            if ptrace::attach(tid).is_ok() {
+                waitpid(tid, Some(WaitPidFlag::__WALL))?;
+                ptrace::setoptions(tid, trace_options())?;
+                inferior.add_thread(tid);
+            }
+        }
+        if unistd::getpgid(Some(pid)) != Ok(unistd::getpgrp()) {
+            INTERRUPT_PID.store(pid.as_raw(), Ordering::SeqCst);
+        }
+        Ok((inferior, status))
+    }
+
+    /// Lets the inferior run on untraced. Any breakpoints and watchpoints must have been removed
+    /// beforehand.
+    pub fn detach(self) -> Result<(), nix::Error> {
+        for thread in &self.threads {
+            ptrace::detach(thread.tid, None)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether this inferior was attached to rather than spawned by deet.
+    pub fn is_attached(&self) -> bool {
+        self.attached
+    }
+
+    /// Replaces the policies for signals the inferior receives.
+    pub fn set_signals(&mut self, signals: &SignalTable) {
+        self.signals = signals.clone();
+    }
+
+    pub fn set_fork_policy(&mut self, fork_policy: ForkPolicy) {
+        self.fork_policy = fork_policy;
+    }
+
+    /// Sets the syscalls the inferior stops at, all of them if the list is empty, or None to stop
+    /// at none.
+    pub fn set_caught_syscalls(&mut self, syscalls: Option<Vec<usize>>) {
+        self.caught_syscalls = syscalls;
+    }
+
+    /// Returns the processes forked off the inferior since the last call that deet is keeping
+    /// stopped.
+    pub fn take_held(&mut self) -> Vec<Inferior> {
+        std::mem::take(&mut self.held)
+    }
+
+    /// Sets the signal delivered to the current thread when it next resumes, or clears it.
+    pub fn set_pending_signal(&mut self, signal: Option<signal::Signal>) {
+        let tid = self.tid();
+        self.thread_mut(tid).pending_signal = signal;
+    }
+
     /// Returns the pid of this inferior.
     pub fn pid(&self) -> Pid {
-        nix::unistd::Pid::from_raw(self.child.id() as i32)
+        self.pid
+    }
+
+    /// Returns the id of the current thread.
+    pub fn tid(&self) -> Pid {
+        self.current
+    }
+
+    /// Returns the number and id of each thread.
+    pub fn threads(&self) -> Vec<(usize, Pid)> {
+        self.threads.iter().map(|thread| (thread.number, thread.tid)).collect()
+    }
+
+    /// Returns the number of the current thread.
+    pub fn thread_number(&self) -> usize {
+        self.threads.iter().find(|thread| thread.tid == self.current).unwrap().number
+    }
+
+    /// Makes the thread with the given number current, returning its id, or None if there is no
+    /// such thread.
+    pub fn select_thread(&mut self, number: usize) -> Option<Pid> {
+        let tid = self.threads.iter().find(|thread| thread.number == number)?.tid;
+        self.current = tid;
+        self.reported_thread = tid;
+        Some(tid)
+    }
+
+    /// Returns the number and id of the current thread if the user hasn't been told about it
+    /// since it became current because it stopped.
+    pub fn take_thread_switch(&mut self) -> Option<(usize, Pid)> {
+        if self.current == self.reported_thread {
+            return None;
+        }
+        self.reported_thread = self.current;
+        Some((self.thread_number(), self.current))
+    }
+
+    fn thread_mut(&mut self, tid: Pid) -> &mut Thread {
+        self.threads.iter_mut().find(|thread| thread.tid == tid).unwrap()
+    }
+
+    fn has_thread(&self, tid: Pid) -> bool {
+        self.threads.iter().any(|thread| thread.tid == tid)
+    }
+
+    /// Starts keeping track of a thread, which must be stopped.
+    fn add_thread(&mut self, tid: Pid) -> &mut Thread {
+        let number = self.next_thread_number;
+        self.next_thread_number += 1;
+        self.threads.push(Thread::new(tid, number));
+        output!("[New thread {} (LWP {})]", number, tid);
+        self.threads.last_mut().unwrap()
+    }
+
+    /// Starts keeping track of a thread that has just been created and will stop by itself.
+    fn add_new_thread(&mut self, tid: Pid) {
+        let thread = self.add_thread(tid);
+        thread.running = true;
+        thread.expected_stops = 1;
+        thread.new = true;
+    }
+
+    fn remove_thread(&mut self, tid: Pid) {
+        if let Some(index) = self.threads.iter().position(|thread| thread.tid == tid) {
+            let thread = self.threads.remove(index);
+            output!("[Thread {} (LWP {}) exited]", thread.number, tid);
+        }
+        if self.current == tid {
+            self.current = self.threads.first().map_or(self.pid, |thread| thread.tid);
+        }
     }
 
     /// Calls waitpid on this inferior and returns a Status to indicate the state of the process
@@ -81,98 +503,775 @@ impl Inferior {
             WaitStatus::Exited(_pid, exit_code) => Status::Exited(exit_code),
             WaitStatus::Signaled(_pid, signal, _core_dumped) => Status::Signaled(signal),
             WaitStatus::Stopped(_pid, signal) => {
-                let regs = ptrace::getregs(self.pid())?;
+                let regs = ptrace::getregs(self.tid())?;
                 Status::Stopped(signal, regs.rip as usize)
             }
             other => panic!("waitpid returned unexpected status: {:?}", other),
         })
     }
 
-    pub fn cont(&mut self) -> Result<Status, nix::Error> {
-        ptrace::cont(self.pid(), None);
-        self.wait(None)
+    /// Resumes the inferior and waits for it to stop. If step is set, only the current thread
+    /// runs, for a single instruction; otherwise all threads run. When a thread stops for a
+    /// reason the user should hear about, it becomes the current thread and the others are
+    /// stopped as well. A thread that hits one of our breakpoints is rewound to point at it,
+    /// unless it was stepping.
+    fn resume(
+        &mut self,
+        step: bool,
+        breakpoints: &HashMap<usize, Option<Breakpoint>>,
+    ) -> Result<Status, nix::Error> {
+        // a thread that stopped while the others were being stopped gets reported first
+        let current = self.current;
+        let pending = self
+            .threads
+            .iter_mut()
+            .filter(|thread| !step || thread.tid == current)
+            .find_map(|thread| Some((thread.tid, thread.pending_status.take()?)));
+        if let Some((tid, status)) = pending {
+            self.current = tid;
+            self.event_thread = tid;
+            return Ok(status);
+        }
+
+        if step {
+            self.resume_thread(self.current, true)?;
+        } else {
+            let stopped: Vec<Pid> = self
+                .threads
+                .iter()
+                .filter(|thread| !thread.running)
+                .map(|thread| thread.tid)
+                .collect();
+            for tid in stopped {
+                self.resume_thread(tid, false)?;
+            }
+        }
+        loop {
+            let event = waitpid(None, Some(WaitPidFlag::__WALL))?;
+            if let Some(status) = self.handle_event(event, step, breakpoints)? {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Resumes one thread, delivering its pending signal. Unless it is stepping, it stops at
+    /// syscalls while any are being caught.
+    fn resume_thread(&mut self, tid: Pid, step: bool) -> Result<(), nix::Error> {
+        let catching = self.caught_syscalls.is_some();
+        let thread = self.thread_mut(tid);
+        let signal = thread.pending_signal.take();
+        thread.running = true;
+        if step {
+            // a thread resumed any other way from a syscall entry doesn't stop at its exit
+            thread.in_syscall = false;
+            ptrace::step(tid, signal)
+        } else if catching {
+            ptrace::syscall(tid, signal)
+        } else {
+            thread.in_syscall = false;
+            ptrace::cont(tid, signal)
+        }
     }
 
-    pub fn kill(&mut self) -> Result<(), std::io::Error> {
-        println!("Killing running inferior (pid {})", self.pid());
-        self.child.kill()
+    /// Reads the syscall that thread tid has stopped at the entry or exit of. Returns it along
+    /// with the instruction pointer, and whether it is one of the syscalls being caught.
+    fn syscall_stop(&mut self, tid: Pid) -> Result<(SyscallStop, usize, bool), nix::Error> {
+        let thread = self.thread_mut(tid);
+        thread.running = false;
+        thread.in_syscall = !thread.in_syscall;
+        let entry = thread.in_syscall;
+        let regs = ptrace::getregs(tid)?;
+        let stop = SyscallStop {
+            number: regs.orig_rax as usize,
+            args: [
+                regs.rdi as usize,
+                regs.rsi as usize,
+                regs.rdx as usize,
+                regs.r10 as usize,
+                regs.r8 as usize,
+                regs.r9 as usize,
+            ],
+            result: if entry { None } else { Some(regs.rax as i64) },
+        };
+        let caught = match &self.caught_syscalls {
+            Some(syscalls) => syscalls.is_empty() || syscalls.contains(&stop.number),
+            None => false,
+        };
+        Ok((stop, regs.rip as usize, caught))
     }
 
-    pub fn print_backtrace(&self, debug_data: &DwarfData) -> Result<(), nix::Error> {
-        use std::convert::TryInto;
-        use gimli::DebugInfo;
-        let regs = ptrace::getregs(self.pid())?;
-        let mut instruction_ptr: usize = regs.rip.try_into().unwrap();
-        let mut base_ptr: usize = regs.rbp.try_into().unwrap();
-        loop {
-            let function = debug_data.get_function_from_addr(instruction_ptr).unwrap();
-            let line = debug_data.get_line_from_addr(instruction_ptr).unwrap();
-            println!("%rip register: {:#x}", instruction_ptr);
-            println!("{} ({}: {})", function, line.file, line.number); 
-            if function == "main" {
-                break;
-            }
-            instruction_ptr =
-                ptrace::read(self.pid(), (base_ptr + 8) as ptrace::AddressType)? as usize;
-            base_ptr = ptrace::read(self.pid(), base_ptr as ptrace::AddressType)? as usize;
-        }
-        // let mut frame_bottom: usize = regs.rsp.try_into().unwrap();
-        // loop {
-        //     let line = debug_data.get_line_from_addr(instruction_ptr).unwrap();
-        //     let function = debug_data.get_function_from_addr(instruction_ptr).unwrap();
-        //     println!("{} ({}: {})", function, line.file, line.number);
-        //     if function == "main" {
-        //         break;
-        //     }
-        //     let frame_top = DebugInfo::get_frame_start_address(instruction_ptr, frame_bottom);
-        //     instruction_ptr = ptrace::read(self.pid(), (frame_top - 8) as ptrace::AddressType)? as usize;
-        //     frame_bottom = frame_top;
-        // }
+    /// Deals with a change in the state of a thread while the inferior runs. Returns the status
+    /// to report if the inferior should stop.
+    fn handle_event(
+        &mut self,
+        event: WaitStatus,
+        step: bool,
+        breakpoints: &HashMap<usize, Option<Breakpoint>>,
+    ) -> Result<Option<Status>, nix::Error> {
+        match event {
+            WaitStatus::Exited(tid, exit_code) if tid == self.pid => {
+                Ok(Some(Status::Exited(exit_code)))
+            }
+            WaitStatus::Signaled(tid, signal, _) if tid == self.pid => {
+                Ok(Some(Status::Signaled(signal)))
+            }
+            WaitStatus::Exited(tid, _) | WaitStatus::Signaled(tid, _, _) => {
+                let stepped = step && tid == self.current;
+                self.remove_thread(tid);
+                if stepped {
+                    // nothing else is running, so stop in whichever thread is now current
+                    let rip = ptrace::getregs(self.tid())?.rip as usize;
+                    return Ok(Some(Status::Stopped(signal::Signal::SIGTRAP, rip)));
+                }
+                Ok(None)
+            }
+            WaitStatus::PtraceEvent(tid, _, event) => {
+                self.thread_mut(tid).running = false;
+                match event {
+                    libc::PTRACE_EVENT_CLONE => {
+                        let new_tid = Pid::from_raw(ptrace::getevent(tid)? as i32);
+                        if !self.has_thread(new_tid) {
+                            self.add_new_thread(new_tid);
+                        }
+                    }
+                    libc::PTRACE_EVENT_FORK | libc::PTRACE_EVENT_VFORK => {
+                        let child = self.forked_child(tid)?;
+                        if self.fork_policy.follow_child {
+                            self.follow_child(child, breakpoints)?;
+                            if step {
+                                let rip = ptrace::getregs(self.pid)?.rip as usize;
+                                return Ok(Some(Status::Stopped(signal::Signal::SIGTRAP, rip)));
+                            }
+                            self.resume_thread(self.pid, false)?;
+                            return Ok(None);
+                        }
+                        self.let_go(child, "child", breakpoints)?;
+                    }
+                    libc::PTRACE_EVENT_EXEC => {
+                        // only the thread that called exec is left, and it now has the pid. It
+                        // is still inside execve, whose exit stop is yet to come.
+                        let mut thread = Thread::new(self.pid, 1);
+                        thread.in_syscall = self.caught_syscalls.is_some();
+                        self.threads = vec![thread];
+                        self.next_thread_number = 2;
+                        self.current = self.pid;
+                        self.event_thread = self.pid;
+                        self.reported_thread = self.pid;
+                        self.debug_registers = [0; 8];
+                        let rip = ptrace::getregs(self.pid)?.rip as usize;
+                        return Ok(Some(Status::Exec(rip)));
+                    }
+                    _ => {}
+                }
+                self.resume_thread(tid, step)?;
+                Ok(None)
+            }
+            WaitStatus::PtraceSyscall(tid) => {
+                let (stop, rip, caught) = self.syscall_stop(tid)?;
+                if !caught {
+                    self.resume_thread(tid, step)?;
+                    return Ok(None);
+                }
+                self.current = tid;
+                self.event_thread = tid;
+                self.stop_all(breakpoints)?;
+                Ok(Some(Status::Syscall(stop, rip)))
+            }
+            WaitStatus::Stopped(tid, _) if !self.has_thread(tid) && !self.owns_thread(tid) => {
+                // a forked child that stopped before the fork was reported
+                self.unclaimed_stops.push(tid);
+                Ok(None)
+            }
+            WaitStatus::Stopped(tid, signal::Signal::SIGSTOP)
+                if !self.has_thread(tid) || self.thread_mut(tid).expected_stops > 0 =>
+            {
+                // a new thread may stop before its creation is reported
+                if !self.has_thread(tid) {
+                    self.add_new_thread(tid);
+                }
+                self.expected_stop(tid)?;
+                if !step {
+                    self.resume_thread(tid, false)?;
+                }
+                Ok(None)
+            }
+            WaitStatus::Stopped(tid, signal) => {
+                if !self.has_thread(tid) {
+                    self.add_thread(tid);
+                }
+                self.thread_mut(tid).running = false;
+                let mut regs = ptrace::getregs(tid)?;
+                if signal == signal::Signal::SIGTRAP {
+                    // if we hit one of our breakpoints, rewind %rip so it points at the breakpoint
+                    let addr = (regs.rip - 1) as usize;
+                    if !step && matches!(breakpoints.get(&addr), Some(Some(_))) {
+                        regs.rip = addr as u64;
+                        ptrace::setregs(tid, regs)?;
+                    }
+                } else if !self.filter_signal(tid, signal) {
+                    self.resume_thread(tid, step)?;
+                    return Ok(None);
+                }
+                self.current = tid;
+                self.event_thread = tid;
+                self.stop_all(breakpoints)?;
+                Ok(Some(Status::Stopped(signal, regs.rip as usize)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns whether tid is a thread of the inferior, even if deet doesn't know about it yet.
+    fn owns_thread(&self, tid: Pid) -> bool {
+        Path::new(&format!("/proc/{}/task/{}", self.pid, tid)).exists()
+    }
+
+    /// Returns an inferior for the process that thread tid has just forked off, once it has
+    /// stopped for the first time.
+    fn forked_child(&mut self, tid: Pid) -> Result<Inferior, nix::Error> {
+        let child = Pid::from_raw(ptrace::getevent(tid)? as i32);
+        match self.unclaimed_stops.iter().position(|pid| *pid == child) {
+            Some(index) => {
+                self.unclaimed_stops.remove(index);
+            }
+            None => {
+                waitpid(child, Some(WaitPidFlag::__WALL))?;
+            }
+        }
+        let mut inferior = Inferior::traced(child, self.attached);
+        inferior.signals = self.signals.clone();
+        inferior.fork_policy = self.fork_policy;
+        Ok(inferior)
+    }
+
+    /// Switches over to debugging a forked child, letting go of the parent once all its threads
+    /// are stopped. Watchpoints carry over to the child.
+    fn follow_child(
+        &mut self,
+        mut child: Inferior,
+        breakpoints: &HashMap<usize, Option<Breakpoint>>,
+    ) -> Result<(), nix::Error> {
+        output!("[Attaching after process {} fork to child process {}]", self.pid, child.pid);
+        self.stop_all(breakpoints)?;
+        child.held = self.take_held();
+        child.unclaimed_stops = std::mem::take(&mut self.unclaimed_stops);
+        for index in &[0, 1, 2, 3, 7] {
+            child.write_debug_register(*index, self.debug_registers[*index])?;
+        }
+        std::mem::swap(self, &mut child);
+        self.let_go(child, "parent", breakpoints)
+    }
+
+    /// Takes our breakpoints and watchpoints out of a process on the other side of a fork from
+    /// the one being followed, and then either detaches from it or holds on to it.
+    fn let_go(
+        &mut self,
+        mut other: Inferior,
+        relation: &str,
+        breakpoints: &HashMap<usize, Option<Breakpoint>>,
+    ) -> Result<(), nix::Error> {
+        for (addr, breakpoint) in breakpoints {
+            if let Some(bp) = breakpoint {
+                other.write_byte(*addr, bp.orig_byte)?;
+            }
+        }
+        other.write_debug_register(7, 0)?;
+        if self.fork_policy.detach {
+            output!("[Detaching after fork from {} process {}]", relation, other.pid);
+            other.detach()
+        } else {
+            self.held.push(other);
+            Ok(())
+        }
+    }
+
+    /// Notes that a thread has reported a SIGSTOP that deet expected. A new thread gets a copy of
+    /// the debug registers so that watchpoints apply to it too.
+    fn expected_stop(&mut self, tid: Pid) -> Result<(), nix::Error> {
+        let thread = self.thread_mut(tid);
+        thread.expected_stops -= 1;
+        thread.running = false;
+        if thread.new {
+            thread.new = false;
+            for index in &[0, 1, 2, 3, 7] {
+                poke_debug_register(tid, *index, self.debug_registers[*index])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decides what to do about a signal that stopped a thread, according to the policy for it.
+    /// Makes the signal pending if it is to be passed on, and returns whether the inferior should
+    /// stop.
+    fn filter_signal(&mut self, tid: Pid, signal: signal::Signal) -> bool {
+        let policy = self.signals.get(signal);
+        if policy.print && !policy.stop {
+            let description = signals::describe(signal);
+            output!("Program received signal {}, {}.", signal.as_ref(), description);
+        }
+        // a process stopped by SIGSTOP and friends has no signal to be delivered, which
+        // PTRACE_GETSIGINFO tells apart with EINVAL
+        let group_stop = matches!(ptrace::getsiginfo(tid), Err(nix::Error::Sys(Errno::EINVAL)));
+        if policy.pass && !group_stop {
+            self.thread_mut(tid).pending_signal = Some(signal);
+        }
+        policy.stop
+    }
+
+    /// Stops every thread that is still running, one at a time, with a SIGSTOP. A thread that
+    /// hits a breakpoint in the meantime is rewound to hit it again when it resumes, while other
+    /// reasons for stopping are kept to be reported later.
+    fn stop_all(
+        &mut self,
+        breakpoints: &HashMap<usize, Option<Breakpoint>>,
+    ) -> Result<(), nix::Error> {
+        while let Some(thread) = self.threads.iter_mut().find(|thread| thread.running) {
+            let tid = thread.tid;
+            if thread.expected_stops == 0 {
+                tgkill(self.pid, tid, signal::Signal::SIGSTOP)?;
+                thread.expected_stops += 1;
+            }
+            match waitpid(tid, Some(WaitPidFlag::__WALL))? {
+                WaitStatus::Stopped(_, signal::Signal::SIGSTOP) => self.expected_stop(tid)?,
+                WaitStatus::Stopped(_, signal) => {
+                    self.thread_mut(tid).running = false;
+                    let mut regs = ptrace::getregs(tid)?;
+                    let addr = (regs.rip - 1) as usize;
+                    let status = if signal == signal::Signal::SIGTRAP {
+                        if matches!(breakpoints.get(&addr), Some(Some(_))) {
+                            regs.rip = addr as u64;
+                            ptrace::setregs(tid, regs)?;
+                            None
+                        } else {
+                            Some(Status::Stopped(signal, regs.rip as usize))
+                        }
+                    } else if self.filter_signal(tid, signal) {
+                        Some(Status::Stopped(signal, regs.rip as usize))
+                    } else {
+                        None
+                    };
+                    self.thread_mut(tid).pending_status = status;
+                }
+                WaitStatus::PtraceEvent(_, _, event) => {
+                    self.thread_mut(tid).running = false;
+                    if event == libc::PTRACE_EVENT_CLONE {
+                        let new_tid = Pid::from_raw(ptrace::getevent(tid)? as i32);
+                        if !self.has_thread(new_tid) {
+                            self.add_new_thread(new_tid);
+                        }
+                    } else if event == libc::PTRACE_EVENT_FORK || event == libc::PTRACE_EVENT_VFORK
+                    {
+                        // there's no switching to the child partway through stopping the parent
+                        let child = self.forked_child(tid)?;
+                        self.let_go(child, "child", breakpoints)?;
+                    }
+                }
+                WaitStatus::PtraceSyscall(_) => {
+                    let (stop, rip, caught) = self.syscall_stop(tid)?;
+                    if caught {
+                        self.thread_mut(tid).pending_status = Some(Status::Syscall(stop, rip));
+                    }
+                }
+                WaitStatus::Exited(_, _) | WaitStatus::Signaled(_, _, _) => self.remove_thread(tid),
+                _ => {}
+            }
+        }
         Ok(())
     }
 
+    pub fn kill(&mut self) -> Result<(), nix::Error> {
+        output!("Killing running inferior (pid {})", self.pid());
+        signal::kill(self.pid(), signal::Signal::SIGKILL)?;
+        // reap every thread so that none of them linger as zombies
+        loop {
+            match waitpid(None, Some(WaitPidFlag::__WALL))? {
+                WaitStatus::Exited(pid, _) | WaitStatus::Signaled(pid, _, _) if pid == self.pid => {
+                    return Ok(())
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Writes val at addr, returning the byte that was there before.
     pub fn write_byte(&mut self, addr: usize, val: u8) -> Result<u8, nix::Error> {
-        let aligned_addr = align_addr_to_word(addr);
-        let byte_offset = addr - aligned_addr;
-        let word = ptrace::read(self.pid(), aligned_addr as ptrace::AddressType)? as usize;
-        let orig_byte = (word >> 8 * byte_offset) & 0xff;
-        let masked_word = word & !(0xff << 8 * byte_offset);
-        let updated_word = masked_word | ((val as usize) << 8 * byte_offset);
-        ptrace::write(self.pid(), aligned_addr as ptrace::AddressType, updated_word as *mut std::ffi::c_void)?;
-        Ok(orig_byte as u8) 
+        let orig_byte = self.read_bytes(addr, 1)?[0];
+        self.write_bytes(addr, &[val])?;
+        Ok(orig_byte)
+    }
+
+    /// Writes bytes into inferior memory starting at addr.
+    pub fn write_bytes(&mut self, addr: usize, bytes: &[u8]) -> Result<(), nix::Error> {
+        let mut word_addr = align_addr_to_word(addr);
+        while word_addr < addr + bytes.len() {
+            let mut word = self.read_word(word_addr)?.to_le_bytes();
+            for (i, byte) in word.iter_mut().enumerate() {
+                if word_addr + i >= addr && word_addr + i < addr + bytes.len() {
+                    *byte = bytes[word_addr + i - addr];
+                }
+            }
+            let updated_word = usize::from_le_bytes(word);
+            ptrace::write(self.tid(), word_addr as ptrace::AddressType, updated_word as *mut std::ffi::c_void)?;
+            word_addr += size_of::<usize>();
+        }
+        Ok(())
+    }
+
+    /// Reads the word of inferior memory at addr.
+    pub fn read_word(&self, addr: usize) -> Result<usize, nix::Error> {
+        Ok(ptrace::read(self.tid(), addr as ptrace::AddressType)? as usize)
+    }
+
+    /// Reads len bytes of inferior memory starting at addr.
+    pub fn read_bytes(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error> {
+        let mut bytes = Vec::with_capacity(len);
+        let mut word_addr = align_addr_to_word(addr);
+        while word_addr < addr + len {
+            let word = self.read_word(word_addr)?;
+            for (i, byte) in word.to_le_bytes().iter().enumerate() {
+                if word_addr + i >= addr && word_addr + i < addr + len {
+                    bytes.push(*byte);
+                }
+            }
+            word_addr += size_of::<usize>();
+        }
+        Ok(bytes)
+    }
+
+    /// Reads debug register DR<index> of the current thread.
+    pub fn read_debug_register(&self, index: usize) -> Result<usize, nix::Error> {
+        peek_debug_register(self.tid(), index)
+    }
+
+    /// Writes debug register DR<index> of every thread.
+    pub fn write_debug_register(&mut self, index: usize, value: usize) -> Result<(), nix::Error> {
+        for thread in &self.threads {
+            poke_debug_register(thread.tid, index, value)?;
+        }
+        self.debug_registers[index] = value;
+        Ok(())
+    }
+
+    /// Programs debug register slot (0-3) to trap after len bytes at addr are written, or also
+    /// read if read is true. addr must be aligned to len, which must be 1, 2, 4 or 8.
+    pub fn set_hardware_watchpoint(
+        &mut self,
+        slot: usize,
+        addr: usize,
+        len: usize,
+        read: bool,
+    ) -> Result<(), nix::Error> {
+        self.write_debug_register(slot, addr)?;
+        let access_bits = if read { 0b11 } else { 0b01 };
+        let len_bits = match len {
+            1 => 0b00,
+            2 => 0b01,
+            8 => 0b10,
+            _ => 0b11,
+        };
+        let mut dr7 = self.read_debug_register(7)?;
+        dr7 &= !(0b1111 << (16 + 4 * slot));
+        dr7 |= (1 << (2 * slot)) | (access_bits << (16 + 4 * slot)) | (len_bits << (18 + 4 * slot));
+        self.write_debug_register(7, dr7)
+    }
+
+    /// Disables the watchpoint in debug register slot (0-3).
+    pub fn clear_hardware_watchpoint(&mut self, slot: usize) -> Result<(), nix::Error> {
+        let dr7 = self.read_debug_register(7)?;
+        self.write_debug_register(7, dr7 & !(0b11 << (2 * slot)))?;
+        self.write_debug_register(slot, 0)
+    }
+
+    /// Returns the debug register slots whose watchpoints have triggered since the last call to
+    /// clear_watchpoint_hits.
+    pub fn watchpoint_hits(&self) -> Result<Vec<usize>, nix::Error> {
+        let dr6 = self.read_debug_register(6)?;
+        Ok((0..4).filter(|slot| dr6 & (1 << slot) != 0).collect())
+    }
+
+    pub fn clear_watchpoint_hits(&mut self) -> Result<(), nix::Error> {
+        self.write_debug_register(6, 0)
+    }
+
+    /// Executes a single instruction in the current thread while the other threads stay stopped.
+    /// If the thread is sitting on one of our breakpoints, the original instruction is executed
+    /// and the 0xcc byte is put back afterwards.
+    pub fn step_instruction(
+        &mut self,
+        breakpoints: &HashMap<usize, Option<Breakpoint>>,
+    ) -> Result<Status, nix::Error> {
+        let rip = ptrace::getregs(self.tid())?.rip as usize;
+        if let Some(Some(bp)) = breakpoints.get(&rip) {
+            self.write_byte(rip, bp.orig_byte)?;
+            let status = self.resume(true, breakpoints)?;
+            if let Status::Stopped(_, _) = status {
+                self.write_byte(rip, 0xcc)?;
+            }
+            Ok(status)
+        } else {
+            self.resume(true, breakpoints)
+        }
     }
 
     pub fn continue_exec(
         &mut self,
         breakpoints: &HashMap<usize, Option<Breakpoint>>,
     ) -> Result<Status, nix::Error> {
-        let mut regs = ptrace::getregs(self.pid())?;
-        let rip: usize = regs.rip.try_into().unwrap(); // rip as usize
-                                                       // check if inferior stopped at a breakpoint
-        if let Some(breakpoint) = breakpoints.get(&(rip - 1)) {
-            if let Some(bp) = breakpoint {
-                let orig_byte = bp.orig_byte;
-                println!("[inferior.continue_exec] Stopped at a breakpoint");
-                // restore the first byte of the instruction we replaced
-                self.write_byte(rip - 1, orig_byte).unwrap();
-                // set %rip = %rip - 1 to rewind the instruction pointer
-                regs.rip = (rip - 1) as u64;
-                ptrace::setregs(self.pid(), regs).unwrap();
-                // go to the next instruction
-                ptrace::step(self.pid(), None).unwrap();
-                // wait for inferior to stop due to SIGTRAP, just return if the inferior terminates here
-                match self.wait(None).unwrap() {
-                    Status::Exited(exit_code) => return Ok(Status::Exited(exit_code)),
-                    Status::Signaled(signal) => return Ok(Status::Signaled(signal)),
-                    Status::Stopped(_, _) => {
-                        // restore 0xcc in the breakpoint location
-                        self.write_byte(rip - 1, 0xcc).unwrap();
+        // if the current thread, or the one that last hit a breakpoint, is stopped at a
+        // breakpoint, execute the original instruction first. The other threads stay stopped
+        // meanwhile, so none of them can run past the breakpoint while it is removed.
+        let selected = self.current;
+        let mut stepping = vec![self.event_thread, selected];
+        stepping.dedup();
+        for tid in stepping {
+            if !self.has_thread(tid) || self.thread_mut(tid).pending_status.is_some() {
+                continue;
+            }
+            let rip = ptrace::getregs(tid)?.rip as usize;
+            if let Some(Some(_)) = breakpoints.get(&rip) {
+                self.current = tid;
+                match self.step_instruction(breakpoints)? {
+                    Status::Stopped(signal::Signal::SIGTRAP, _) => self.current = selected,
+                    other => return Ok(other),
+                }
+            }
+        }
+
+        // Restart the stopped threads and wait for one of them to stop or the process to end
+        self.resume(false, breakpoints)
+    }
+
+    /// Continues the inferior until the frame whose stack pointer will be frame_rsp after
+    /// returning reaches ret_addr, using a temporary breakpoint. Stops early if a user breakpoint
+    /// is hit or the inferior terminates.
+    pub fn run_to_return(
+        &mut self,
+        ret_addr: usize,
+        frame_rsp: usize,
+        breakpoints: &HashMap<usize, Option<Breakpoint>>,
+    ) -> Result<Status, nix::Error> {
+        let mut temp_breakpoints = breakpoints.clone();
+        let installed_here = !matches!(breakpoints.get(&ret_addr), Some(Some(_)));
+        if installed_here {
+            let orig_byte = self.write_byte(ret_addr, 0xcc)?;
+            temp_breakpoints.insert(ret_addr, Some(Breakpoint { addr: ret_addr, orig_byte }));
+        }
+        let tid = self.tid();
+        let status = loop {
+            let status = self.continue_exec(&temp_breakpoints)?;
+            match status {
+                Status::Stopped(signal::Signal::SIGTRAP, rip) if rip == ret_addr => {
+                    // a recursive call, or another thread, may return to the same address from a
+                    // different frame
+                    let rsp = ptrace::getregs(self.tid())?.rsp as usize;
+                    if (rsp >= frame_rsp && self.tid() == tid) || breakpoints.contains_key(&rip) {
+                        break status;
                     }
                 }
+                _ => break status,
+            }
+        };
+        // the breakpoint has to go wherever the process is still around, but an exec has
+        // replaced the memory it was in
+        if installed_here && matches!(status, Status::Stopped(..) | Status::Syscall(..)) {
+            if let Some(Some(bp)) = temp_breakpoints.get(&ret_addr) {
+                self.write_byte(ret_addr, bp.orig_byte)?;
+            }
+        }
+        Ok(status)
+    }
+
+    /// Saves the registers of the current thread, and takes the signal it was going to be
+    /// resumed with, before calling a function in it.
+    pub fn save_registers(&mut self) -> Result<SavedRegisters, nix::Error> {
+        let tid = self.tid();
+        Ok(SavedRegisters {
+            tid,
+            regs: ptrace::getregs(tid)?,
+            fpregs: get_fpregs(tid)?,
+            pending_signal: self.thread_mut(tid).pending_signal.take(),
+        })
+    }
+
+    /// Puts back the registers saved before a function call, making their thread the current
+    /// one again. A signal the called function received is discarded.
+    pub fn restore_registers(&mut self, saved: &SavedRegisters) -> Result<(), nix::Error> {
+        self.current = saved.tid;
+        self.thread_mut(saved.tid).pending_signal = saved.pending_signal;
+        ptrace::setregs(saved.tid, saved.regs)?;
+        set_fpregs(saved.tid, &saved.fpregs)
+    }
+
+    /// Sets up the current thread to call the function at addr following the System V AMD64
+    /// calling convention: the first integer arguments go in %rdi, %rsi, %rdx, %rcx, %r8 and
+    /// %r9, the first floating point ones in %xmm0-7, and the rest on the stack, which is 16 byte
+    /// aligned at the call. The function returns to return_addr. Returns the value %rsp will
+    /// have once it has returned.
+    pub fn setup_call(
+        &mut self,
+        addr: usize,
+        args: &[Argument],
+        return_addr: usize,
+    ) -> Result<usize, nix::Error> {
+        let tid = self.tid();
+        let mut regs = ptrace::getregs(tid)?;
+        let mut fpregs = get_fpregs(tid)?;
+        let (mut integers, mut floats, mut stack) = (Vec::new(), Vec::new(), Vec::new());
+        for arg in args {
+            match *arg {
+                Argument::Integer(value) if integers.len() < ARGUMENT_REGISTERS => {
+                    integers.push(value)
+                }
+                Argument::Float(bits) if floats.len() < FLOAT_ARGUMENT_REGISTERS => {
+                    floats.push(bits)
+                }
+                Argument::Integer(value) | Argument::Float(value) => stack.push(value),
             }
         }
+        let mut registers = [
+            &mut regs.rdi,
+            &mut regs.rsi,
+            &mut regs.rdx,
+            &mut regs.rcx,
+            &mut regs.r8,
+            &mut regs.r9,
+        ];
+        for (register, value) in registers.iter_mut().zip(integers) {
+            **register = value;
+        }
+        for (i, bits) in floats.iter().enumerate() {
+            // each %xmm register is four 32-bit words
+            fpregs.xmm_space[i * 4] = *bits as u32;
+            fpregs.xmm_space[i * 4 + 1] = (*bits >> 32) as u32;
+            fpregs.xmm_space[i * 4 + 2] = 0;
+            fpregs.xmm_space[i * 4 + 3] = 0;
+        }
+
+        // leave the red zone of the interrupted function alone
+        let mut sp = (regs.rsp as usize - RED_ZONE) & !0xf;
+        sp -= stack.len() * size_of::<u64>();
+        sp &= !0xf;
+        for (i, value) in stack.iter().enumerate() {
+            self.write_bytes(sp + i * size_of::<u64>(), &value.to_le_bytes())?;
+        }
+        sp -= size_of::<usize>();
+        self.write_bytes(sp, &return_addr.to_le_bytes())?;
+
+        regs.rsp = sp as u64;
+        regs.rip = addr as u64;
+        // the number of vector registers used, for variadic functions
+        regs.rax = floats.len() as u64;
+        // keep the kernel from restarting a syscall the thread was stopped in
+        regs.orig_rax = u64::MAX;
+        ptrace::setregs(tid, regs)?;
+        set_fpregs(tid, &fpregs)?;
+        Ok(sp + size_of::<usize>())
+    }
+
+    /// Returns the %rax and the low 64 bits of %xmm0 of the current thread, which hold what a
+    /// function returned.
+    pub fn return_registers(&self) -> Result<(u64, u64), nix::Error> {
+        let rax = ptrace::getregs(self.tid())?.rax;
+        let xmm_space = get_fpregs(self.tid())?.xmm_space;
+        Ok((rax, xmm_space[0] as u64 | (xmm_space[1] as u64) << 32))
+    }
+
+    /// Starts stepping from the current source line, returning the state step_line keeps.
+    pub fn line_step(&self, debug_data: &DwarfData, step_into: bool) -> Result<LineStep, nix::Error> {
+        let rip = ptrace::getregs(self.tid())?.rip as usize;
+        Ok(LineStep {
+            start_line: debug_data.get_line_from_addr(rip).map(|line| (line.file, line.number)),
+            step_into,
+            returning_to: None,
+            entering: None,
+            finished: false,
+        })
+    }
+
+    /// Steps until the inferior reaches a different source line. If step.step_into is false,
+    /// function calls are run to completion; functions without debugging information are always
+    /// stepped over. Stops early at a breakpoint, and carries on from there when called again
+    /// with the same step.
+    pub fn step_line(
+        &mut self,
+        debug_data: &DwarfData,
+        breakpoints: &HashMap<usize, Option<Breakpoint>>,
+        step: &mut LineStep,
+    ) -> Result<Status, nix::Error> {
+        loop {
+            let status = if let Some((ret_addr, frame_rsp)) = step.returning_to {
+                let status = self.run_to_return(ret_addr, frame_rsp, breakpoints)?;
+                match status {
+                    Status::Stopped(signal::Signal::SIGTRAP, rip) if rip == ret_addr => {
+                        step.returning_to = None;
+                        status
+                    }
+                    other => return Ok(other),
+                }
+            } else if let Some(body_addr) = step.entering {
+                // skip the prologue so the new frame is set up when we stop
+                let status = self.step_instruction(breakpoints)?;
+                match status {
+                    Status::Stopped(signal::Signal::SIGTRAP, rip) if rip == body_addr => {
+                        step.entering = None;
+                        step.finished = true;
+                        return Ok(status);
+                    }
+                    Status::Stopped(signal::Signal::SIGTRAP, rip) if breakpoints.contains_key(&rip) => {
+                        return Ok(status);
+                    }
+                    Status::Stopped(signal::Signal::SIGTRAP, _) => continue,
+                    other => return Ok(other),
+                }
+            } else {
+                let regs = ptrace::getregs(self.tid())?;
+                let (prev_rip, prev_rsp) = (regs.rip as usize, regs.rsp as usize);
+                let status = self.step_instruction(breakpoints)?;
+                let rip = match status {
+                    Status::Stopped(signal::Signal::SIGTRAP, rip) => rip,
+                    other => return Ok(other),
+                };
+                if !self.watchpoint_hits()?.is_empty() {
+                    return Ok(status);
+                }
+                let rsp = ptrace::getregs(self.tid())?.rsp as usize;
+
+                // a call instruction pushes a return address just past itself
+                let ret_addr = self.read_word(rsp)?;
+                if rsp == prev_rsp - 8 && ret_addr > prev_rip && ret_addr <= prev_rip + 15 {
+                    let line = debug_data.get_line_from_addr(rip);
+                    if !step.step_into || line.is_none() {
+                        step.returning_to = Some((ret_addr, prev_rsp));
+                        continue;
+                    }
+                    match debug_data.get_function_body_addr(rip) {
+                        Some(body_addr) if body_addr != rip => {
+                            step.entering = Some(body_addr);
+                            if breakpoints.contains_key(&rip) {
+                                return Ok(status);
+                            }
+                            continue;
+                        }
+                        _ => {
+                            step.finished = true;
+                            return Ok(status);
+                        }
+                    }
+                }
+                status
+            };
 
-        ptrace::cont(self.pid(), None)?; // Restart the stopped tracee process
-        self.wait(None) // wait for inferior to stop or terminate
+            let rip = ptrace::getregs(self.tid())?.rip as usize;
+            let line = debug_data
+                .get_line_from_addr(rip)
+                .map(|line| (line.file, line.number));
+            if line.is_some() && line != step.start_line {
+                step.finished = true;
+                return Ok(status);
+            }
+            if let Some(Some(_)) = breakpoints.get(&rip) {
+                return Ok(status);
+            }
+        }
     }
 }
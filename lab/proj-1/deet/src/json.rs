@@ -0,0 +1,352 @@
+use std::fmt;
+
+/// A JSON value, as exchanged with programs driving deet through --interpreter=json.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Keeps its members in order, so that objects are written out the way they were built.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Returns the member of an object with the given key.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// Parses a JSON document, which must hold exactly one value.
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser { chars: text.chars().collect(), pos: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(format!("Unexpected {} after the value", parser.chars[parser.pos]));
+        }
+        Ok(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(string: &str) -> Json {
+        Json::String(string.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(string: String) -> Json {
+        Json::String(string)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(number: usize) -> Json {
+        Json::Number(number as f64)
+    }
+}
+
+impl From<i32> for Json {
+    fn from(number: i32) -> Json {
+        Json::Number(number as f64)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(number: i64) -> Json {
+        Json::Number(number as f64)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+                write!(f, "{}", *number as i64)
+            }
+            Json::Number(number) => write!(f, "{}", number),
+            Json::String(string) => write_string(f, string),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in string.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(format!("Expected {} but found {}", expected, c)),
+            None => Err(format!("Expected {} but found the end", expected)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        match self.peek().ok_or("Expected a value but found the end")? {
+            '{' => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    members.push((key, self.value()?));
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                self.expect('}')?;
+                Ok(Json::Object(members))
+            }
+            '[' => {
+                self.pos += 1;
+                let mut values = Vec::new();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(']')?;
+                Ok(Json::Array(values))
+            }
+            '"' => Ok(Json::String(self.string()?)),
+            _ => self.literal(),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            let c = *self.chars.get(self.pos).ok_or("Unterminated string")?;
+            self.pos += 1;
+            match c {
+                '"' => return Ok(string),
+                '\\' => {
+                    let escape = *self.chars.get(self.pos).ok_or("Unterminated string")?;
+                    self.pos += 1;
+                    string.push(match escape {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        '"' | '\\' | '/' => escape,
+                        'u' => self.unicode_escape()?,
+                        c => return Err(format!("Invalid escape \\{}", c)),
+                    });
+                }
+                c => string.push(c),
+            }
+        }
+    }
+
+    /// Parses the hex digits of a \u escape. A character outside the Basic Multilingual Plane is
+    /// written as a surrogate pair, the high surrogate's escape followed by the low one's.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let mut code = self.hex_digits()?;
+        if (0xd800..0xdc00).contains(&code) {
+            let low = match self.chars.get(self.pos..self.pos + 2) {
+                Some(['\\', 'u']) => {
+                    self.pos += 2;
+                    self.hex_digits()?
+                }
+                _ => 0,
+            };
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(format!("Unpaired surrogate \\u{:04x}", code));
+            }
+            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+        }
+        std::char::from_u32(code).ok_or(format!("Unpaired surrogate \\u{:04x}", code))
+    }
+
+    fn hex_digits(&mut self) -> Result<u32, String> {
+        let hex: String = self.chars.iter().skip(self.pos).take(4).collect();
+        match u32::from_str_radix(&hex, 16) {
+            // from_str_radix also takes a sign
+            Ok(code) if hex.len() == 4 && !hex.starts_with('+') => {
+                self.pos += 4;
+                Ok(code)
+            }
+            _ => Err(format!("Invalid escape \\u{}", hex)),
+        }
+    }
+
+    /// Parses a number, true, false or null.
+    fn literal(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while self.pos < self.chars.len()
+            && (self.chars[self.pos].is_alphanumeric() || "+-.".contains(self.chars[self.pos]))
+        {
+            self.pos += 1;
+        }
+        let word: String = self.chars[start..self.pos].iter().collect();
+        match word.as_str() {
+            "true" => Ok(Json::Bool(true)),
+            "false" => Ok(Json::Bool(false)),
+            "null" => Ok(Json::Null),
+            // Rust also parses NaN, inf and a leading +, none of which are JSON
+            _ if word.starts_with(|c: char| c == '-' || c.is_ascii_digit())
+                && word.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c)) =>
+            {
+                word.parse().map(Json::Number).map_err(|_| format!("Invalid value {}", word))
+            }
+            _ => Err(format!("Invalid value {}", word)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_documents() {
+        let request = Json::parse(r#" {"command": "break", "args": ["main", 12, -1.5e2]} "#);
+        assert_eq!(
+            request,
+            Ok(Json::Object(vec![
+                ("command".to_string(), Json::from("break")),
+                (
+                    "args".to_string(),
+                    Json::Array(vec![Json::from("main"), Json::Number(12.0), Json::Number(-150.0)])
+                ),
+            ]))
+        );
+        let request = request.unwrap();
+        assert_eq!(request.get("command").and_then(Json::as_str), Some("break"));
+        assert_eq!(request.get("missing"), None);
+        assert_eq!(
+            Json::parse("[true,false,null,{},[]]").unwrap().to_string(),
+            "[true,false,null,{},[]]"
+        );
+    }
+
+    #[test]
+    fn parses_escapes() {
+        assert_eq!(Json::parse(r#""a\"b\\c\/d\n\t\u00e9""#), Ok(Json::from("a\"b\\c/d\n\t\u{e9}")));
+        assert!(Json::parse(r#""\uzzzz""#).is_err());
+        assert!(Json::parse(r#""\u+12a""#).is_err());
+        assert!(Json::parse(r#""\u12""#).is_err());
+        // characters outside the BMP come as surrogate pairs
+        assert_eq!(Json::parse(r#""\ud83d\ude00!""#), Ok(Json::from("\u{1f600}!")));
+        assert!(Json::parse(r#""\ud83d""#).is_err());
+        assert!(Json::parse(r#""\ud83dx""#).is_err());
+        assert!(Json::parse(r#""\ud83d\u0041""#).is_err());
+        assert!(Json::parse(r#""\ude00""#).is_err());
+        // only the escapes JSON has are accepted
+        assert!(Json::parse(r#""\x41""#).is_err());
+        assert!(Json::parse(r#""\'""#).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        assert!(Json::parse("").is_err());
+        assert!(Json::parse("[1, 2").is_err());
+        assert!(Json::parse(r#"{"a" 1}"#).is_err());
+        assert!(Json::parse(r#""unterminated"#).is_err());
+        assert!(Json::parse("1 2").is_err());
+        assert!(Json::parse("nul").is_err());
+        // numbers Rust would parse but JSON doesn't have
+        for number in ["NaN", "nan", "inf", "-inf", "infinity", "+1"].iter() {
+            assert!(Json::parse(number).is_err(), "{} was accepted", number);
+        }
+    }
+
+    #[test]
+    fn writes_escaped_strings_and_numbers() {
+        let string = Json::from("say \"hi\"\n\\\u{1}");
+        assert_eq!(string.to_string(), r#""say \"hi\"\n\\\u0001""#);
+        assert_eq!(Json::from(42usize).to_string(), "42");
+        assert_eq!(Json::from(-7i64).to_string(), "-7");
+        assert_eq!(Json::Number(0.5).to_string(), "0.5");
+        assert_eq!(Json::from(None::<i32>).to_string(), "null");
+        let object = Json::Object(vec![("b".to_string(), 1.into()), ("a".to_string(), 2.into())]);
+        assert_eq!(object.to_string(), r#"{"b":1,"a":2}"#);
+    }
+}
@@ -1,25 +1,114 @@
+#[macro_use]
+mod events;
+
+mod completer;
+mod core_file;
 mod debugger;
 mod debugger_command;
+mod disasm;
+mod expr;
+mod gdbserver;
 mod inferior;
+mod json;
 
 mod dwarf_data;
 mod gimli_wrapper;
+mod registers;
+mod signals;
+mod solib;
+mod syscalls;
+mod source;
+mod target;
+mod unwind;
+mod value;
 
 use crate::debugger::Debugger;
-use nix::sys::signal::{signal, SigHandler, Signal};
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use nix::unistd::Pid;
 use std::env;
+use std::fs;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: {} <target program>", args[0]);
-        std::process::exit(1);
+    let mut args: Vec<String> = env::args().collect();
+    // each -x <script> runs the commands in script once deet has started
+    let mut scripts = Vec::new();
+    while let Some(index) = args.iter().position(|arg| arg == "-x") {
+        if index + 1 >= args.len() {
+            break;
+        }
+        scripts.push(args.remove(index + 1));
+        args.remove(index);
+    }
+    // --interpreter=json lets another program drive deet with JSON requests and events
+    if let Some(index) = args.iter().position(|arg| arg == "--interpreter=json") {
+        args.remove(index);
+        events::set_json(true);
     }
-    let target = &args[1];
+    let server = args.len() >= 4 && args[1] == "--gdbserver";
+    let trace = args.len() >= 3 && args[1] == "--trace-syscalls";
+    let (pid, core) = match args.len() {
+        _ if server || trace => (None, None),
+        2 => (None, None),
+        3 if args[1] == "--pid" => match args[2].parse() {
+            Ok(pid) => (Some(Pid::from_raw(pid)), None),
+            Err(_) => {
+                println!("Invalid pid {}", args[2]);
+                std::process::exit(1);
+            }
+        },
+        4 if args[2] == "--core" => (None, Some(&args[3])),
+        _ => {
+            println!("Usage: {} <target program>", args[0]);
+            println!("       {} --pid <pid>", args[0]);
+            println!("       {} <target program> --core <core file>", args[0]);
+            println!("       {} --gdbserver [host]:<port> <target program> [args...]", args[0]);
+            println!("       {} --trace-syscalls <target program> [args...]", args[0]);
+            println!("Each -x <script> option runs the commands in script at startup.");
+            println!("With --interpreter=json, deet reads JSON requests and writes JSON events.");
+            std::process::exit(1);
+        }
+    };
+    let target = match pid {
+        // debug whatever program the process is running
+        Some(pid) => match fs::read_link(format!("/proc/{}/exe", pid)) {
+            Ok(exe) => exe.to_string_lossy().to_string(),
+            Err(err) => {
+                println!("Could not find the executable of process {}: {}", pid, err);
+                std::process::exit(1);
+            }
+        },
+        None if server => args[3].clone(),
+        None if trace => args[2].clone(),
+        None => args[1].clone(),
+    };
 
-    // Disable handling of ctrl+c in this process (so that ctrl+c only gets delivered to child
-    // processes)
-    unsafe { signal(Signal::SIGINT, SigHandler::SigIgn) }.expect("Error disabling SIGINT handling");
+    // Keep ctrl+c from killing this process, so that it only interrupts the inferior. Unlike an
+    // ignored signal, a handler isn't inherited by the child processes deet starts.
+    let action = SigAction::new(
+        SigHandler::Handler(inferior::interrupt),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+    unsafe { sigaction(Signal::SIGINT, &action) }.expect("Error setting up SIGINT handling");
+
+    if server {
+        gdbserver::serve(&args[2], &target, &args[4..]);
+        return;
+    }
 
-    Debugger::new(target).run();
+    let mut debugger = Debugger::new(&target);
+    if trace {
+        debugger.trace_syscalls(&args[3..]);
+        return;
+    }
+    for script in &scripts {
+        debugger.load_script(script);
+    }
+    if let Some(pid) = pid {
+        debugger.attach(pid);
+    }
+    if let Some(core) = core {
+        debugger.load_core(core);
+    }
+    debugger.run();
 }
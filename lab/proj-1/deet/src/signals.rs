@@ -0,0 +1,112 @@
+use nix::sys::signal::Signal;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ffi::CStr;
+use std::str::FromStr;
+
+/// What happens when the inferior receives a signal.
+#[derive(Clone, Copy)]
+pub struct SignalPolicy {
+    /// Whether the inferior stops and control returns to the user.
+    pub stop: bool,
+    /// Whether deet says that the signal arrived.
+    pub print: bool,
+    /// Whether the signal is delivered to the inferior when it resumes.
+    pub pass: bool,
+}
+
+/// Signals that programs routinely receive while running normally. They are passed on without
+/// stopping or saying anything.
+const QUIET_SIGNALS: [Signal; 8] = [
+    Signal::SIGALRM,
+    Signal::SIGURG,
+    Signal::SIGCHLD,
+    Signal::SIGWINCH,
+    Signal::SIGIO,
+    Signal::SIGVTALRM,
+    Signal::SIGPROF,
+    Signal::SIGPWR,
+];
+
+/// The policy for each signal, as changed by the handle command.
+#[derive(Clone)]
+pub struct SignalTable {
+    policies: HashMap<Signal, SignalPolicy>,
+}
+
+impl SignalTable {
+    /// Returns the default policies: every signal stops the inferior and is passed on, except
+    /// for the quiet ones, and SIGINT and SIGTRAP, which are deet's own business.
+    pub fn new() -> SignalTable {
+        let policies = Signal::iterator()
+            .map(|signal| {
+                let policy = if QUIET_SIGNALS.contains(&signal) {
+                    SignalPolicy { stop: false, print: false, pass: true }
+                } else {
+                    let pass = signal != Signal::SIGINT && signal != Signal::SIGTRAP;
+                    SignalPolicy { stop: true, print: true, pass }
+                };
+                (signal, policy)
+            })
+            .collect();
+        SignalTable { policies }
+    }
+
+    pub fn get(&self, signal: Signal) -> SignalPolicy {
+        self.policies[&signal]
+    }
+
+    /// Changes the policy for signal according to one of gdb's handle keywords. Stopping implies
+    /// printing, and not printing implies not stopping.
+    pub fn handle(&mut self, signal: Signal, keyword: &str) -> Result<(), String> {
+        let policy = self.policies.get_mut(&signal).unwrap();
+        match keyword {
+            "stop" => {
+                policy.stop = true;
+                policy.print = true;
+            }
+            "nostop" => policy.stop = false,
+            "print" => policy.print = true,
+            "noprint" => {
+                policy.print = false;
+                policy.stop = false;
+            }
+            "pass" | "noignore" => policy.pass = true,
+            "nopass" | "ignore" => policy.pass = false,
+            _ => return Err(format!("Unrecognized or ambiguous flag word: \"{}\".", keyword)),
+        }
+        Ok(())
+    }
+
+    /// Prints the header of the table print_policy adds rows to.
+    pub fn print_header(&self) {
+        output!("Signal        Stop\tPrint\tPass to program\tDescription");
+    }
+
+    pub fn print_policy(&self, signal: Signal) {
+        let policy = self.get(signal);
+        let yes_no = |flag| if flag { "Yes" } else { "No" };
+        output!(
+            "{:<14}{}\t{}\t{}\t\t{}",
+            signal.as_ref(),
+            yes_no(policy.stop),
+            yes_no(policy.print),
+            yes_no(policy.pass),
+            describe(signal)
+        );
+    }
+}
+
+/// Parses a signal given by name, like SIGUSR1, or by number.
+pub fn parse_signal(text: &str) -> Option<Signal> {
+    match text.parse::<i32>() {
+        Ok(number) => Signal::try_from(number).ok(),
+        Err(_) => Signal::from_str(&text.to_uppercase()).ok(),
+    }
+}
+
+/// Returns the usual description of a signal, e.g. "Segmentation fault" for SIGSEGV.
+pub fn describe(signal: Signal) -> String {
+    let description = unsafe { CStr::from_ptr(libc::strsignal(signal as libc::c_int)) };
+    description.to_string_lossy().to_string()
+}
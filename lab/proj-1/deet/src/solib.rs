@@ -0,0 +1,83 @@
+use crate::dwarf_data::DwarfData;
+use crate::target::Target;
+use crate::value;
+use std::fs;
+use std::path::Path;
+
+/// The .dynamic entry the dynamic linker fills in with the address of its r_debug structure.
+const DT_DEBUG: usize = 21;
+const DT_NULL: usize = 0;
+/// Offsets of r_debug.r_map and of the link_map fields deet uses.
+const R_MAP: usize = 8;
+const L_ADDR: usize = 0;
+const L_NAME: usize = 8;
+const L_NEXT: usize = 24;
+/// Gives up on library lists longer than this, which are most likely corrupted.
+const MAX_LIBRARIES: usize = 1024;
+/// The longest path a library's name can be.
+const PATH_MAX: usize = 4096;
+
+/// Returns how far from its link-time addresses the program is loaded: the address of its first
+/// mapping in the memory map, less the address it was linked at. Without a mapping for it, the
+/// entry point from the auxiliary vector is compared with the one in the file instead.
+pub fn program_bias(target: &dyn Target, path: &str, debug_data: &DwarfData) -> Option<usize> {
+    let path = fs::canonicalize(path).ok()?;
+    let mapped = target.memory_map().lines().find_map(|line| {
+        // start-end perms offset dev inode path
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 6 || Path::new(fields[5]) != path {
+            return None;
+        }
+        if usize::from_str_radix(fields[3], 16).ok()? != 0 {
+            return None;
+        }
+        usize::from_str_radix(fields[0].split('-').next()?, 16).ok()
+    });
+    match mapped {
+        Some(start) => Some(start.wrapping_sub(debug_data.link_base())),
+        None => Some(target.entry_point()?.wrapping_sub(debug_data.entry())),
+    }
+}
+
+/// Walks the dynamic linker's list of loaded shared libraries, which is found through the
+/// DT_DEBUG entry of the program's .dynamic section at the given runtime address. Returns the
+/// path of each library with the bias it was loaded at. The list is empty until the dynamic
+/// linker has run.
+pub fn loaded_libraries(target: &dyn Target, dynamic: usize) -> Vec<(String, usize)> {
+    let mut libraries = Vec::new();
+    let r_debug = match find_r_debug(target, dynamic) {
+        Some(r_debug) => r_debug,
+        None => return libraries,
+    };
+    let mut link_map = target.read_word(r_debug + R_MAP).unwrap_or(0);
+    while link_map != 0 && libraries.len() < MAX_LIBRARIES {
+        let bias = target.read_word(link_map + L_ADDR);
+        let name = target
+            .read_word(link_map + L_NAME)
+            .ok()
+            .and_then(|addr| value::read_string_bytes(target, addr, PATH_MAX))
+            .filter(|(_, truncated)| !truncated)
+            .map(|(bytes, _)| String::from_utf8_lossy(&bytes).to_string());
+        if let (Ok(bias), Some(name)) = (bias, name) {
+            // the program itself has no name, and the vDSO isn't a file
+            if !name.is_empty() && Path::new(&name).is_file() {
+                libraries.push((name, bias));
+            }
+        }
+        link_map = target.read_word(link_map + L_NEXT).unwrap_or(0);
+    }
+    libraries
+}
+
+/// Returns the address of the dynamic linker's r_debug structure, if it has been set up.
+fn find_r_debug(target: &dyn Target, dynamic: usize) -> Option<usize> {
+    // each entry is a tag followed by a value
+    for entry in (dynamic..).step_by(16).take(MAX_LIBRARIES) {
+        match target.read_word(entry).ok()? {
+            DT_NULL => return None,
+            DT_DEBUG => return Some(target.read_word(entry + 8).ok()?).filter(|addr| *addr != 0),
+            _ => {}
+        }
+    }
+    None
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Source files read for listing, by the path the debugging information records for them.
+pub struct SourceCache {
+    /// Directories to look for source files in before the recorded path, most recently added
+    /// first.
+    directories: Vec<String>,
+    /// The lines of each file, or None if it couldn't be found.
+    files: HashMap<String, Option<Vec<String>>>,
+}
+
+impl SourceCache {
+    pub fn new() -> SourceCache {
+        SourceCache {
+            directories: Vec::new(),
+            files: HashMap::new(),
+        }
+    }
+
+    pub fn directories(&self) -> &[String] {
+        &self.directories
+    }
+
+    /// Adds a directory to search for source files, e.g. a local checkout of a program that was
+    /// compiled somewhere else.
+    pub fn add_directory(&mut self, dir: &str) {
+        self.directories.retain(|existing| existing != dir);
+        self.directories.insert(0, dir.to_string());
+        // files may now be found somewhere else
+        self.files.clear();
+    }
+
+    pub fn clear_directories(&mut self) {
+        self.directories.clear();
+        self.files.clear();
+    }
+
+    /// Returns the lines of a source file, or None if it can't be found.
+    pub fn lines(&mut self, path: &str) -> Option<&[String]> {
+        if !self.files.contains_key(path) {
+            let lines = self.find(path).and_then(|found| fs::read(found).ok()).map(|bytes| {
+                String::from_utf8_lossy(&bytes).lines().map(|line| line.to_string()).collect()
+            });
+            self.files.insert(path.to_string(), lines);
+        }
+        self.files[path].as_deref()
+    }
+
+    /// Finds a file in the source directories, trying the whole recorded path under each of
+    /// them and then shorter and shorter trailing parts of it, down to the file name. Falls back
+    /// to the recorded path itself.
+    fn find(&self, path: &str) -> Option<PathBuf> {
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        for dir in &self.directories {
+            for start in 0..components.len() {
+                let candidate = Path::new(dir).join(components[start..].join("/"));
+                if candidate.is_file() {
+                    return Some(candidate);
+                }
+            }
+        }
+        Some(PathBuf::from(path)).filter(|path| path.is_file())
+    }
+}
@@ -0,0 +1,646 @@
+use crate::target::Target;
+use nix::errno::Errno;
+use std::mem::size_of;
+
+/// Number of bytes of a string or buffer argument that are shown, like strace does.
+const MAX_STRING_LEN: usize = 32;
+
+/// The inferior stopped at the entry or exit of a syscall.
+#[derive(Clone)]
+pub struct SyscallStop {
+    pub number: usize,
+    pub args: [usize; 6],
+    /// What the syscall returned, or None if it is being entered.
+    pub result: Option<i64>,
+}
+
+/// Returns the name of a syscall, if it is a known one.
+pub fn name(number: usize) -> Option<&'static str> {
+    SYSCALLS.iter().find(|(n, _, _)| *n == number).map(|(_, name, _)| *name)
+}
+
+/// Returns the number of a syscall given by name or number.
+pub fn parse(syscall: &str) -> Option<usize> {
+    match syscall.parse() {
+        Ok(number) => Some(number),
+        Err(_) => SYSCALLS.iter().find(|(_, name, _)| *name == syscall).map(|(n, _, _)| *n),
+    }
+}
+
+/// Returns the name of a syscall, or syscall_<number> for one deet doesn't know.
+pub fn describe(number: usize) -> String {
+    name(number).map_or(format!("syscall_{}", number), |name| name.to_string())
+}
+
+/// Formats a syscall as a call with its arguments decoded, like write(1, "hi\n", 3). Buffers
+/// the syscall fills in are only shown once it has returned.
+pub fn format_call(target: &dyn Target, stop: &SyscallStop) -> String {
+    let kinds: Vec<char> = match SYSCALLS.iter().find(|(n, _, _)| *n == stop.number) {
+        Some((_, _, kinds)) => kinds.chars().collect(),
+        // show every argument in hex for an unknown syscall
+        None => vec!['x'; 6],
+    };
+    let args: Vec<String> = kinds
+        .iter()
+        .enumerate()
+        .map(|(i, kind)| {
+            let arg = stop.args[i];
+            let len = stop.args.get(i + 1).cloned().unwrap_or(0);
+            match kind {
+                'd' => (arg as i32).to_string(),
+                'u' => arg.to_string(),
+                'o' if arg == 0 => "0".to_string(),
+                'o' => format!("0{:o}", arg),
+                'f' if arg as i32 == libc::AT_FDCWD => "AT_FDCWD".to_string(),
+                'f' => (arg as i32).to_string(),
+                's' => read_string(target, arg),
+                'b' => read_buffer(target, arg, len),
+                'r' => match stop.result {
+                    Some(result) if result >= 0 => read_buffer(target, arg, result as usize),
+                    _ => format!("{:#x}", arg),
+                },
+                _ => format!("{:#x}", arg),
+            }
+        })
+        .collect();
+    format!("{}({})", describe(stop.number), args.join(", "))
+}
+
+/// Formats what a syscall returned: an error number along with its name and description, an
+/// address for the syscalls that return one, or else a number.
+pub fn format_result(number: usize, result: i64) -> String {
+    // the kernel's own error numbers for an interrupted syscall that is going to be restarted
+    let restart = match -result {
+        512 => Some("ERESTARTSYS (To be restarted if SA_RESTART is set)"),
+        513 => Some("ERESTARTNOINTR (To be restarted)"),
+        514 => Some("ERESTARTNOHAND (To be restarted if no handler)"),
+        516 => Some("ERESTART_RESTARTBLOCK (Interrupted by signal)"),
+        _ => None,
+    };
+    if let Some(restart) = restart {
+        return format!("? {}", restart);
+    }
+    if (-4095..0).contains(&result) {
+        let errno = Errno::from_i32(-result as i32);
+        return format!("-1 {:?} ({})", errno, errno.desc());
+    }
+    match name(number) {
+        Some("mmap") | Some("mremap") | Some("brk") | Some("shmat") => format!("{:#x}", result),
+        _ => result.to_string(),
+    }
+}
+
+/// Returns whether a syscall ends the thread or process that made it, never returning.
+pub fn is_exit(number: usize) -> bool {
+    matches!(name(number), Some("exit") | Some("exit_group"))
+}
+
+/// Returns whether a syscall replaces the program, so that memory its arguments point to is
+/// gone once it has returned.
+pub fn is_exec(number: usize) -> bool {
+    matches!(name(number), Some("execve") | Some("execveat"))
+}
+
+/// Reads the NUL terminated string at addr.
+fn read_string(target: &dyn Target, addr: usize) -> String {
+    if addr == 0 {
+        return "NULL".to_string();
+    }
+    let mut bytes = Vec::new();
+    let mut word_addr = addr;
+    // read a word at a time, since the string may end just before unmapped memory
+    while bytes.len() <= MAX_STRING_LEN {
+        match target.read_word(word_addr) {
+            Ok(word) => bytes.extend_from_slice(&word.to_le_bytes()),
+            Err(_) if bytes.is_empty() => return format!("{:#x}", addr),
+            Err(_) => break,
+        }
+        if let Some(end) = bytes.iter().position(|byte| *byte == 0) {
+            bytes.truncate(end);
+            return quote(&bytes, false);
+        }
+        word_addr += size_of::<usize>();
+    }
+    quote(&bytes[..bytes.len().min(MAX_STRING_LEN)], true)
+}
+
+/// Reads a buffer of len bytes at addr.
+fn read_buffer(target: &dyn Target, addr: usize, len: usize) -> String {
+    match target.read_bytes(addr, len.min(MAX_STRING_LEN)) {
+        Ok(bytes) => quote(&bytes, len > MAX_STRING_LEN),
+        Err(_) => format!("{:#x}", addr),
+    }
+}
+
+/// Quotes bytes as a C string literal, followed by ... if they were cut short.
+fn quote(bytes: &[u8], truncated: bool) -> String {
+    let mut quoted = String::from("\"");
+    for byte in bytes {
+        match byte {
+            b'\n' => quoted += "\\n",
+            b'\t' => quoted += "\\t",
+            b'\r' => quoted += "\\r",
+            b'"' => quoted += "\\\"",
+            b'\\' => quoted += "\\\\",
+            0x20..=0x7e => quoted.push(*byte as char),
+            _ => quoted += &format!("\\x{:02x}", byte),
+        }
+    }
+    quoted.push('"');
+    if truncated {
+        quoted += "...";
+    }
+    quoted
+}
+
+/// The x86-64 syscalls by number, with a letter for how each argument is shown: d for a signed
+/// int, u for an unsigned number, o for octal, f for a file descriptor, s for a string, b for a
+/// buffer whose length is the next argument, r for a buffer the syscall fills in and returns
+/// the length of, and x for hex.
+const SYSCALLS: &[(usize, &str, &str)] = &[
+    (0, "read", "fru"),
+    (1, "write", "fbu"),
+    (2, "open", "sxo"),
+    (3, "close", "f"),
+    (4, "stat", "sx"),
+    (5, "fstat", "fx"),
+    (6, "lstat", "sx"),
+    (7, "poll", "xud"),
+    (8, "lseek", "fdd"),
+    (9, "mmap", "xuxxfd"),
+    (10, "mprotect", "xux"),
+    (11, "munmap", "xu"),
+    (12, "brk", "x"),
+    (13, "rt_sigaction", "dxxu"),
+    (14, "rt_sigprocmask", "dxxu"),
+    (15, "rt_sigreturn", ""),
+    (16, "ioctl", "fxx"),
+    (17, "pread64", "frud"),
+    (18, "pwrite64", "fbud"),
+    (19, "readv", "fxd"),
+    (20, "writev", "fxd"),
+    (21, "access", "so"),
+    (22, "pipe", "x"),
+    (23, "select", "dxxxx"),
+    (24, "sched_yield", ""),
+    (25, "mremap", "xuuxx"),
+    (26, "msync", "xux"),
+    (27, "mincore", "xux"),
+    (28, "madvise", "xud"),
+    (29, "shmget", "dud"),
+    (30, "shmat", "dxx"),
+    (31, "shmctl", "ddx"),
+    (32, "dup", "f"),
+    (33, "dup2", "ff"),
+    (34, "pause", ""),
+    (35, "nanosleep", "xx"),
+    (36, "getitimer", "dx"),
+    (37, "alarm", "u"),
+    (38, "setitimer", "dxx"),
+    (39, "getpid", ""),
+    (40, "sendfile", "ffxu"),
+    (41, "socket", "ddd"),
+    (42, "connect", "fxd"),
+    (43, "accept", "fxx"),
+    (44, "sendto", "fbuxxd"),
+    (45, "recvfrom", "fruxxx"),
+    (46, "sendmsg", "fxx"),
+    (47, "recvmsg", "fxx"),
+    (48, "shutdown", "fd"),
+    (49, "bind", "fxd"),
+    (50, "listen", "fd"),
+    (51, "getsockname", "fxx"),
+    (52, "getpeername", "fxx"),
+    (53, "socketpair", "dddx"),
+    (54, "setsockopt", "fddxd"),
+    (55, "getsockopt", "fddxx"),
+    (56, "clone", "xxxxx"),
+    (57, "fork", ""),
+    (58, "vfork", ""),
+    (59, "execve", "sxx"),
+    (60, "exit", "d"),
+    (61, "wait4", "dxxx"),
+    (62, "kill", "dd"),
+    (63, "uname", "x"),
+    (64, "semget", "ddx"),
+    (65, "semop", "dxu"),
+    (66, "semctl", "dddx"),
+    (67, "shmdt", "x"),
+    (68, "msgget", "dx"),
+    (69, "msgsnd", "dxux"),
+    (70, "msgrcv", "dxudx"),
+    (71, "msgctl", "ddx"),
+    (72, "fcntl", "fdx"),
+    (73, "flock", "fd"),
+    (74, "fsync", "f"),
+    (75, "fdatasync", "f"),
+    (76, "truncate", "sd"),
+    (77, "ftruncate", "fd"),
+    (78, "getdents", "fxu"),
+    (79, "getcwd", "xu"),
+    (80, "chdir", "s"),
+    (81, "fchdir", "f"),
+    (82, "rename", "ss"),
+    (83, "mkdir", "so"),
+    (84, "rmdir", "s"),
+    (85, "creat", "so"),
+    (86, "link", "ss"),
+    (87, "unlink", "s"),
+    (88, "symlink", "ss"),
+    (89, "readlink", "sxu"),
+    (90, "chmod", "so"),
+    (91, "fchmod", "fo"),
+    (92, "chown", "sdd"),
+    (93, "fchown", "fdd"),
+    (94, "lchown", "sdd"),
+    (95, "umask", "o"),
+    (96, "gettimeofday", "xx"),
+    (97, "getrlimit", "dx"),
+    (98, "getrusage", "dx"),
+    (99, "sysinfo", "x"),
+    (100, "times", "x"),
+    (101, "ptrace", "ddxx"),
+    (102, "getuid", ""),
+    (103, "syslog", "dxd"),
+    (104, "getgid", ""),
+    (105, "setuid", "d"),
+    (106, "setgid", "d"),
+    (107, "geteuid", ""),
+    (108, "getegid", ""),
+    (109, "setpgid", "dd"),
+    (110, "getppid", ""),
+    (111, "getpgrp", ""),
+    (112, "setsid", ""),
+    (113, "setreuid", "dd"),
+    (114, "setregid", "dd"),
+    (115, "getgroups", "dx"),
+    (116, "setgroups", "dx"),
+    (117, "setresuid", "ddd"),
+    (118, "getresuid", "xxx"),
+    (119, "setresgid", "ddd"),
+    (120, "getresgid", "xxx"),
+    (121, "getpgid", "d"),
+    (122, "setfsuid", "d"),
+    (123, "setfsgid", "d"),
+    (124, "getsid", "d"),
+    (125, "capget", "xx"),
+    (126, "capset", "xx"),
+    (127, "rt_sigpending", "xu"),
+    (128, "rt_sigtimedwait", "xxxu"),
+    (129, "rt_sigqueueinfo", "ddx"),
+    (130, "rt_sigsuspend", "xu"),
+    (131, "sigaltstack", "xx"),
+    (132, "utime", "sx"),
+    (133, "mknod", "sox"),
+    (134, "uselib", "s"),
+    (135, "personality", "x"),
+    (136, "ustat", "xx"),
+    (137, "statfs", "sx"),
+    (138, "fstatfs", "fx"),
+    (139, "sysfs", "dxx"),
+    (140, "getpriority", "dd"),
+    (141, "setpriority", "ddd"),
+    (142, "sched_setparam", "dx"),
+    (143, "sched_getparam", "dx"),
+    (144, "sched_setscheduler", "ddx"),
+    (145, "sched_getscheduler", "d"),
+    (146, "sched_get_priority_max", "d"),
+    (147, "sched_get_priority_min", "d"),
+    (148, "sched_rr_get_interval", "dx"),
+    (149, "mlock", "xu"),
+    (150, "munlock", "xu"),
+    (151, "mlockall", "x"),
+    (152, "munlockall", ""),
+    (153, "vhangup", ""),
+    (154, "modify_ldt", "dxu"),
+    (155, "pivot_root", "ss"),
+    (156, "_sysctl", "x"),
+    (157, "prctl", "dxxxx"),
+    (158, "arch_prctl", "dx"),
+    (159, "adjtimex", "x"),
+    (160, "setrlimit", "dx"),
+    (161, "chroot", "s"),
+    (162, "sync", ""),
+    (163, "acct", "s"),
+    (164, "settimeofday", "xx"),
+    (165, "mount", "sssxx"),
+    (166, "umount2", "sx"),
+    (167, "swapon", "sx"),
+    (168, "swapoff", "s"),
+    (169, "reboot", "xxdx"),
+    (170, "sethostname", "su"),
+    (171, "setdomainname", "su"),
+    (172, "iopl", "d"),
+    (173, "ioperm", "uud"),
+    (174, "create_module", "su"),
+    (175, "init_module", "xus"),
+    (176, "delete_module", "sx"),
+    (177, "get_kernel_syms", "x"),
+    (178, "query_module", "sdxux"),
+    (179, "quotactl", "dsdx"),
+    (180, "nfsservctl", "dxx"),
+    (181, "getpmsg", ""),
+    (182, "putpmsg", ""),
+    (183, "afs_syscall", ""),
+    (184, "tuxcall", ""),
+    (185, "security", ""),
+    (186, "gettid", ""),
+    (187, "readahead", "fdu"),
+    (188, "setxattr", "ssxux"),
+    (189, "lsetxattr", "ssxux"),
+    (190, "fsetxattr", "fsxux"),
+    (191, "getxattr", "ssxu"),
+    (192, "lgetxattr", "ssxu"),
+    (193, "fgetxattr", "fsxu"),
+    (194, "listxattr", "sxu"),
+    (195, "llistxattr", "sxu"),
+    (196, "flistxattr", "fxu"),
+    (197, "removexattr", "ss"),
+    (198, "lremovexattr", "ss"),
+    (199, "fremovexattr", "fs"),
+    (200, "tkill", "dd"),
+    (201, "time", "x"),
+    (202, "futex", "xdxxxd"),
+    (203, "sched_setaffinity", "dux"),
+    (204, "sched_getaffinity", "dux"),
+    (205, "set_thread_area", "x"),
+    (206, "io_setup", "ux"),
+    (207, "io_destroy", "x"),
+    (208, "io_getevents", "xddxx"),
+    (209, "io_submit", "xdx"),
+    (210, "io_cancel", "xxx"),
+    (211, "get_thread_area", "x"),
+    (212, "lookup_dcookie", "xxu"),
+    (213, "epoll_create", "d"),
+    (214, "epoll_ctl_old", ""),
+    (215, "epoll_wait_old", ""),
+    (216, "remap_file_pages", "xuxux"),
+    (217, "getdents64", "fxu"),
+    (218, "set_tid_address", "x"),
+    (219, "restart_syscall", ""),
+    (220, "semtimedop", "dxux"),
+    (221, "fadvise64", "fddd"),
+    (222, "timer_create", "dxx"),
+    (223, "timer_settime", "dxxx"),
+    (224, "timer_gettime", "dx"),
+    (225, "timer_getoverrun", "d"),
+    (226, "timer_delete", "d"),
+    (227, "clock_settime", "dx"),
+    (228, "clock_gettime", "dx"),
+    (229, "clock_getres", "dx"),
+    (230, "clock_nanosleep", "dxxx"),
+    (231, "exit_group", "d"),
+    (232, "epoll_wait", "fxdd"),
+    (233, "epoll_ctl", "fdfx"),
+    (234, "tgkill", "ddd"),
+    (235, "utimes", "sx"),
+    (236, "vserver", ""),
+    (237, "mbind", "xuxxux"),
+    (238, "set_mempolicy", "dxu"),
+    (239, "get_mempolicy", "xxuxx"),
+    (240, "mq_open", "sxox"),
+    (241, "mq_unlink", "s"),
+    (242, "mq_timedsend", "fbuux"),
+    (243, "mq_timedreceive", "fruxx"),
+    (244, "mq_notify", "fx"),
+    (245, "mq_getsetattr", "fxx"),
+    (246, "kexec_load", "xuxx"),
+    (247, "waitid", "ddxxx"),
+    (248, "add_key", "ssxuf"),
+    (249, "request_key", "sssd"),
+    (250, "keyctl", "dxxxx"),
+    (251, "ioprio_set", "ddd"),
+    (252, "ioprio_get", "dd"),
+    (253, "inotify_init", ""),
+    (254, "inotify_add_watch", "fsx"),
+    (255, "inotify_rm_watch", "fd"),
+    (256, "migrate_pages", "duxx"),
+    (257, "openat", "fsxo"),
+    (258, "mkdirat", "fso"),
+    (259, "mknodat", "fsox"),
+    (260, "fchownat", "fsddx"),
+    (261, "futimesat", "fsx"),
+    (262, "newfstatat", "fsxx"),
+    (263, "unlinkat", "fsx"),
+    (264, "renameat", "fsfs"),
+    (265, "linkat", "fsfsx"),
+    (266, "symlinkat", "sfs"),
+    (267, "readlinkat", "fsxu"),
+    (268, "fchmodat", "fso"),
+    (269, "faccessat", "fso"),
+    (270, "pselect6", "dxxxxx"),
+    (271, "ppoll", "xuxxu"),
+    (272, "unshare", "x"),
+    (273, "set_robust_list", "xu"),
+    (274, "get_robust_list", "dxx"),
+    (275, "splice", "fxfxux"),
+    (276, "tee", "ffux"),
+    (277, "sync_file_range", "fddx"),
+    (278, "vmsplice", "fxux"),
+    (279, "move_pages", "duxxxx"),
+    (280, "utimensat", "fsxx"),
+    (281, "epoll_pwait", "fxddxu"),
+    (282, "signalfd", "fxu"),
+    (283, "timerfd_create", "dx"),
+    (284, "eventfd", "u"),
+    (285, "fallocate", "fxdd"),
+    (286, "timerfd_settime", "fxxx"),
+    (287, "timerfd_gettime", "fx"),
+    (288, "accept4", "fxxx"),
+    (289, "signalfd4", "fxux"),
+    (290, "eventfd2", "ux"),
+    (291, "epoll_create1", "x"),
+    (292, "dup3", "ffx"),
+    (293, "pipe2", "xx"),
+    (294, "inotify_init1", "x"),
+    (295, "preadv", "fxdd"),
+    (296, "pwritev", "fxdd"),
+    (297, "rt_tgsigqueueinfo", "dddx"),
+    (298, "perf_event_open", "xddfx"),
+    (299, "recvmmsg", "fxuxx"),
+    (300, "fanotify_init", "xx"),
+    (301, "fanotify_mark", "fxxfs"),
+    (302, "prlimit64", "ddxx"),
+    (303, "name_to_handle_at", "fsxxx"),
+    (304, "open_by_handle_at", "fxx"),
+    (305, "clock_adjtime", "dx"),
+    (306, "syncfs", "f"),
+    (307, "sendmmsg", "fxux"),
+    (308, "setns", "fx"),
+    (309, "getcpu", "xxx"),
+    (310, "process_vm_readv", "dxuxux"),
+    (311, "process_vm_writev", "dxuxux"),
+    (312, "kcmp", "dddxx"),
+    (313, "finit_module", "fsx"),
+    (314, "sched_setattr", "dxx"),
+    (315, "sched_getattr", "dxux"),
+    (316, "renameat2", "fsfsx"),
+    (317, "seccomp", "xxx"),
+    (318, "getrandom", "xux"),
+    (319, "memfd_create", "sx"),
+    (320, "kexec_file_load", "ffusx"),
+    (321, "bpf", "dxu"),
+    (322, "execveat", "fsxxx"),
+    (323, "userfaultfd", "x"),
+    (324, "membarrier", "dx"),
+    (325, "mlock2", "xux"),
+    (326, "copy_file_range", "fxfxux"),
+    (327, "preadv2", "fxddx"),
+    (328, "pwritev2", "fxddx"),
+    (329, "pkey_mprotect", "xuxd"),
+    (330, "pkey_alloc", "xx"),
+    (331, "pkey_free", "d"),
+    (332, "statx", "fsxxx"),
+    (333, "io_pgetevents", "xddxxx"),
+    (334, "rseq", "xuxx"),
+    (424, "pidfd_send_signal", "fdxx"),
+    (425, "io_uring_setup", "ux"),
+    (426, "io_uring_enter", "fuuxxu"),
+    (427, "io_uring_register", "fdxu"),
+    (428, "open_tree", "fsx"),
+    (429, "move_mount", "fsfsx"),
+    (430, "fsopen", "sx"),
+    (431, "fsconfig", "fdssd"),
+    (432, "fsmount", "fxx"),
+    (433, "fspick", "fsx"),
+    (434, "pidfd_open", "dx"),
+    (435, "clone3", "xu"),
+    (436, "close_range", "uux"),
+    (437, "openat2", "fsxu"),
+    (438, "pidfd_getfd", "ffx"),
+    (439, "faccessat2", "fsox"),
+    (440, "process_madvise", "fxudx"),
+    (441, "epoll_pwait2", "fxdxxu"),
+    (442, "mount_setattr", "fsxxu"),
+    (443, "quotactl_fd", "fxdx"),
+    (444, "landlock_create_ruleset", "xux"),
+    (445, "landlock_add_rule", "fdxx"),
+    (446, "landlock_restrict_self", "fx"),
+    (447, "memfd_secret", "x"),
+    (448, "process_mrelease", "fx"),
+    (449, "futex_waitv", "xuxxd"),
+    (450, "set_mempolicy_home_node", "xuux"),
+    (451, "cachestat", "fxxx"),
+    (452, "fchmodat2", "fsox"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Memory starting at base, with everything else unmapped.
+    struct Memory {
+        base: usize,
+        bytes: Vec<u8>,
+    }
+
+    impl Memory {
+        fn new() -> Memory {
+            Memory { base: 0x1000, bytes: vec![0; 0x400] }
+        }
+
+        fn write(&mut self, addr: usize, bytes: &[u8]) {
+            let start = addr - self.base;
+            self.bytes[start..start + bytes.len()].copy_from_slice(bytes);
+        }
+    }
+
+    impl Target for Memory {
+        fn registers(&self) -> Result<libc::user_regs_struct, nix::Error> {
+            Err(nix::Error::Sys(Errno::ESRCH))
+        }
+
+        fn read_bytes(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error> {
+            match addr.checked_sub(self.base) {
+                Some(start) if start + len <= self.bytes.len() => {
+                    Ok(self.bytes[start..start + len].to_vec())
+                }
+                _ => Err(nix::Error::Sys(Errno::EIO)),
+            }
+        }
+
+        fn memory_map(&self) -> String {
+            String::new()
+        }
+    }
+
+    fn call(target: &dyn Target, number: usize, args: &[usize], result: Option<i64>) -> String {
+        let mut stop = SyscallStop { number, args: [0; 6], result };
+        stop.args[..args.len()].copy_from_slice(args);
+        format_call(target, &stop)
+    }
+
+    #[test]
+    fn looks_up_syscalls() {
+        assert_eq!(name(1), Some("write"));
+        assert_eq!(name(999), None);
+        assert_eq!(parse("openat"), Some(257));
+        assert_eq!(parse("59"), Some(59));
+        assert_eq!(parse("nosuchcall"), None);
+        assert_eq!(describe(231), "exit_group");
+        assert_eq!(describe(999), "syscall_999");
+        assert!(is_exit(60) && is_exit(231) && !is_exit(1));
+        assert!(is_exec(59) && !is_exec(57));
+    }
+
+    #[test]
+    fn decodes_numbers_and_descriptors() {
+        let memory = Memory::new();
+        assert_eq!(call(&memory, 3, &[4], None), "close(4)");
+        assert_eq!(call(&memory, 62, &[-1i64 as usize, 9], None), "kill(-1, 9)");
+        assert_eq!(
+            call(&memory, 9, &[0, 4096, 3, 0x22, -1i64 as usize, 0], None),
+            "mmap(0x0, 4096, 0x3, 0x22, -1, 0)"
+        );
+        // an unknown syscall gets all six arguments in hex
+        assert_eq!(
+            call(&memory, 999, &[1, 2, 3, 4, 5, 6], None),
+            "syscall_999(0x1, 0x2, 0x3, 0x4, 0x5, 0x6)"
+        );
+    }
+
+    #[test]
+    fn decodes_strings() {
+        let mut memory = Memory::new();
+        memory.write(0x1000, b"/etc/passwd\0");
+        memory.write(0x1100, &[b'a'; 40]);
+        assert_eq!(
+            call(&memory, 257, &[libc::AT_FDCWD as usize, 0x1000, 0x241, 0o644], None),
+            "openat(AT_FDCWD, \"/etc/passwd\", 0x241, 0644)"
+        );
+        assert_eq!(
+            call(&memory, 59, &[0x1100, 0, 0], None),
+            format!("execve(\"{}\"..., 0x0, 0x0)", "a".repeat(MAX_STRING_LEN))
+        );
+        assert_eq!(call(&memory, 59, &[0, 0, 0], None), "execve(NULL, 0x0, 0x0)");
+        // a pointer into unmapped memory is shown as it is
+        assert_eq!(call(&memory, 59, &[0xdead0, 0, 0], None), "execve(0xdead0, 0x0, 0x0)");
+    }
+
+    #[test]
+    fn decodes_buffers() {
+        let mut memory = Memory::new();
+        memory.write(0x1000, b"hi\t\"there\"\\\n\x01");
+        memory.write(0x1100, &[b'b'; 40]);
+        assert_eq!(
+            call(&memory, 1, &[1, 0x1000, 14], None),
+            r#"write(1, "hi\t\"there\"\\\n\x01\x00", 14)"#
+        );
+        assert_eq!(
+            call(&memory, 1, &[1, 0x1100, 40], None),
+            format!("write(1, \"{}\"..., 40)", "b".repeat(MAX_STRING_LEN))
+        );
+        // a buffer read fills in is only shown once it has returned, and only as much as it read
+        assert_eq!(call(&memory, 0, &[0, 0x1000, 100], None), "read(0, 0x1000, 100)");
+        assert_eq!(call(&memory, 0, &[0, 0x1000, 100], Some(2)), "read(0, \"hi\", 100)");
+        assert_eq!(call(&memory, 0, &[0, 0x1000, 100], Some(-11)), "read(0, 0x1000, 100)");
+    }
+
+    #[test]
+    fn formats_results() {
+        assert_eq!(format_result(1, 6), "6");
+        assert_eq!(format_result(257, -2), "-1 ENOENT (No such file or directory)");
+        assert_eq!(format_result(9, 0x7f00_0000_0000), "0x7f0000000000");
+        assert_eq!(format_result(0, -512), "? ERESTARTSYS (To be restarted if SA_RESTART is set)");
+    }
+}
@@ -0,0 +1,44 @@
+use nix::errno::Errno;
+use std::mem::size_of;
+
+/// A program whose registers and memory can be inspected: either a live inferior or a core dump.
+pub trait Target {
+    /// Returns the general purpose registers of the thread that stopped.
+    fn registers(&self) -> Result<libc::user_regs_struct, nix::Error>;
+
+    /// Reads len bytes of memory starting at addr.
+    fn read_bytes(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error>;
+
+    /// Returns the memory mappings, in the format of /proc/<pid>/maps.
+    fn memory_map(&self) -> String;
+
+    /// Returns the address the program started running at, from its auxiliary vector.
+    fn entry_point(&self) -> Option<usize> {
+        None
+    }
+
+    /// Reads the word of memory at addr.
+    fn read_word(&self, addr: usize) -> Result<usize, nix::Error> {
+        let mut word = [0u8; size_of::<usize>()];
+        word.copy_from_slice(&self.read_bytes(addr, size_of::<usize>())?);
+        Ok(usize::from_le_bytes(word))
+    }
+}
+
+/// Stands in for the program when there is none, so that expressions which don't need its
+/// registers or memory can still be evaluated.
+pub struct NoProcess;
+
+impl Target for NoProcess {
+    fn registers(&self) -> Result<libc::user_regs_struct, nix::Error> {
+        Err(nix::Error::Sys(Errno::ESRCH))
+    }
+
+    fn read_bytes(&self, _addr: usize, _len: usize) -> Result<Vec<u8>, nix::Error> {
+        Err(nix::Error::Sys(Errno::ESRCH))
+    }
+
+    fn memory_map(&self) -> String {
+        String::new()
+    }
+}
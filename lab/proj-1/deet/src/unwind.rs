@@ -0,0 +1,278 @@
+use gimli::{
+    BaseAddresses, CfaRule, DebugFrame, EhFrame, EndianRcSlice, Register, RegisterRule,
+    RunTimeEndian, UninitializedUnwindContext, UnwindSection, UnwindTableRow,
+};
+use object::{Object, ObjectSection};
+use std::convert::TryInto;
+use std::fs;
+use std::rc::Rc;
+
+type Reader = EndianRcSlice<RunTimeEndian>;
+
+/// DWARF numbers of the x86-64 registers the debugger cares about when unwinding.
+pub const RBP: usize = 6;
+pub const RSP: usize = 7;
+/// The return address column, which holds the caller's %rip.
+pub const RIP: usize = 16;
+const NUM_REGISTERS: usize = 17;
+/// Registers a callee has to preserve: %rbx, %rbp and %r12-%r15.
+const CALLEE_SAVED: [usize; 6] = [3, 6, 12, 13, 14, 15];
+
+/// Gives up on stacks deeper than this, which are most likely corrupted.
+const MAX_FRAMES: usize = 1024;
+
+/// Register values in a stack frame, indexed by DWARF register number. Registers whose values
+/// can't be recovered in a caller's frame are None.
+#[derive(Clone, Debug)]
+pub struct FrameRegisters([Option<usize>; NUM_REGISTERS]);
+
+impl FrameRegisters {
+    pub fn from_regs(regs: &libc::user_regs_struct) -> FrameRegisters {
+        let values = [
+            regs.rax, regs.rdx, regs.rcx, regs.rbx, regs.rsi, regs.rdi, regs.rbp, regs.rsp,
+            regs.r8, regs.r9, regs.r10, regs.r11, regs.r12, regs.r13, regs.r14, regs.r15,
+            regs.rip,
+        ];
+        let mut registers = [None; NUM_REGISTERS];
+        for (register, value) in registers.iter_mut().zip(values.iter()) {
+            *register = Some(*value as usize);
+        }
+        FrameRegisters(registers)
+    }
+
+    pub fn get(&self, register: usize) -> Option<usize> {
+        *self.0.get(register)?
+    }
+
+    /// Returns regs with the values recovered for this frame in place of the innermost frame's.
+    /// Registers that couldn't be recovered keep their innermost values.
+    pub fn apply(&self, regs: &libc::user_regs_struct) -> libc::user_regs_struct {
+        let mut regs = *regs;
+        let mut fields = [
+            &mut regs.rax, &mut regs.rdx, &mut regs.rcx, &mut regs.rbx, &mut regs.rsi,
+            &mut regs.rdi, &mut regs.rbp, &mut regs.rsp, &mut regs.r8, &mut regs.r9,
+            &mut regs.r10, &mut regs.r11, &mut regs.r12, &mut regs.r13, &mut regs.r14,
+            &mut regs.r15, &mut regs.rip,
+        ];
+        for (field, value) in fields.iter_mut().zip(self.0.iter()) {
+            if let Some(value) = value {
+                **field = *value as u64;
+            }
+        }
+        regs
+    }
+}
+
+/// A stack frame found by unwinding.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// The frame's %rip: where it is stopped, or where it will continue when its callee returns.
+    pub pc: usize,
+    /// The canonical frame address, i.e. %rsp just before the call that created this frame. It
+    /// is None if there's no call frame information for pc.
+    pub cfa: Option<usize>,
+    pub regs: FrameRegisters,
+}
+
+/// The call frame information of one executable file mapped into the inferior.
+struct Module {
+    start: usize,
+    end: usize,
+    /// Difference between runtime addresses and the addresses in the file.
+    bias: usize,
+    eh_frame: Option<(EhFrame<Reader>, BaseAddresses)>,
+    debug_frame: Option<(DebugFrame<Reader>, BaseAddresses)>,
+}
+
+impl Module {
+    fn load(path: &str, start: usize, end: usize, base: usize) -> Option<Module> {
+        let data = fs::read(path).ok()?;
+        let object = object::File::parse(&*data).ok()?;
+        let endian = if object.is_little_endian() {
+            RunTimeEndian::Little
+        } else {
+            RunTimeEndian::Big
+        };
+        let bias = base.wrapping_sub(first_load_address(&data)?);
+        let text = object.section_by_name(".text").map_or(0, |section| section.address());
+        let section = |name| {
+            object.section_by_name(name).map(|section| {
+                let data = EndianRcSlice::new(Rc::from(section.data()), endian);
+                let bases = BaseAddresses::default()
+                    .set_eh_frame(section.address())
+                    .set_text(text);
+                (data, bases)
+            })
+        };
+        Some(Module {
+            start,
+            end,
+            bias,
+            eh_frame: section(".eh_frame").map(|(data, bases)| (EhFrame::from(data), bases)),
+            debug_frame: section(".debug_frame")
+                .map(|(data, bases)| (DebugFrame::from(data), bases)),
+        })
+    }
+
+    /// Finds the unwind rules that apply at the (file) address addr.
+    fn row(&self, addr: u64) -> Option<UnwindTableRow<Reader>> {
+        let mut ctx = UninitializedUnwindContext::new();
+        if let Some((eh_frame, bases)) = &self.eh_frame {
+            if let Ok(row) =
+                eh_frame.unwind_info_for_address(bases, &mut ctx, addr, EhFrame::cie_from_offset)
+            {
+                return Some(row);
+            }
+        }
+        let (debug_frame, bases) = self.debug_frame.as_ref()?;
+        debug_frame
+            .unwind_info_for_address(bases, &mut ctx, addr, DebugFrame::cie_from_offset)
+            .ok()
+    }
+}
+
+/// Walks the inferior's stack using the .eh_frame and .debug_frame call frame information of
+/// the program and the libraries it has loaded, so that frame pointers aren't needed.
+pub struct Unwinder {
+    modules: Vec<Module>,
+}
+
+impl Unwinder {
+    /// Loads the call frame information of every executable file in a process's memory map,
+    /// given the contents of its /proc/<pid>/maps.
+    pub fn new(maps: &str) -> Unwinder {
+        let mut modules = Vec::new();
+        // (path, address of the mapping at file offset 0, executable ranges)
+        let mut files: Vec<(String, usize, Vec<(usize, usize)>)> = Vec::new();
+        for line in maps.lines() {
+            // start-end perms offset dev inode path
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 || !fields[5].starts_with('/') {
+                continue;
+            }
+            let mut range = fields[0].split('-').map(|n| usize::from_str_radix(n, 16));
+            let (start, end) = match (range.next(), range.next()) {
+                (Some(Ok(start)), Some(Ok(end))) => (start, end),
+                _ => continue,
+            };
+            let offset = usize::from_str_radix(fields[3], 16).unwrap_or(1);
+            let path = fields[5].to_string();
+            if files.last().map(|(last, _, _)| last != &path).unwrap_or(true) {
+                files.push((path, start, Vec::new()));
+            }
+            let file = files.last_mut().unwrap();
+            if offset == 0 {
+                file.1 = start;
+            }
+            if fields[1].contains('x') {
+                file.2.push((start, end));
+            }
+        }
+        for (path, base, ranges) in files {
+            for (start, end) in ranges {
+                if let Some(module) = Module::load(&path, start, end, base) {
+                    modules.push(module);
+                }
+            }
+        }
+        Unwinder { modules }
+    }
+
+    /// Returns the frames on the stack, innermost first, starting from the registers the
+    /// inferior is stopped with. Words of stack memory are read with read_word.
+    pub fn backtrace<F>(&self, regs: FrameRegisters, read_word: F) -> Vec<Frame>
+    where
+        F: Fn(usize) -> Option<usize>,
+    {
+        let mut frames = Vec::new();
+        let mut regs = Some(regs);
+        while let Some(current) = regs.take() {
+            let pc = match current.get(RIP) {
+                Some(pc) if pc != 0 => pc,
+                _ => break,
+            };
+            // A return address may be just past the end of the calling function if the call
+            // never returns, so look up the call instruction itself for callers
+            let lookup_pc = if frames.is_empty() { pc } else { pc - 1 };
+            let row = self
+                .modules
+                .iter()
+                .find(|module| module.start <= lookup_pc && lookup_pc < module.end)
+                .and_then(|module| module.row((lookup_pc.wrapping_sub(module.bias)) as u64));
+            let cfa = row.as_ref().and_then(|row| compute_cfa(row, &current));
+            if let (Some(row), Some(cfa)) = (&row, cfa) {
+                let caller = unwind_registers(row, cfa, &current, &read_word);
+                // the stack grows down, so callers' frames must be at higher addresses
+                let makes_progress = current.get(RSP).map_or(true, |rsp| cfa > rsp);
+                if makes_progress && frames.len() < MAX_FRAMES {
+                    regs = Some(caller);
+                }
+            }
+            frames.push(Frame {
+                pc,
+                cfa,
+                regs: current,
+            });
+        }
+        frames
+    }
+}
+
+/// Evaluates the rule for the canonical frame address. Expression rules aren't supported.
+fn compute_cfa(row: &UnwindTableRow<Reader>, regs: &FrameRegisters) -> Option<usize> {
+    match row.cfa() {
+        CfaRule::RegisterAndOffset { register, offset } => {
+            let base = regs.get(register.0 as usize)?;
+            Some((base as i64 + offset) as usize)
+        }
+        CfaRule::Expression(_) => None,
+    }
+}
+
+/// Recovers the registers of the caller of the frame with registers regs and the given CFA.
+fn unwind_registers<F>(
+    row: &UnwindTableRow<Reader>,
+    cfa: usize,
+    regs: &FrameRegisters,
+    read_word: &F,
+) -> FrameRegisters
+where
+    F: Fn(usize) -> Option<usize>,
+{
+    let mut caller = [None; NUM_REGISTERS];
+    for (number, value) in caller.iter_mut().enumerate() {
+        *value = match row.register(Register(number as u16)) {
+            // registers without a rule are unchanged if the ABI says the callee must preserve
+            // them, and lost otherwise
+            RegisterRule::Undefined if CALLEE_SAVED.contains(&number) => regs.get(number),
+            RegisterRule::Undefined => None,
+            RegisterRule::SameValue => regs.get(number),
+            RegisterRule::Offset(offset) => read_word((cfa as i64 + offset) as usize),
+            RegisterRule::ValOffset(offset) => Some((cfa as i64 + offset) as usize),
+            RegisterRule::Register(other) => regs.get(other.0 as usize),
+            _ => None,
+        };
+    }
+    // the caller's stack pointer is where it was before pushing the return address
+    caller[RSP] = Some(cfa);
+    FrameRegisters(caller)
+}
+
+/// Returns the lowest virtual address of a PT_LOAD segment in a 64-bit ELF file, which is where
+/// the file's first mapping goes if it is loaded without relocation.
+fn first_load_address(elf: &[u8]) -> Option<usize> {
+    const PT_LOAD: u32 = 1;
+    let bytes = |offset: usize, len: usize| elf.get(offset..offset + len);
+    let read_u16 = |offset| Some(u16::from_le_bytes(bytes(offset, 2)?.try_into().ok()?));
+    let read_u32 = |offset| Some(u32::from_le_bytes(bytes(offset, 4)?.try_into().ok()?));
+    let read_u64 = |offset| Some(u64::from_le_bytes(bytes(offset, 8)?.try_into().ok()?));
+    let phoff = read_u64(0x20)? as usize;
+    let phentsize = read_u16(0x36)? as usize;
+    let phnum = read_u16(0x38)? as usize;
+    (0..phnum)
+        .map(|index| phoff + index * phentsize)
+        .filter(|header| read_u32(*header) == Some(PT_LOAD))
+        .filter_map(|header| read_u64(header + 0x10))
+        .min()
+        .map(|vaddr| (vaddr & !0xfff) as usize)
+}
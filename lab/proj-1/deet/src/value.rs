@@ -0,0 +1,419 @@
+use crate::dwarf_data::{
+    BaseEncoding, DwarfData, Language, Location, Member, Type, TypeKind, Variable, Variant,
+};
+use crate::target::Target;
+use std::convert::TryInto;
+
+/// Longest string we will print when following a char pointer.
+pub const MAX_STRING_LEN: usize = 200;
+/// Most elements of an array we will print.
+const MAX_ARRAY_ELEMENTS: usize = 200;
+
+/// Returns the address of a variable in the frame whose canonical frame address is frame_base.
+pub fn variable_address(var: &Variable, frame_base: usize) -> usize {
+    match var.location {
+        Location::Address(addr) => addr,
+        // gcc uses DW_OP_call_frame_cfa as the frame base
+        Location::FramePointerOffset(offset) => (frame_base as isize + offset) as usize,
+    }
+}
+
+/// Reads a little-endian unsigned integer of up to 8 bytes.
+pub fn read_uint(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    let len = bytes.len().min(8);
+    buf[..len].copy_from_slice(&bytes[..len]);
+    u64::from_le_bytes(buf)
+}
+
+/// Reads a little-endian signed integer of up to 8 bytes, sign-extending it.
+pub fn read_int(bytes: &[u8]) -> i64 {
+    let len = bytes.len().min(8);
+    if len == 0 {
+        return 0;
+    }
+    let shift = 64 - 8 * len as u32;
+    ((read_uint(bytes) << shift) as i64) >> shift
+}
+
+pub fn format_char(value: u8) -> String {
+    match value {
+        0 => format!("{} '\\0'", value),
+        _ => format!("{} '{}'", value, (value as char).escape_default()),
+    }
+}
+
+/// Formats very large and very small floats in scientific notation rather than with dozens of
+/// digits.
+pub fn format_float(value: f64) -> String {
+    if value != 0.0 && (value.abs() < 1e-4 || value.abs() >= 1e16) {
+        format!("{:e}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Returns " <symbol+offset>" for an address in a function or global variable, or ""
+/// otherwise.
+pub fn symbol_suffix(debug_data: &DwarfData, addr: usize) -> String {
+    let symbol = match (
+        debug_data.get_function(addr),
+        debug_data.get_global_variable(addr),
+    ) {
+        (Some(func), _) => Some((&func.name, func.address)),
+        (None, Some(var)) => match var.location {
+            Location::Address(start) => Some((&var.name, start)),
+            _ => None,
+        },
+        (None, None) => None,
+    };
+    match symbol {
+        Some((name, start)) if addr == start => format!(" <{}>", name),
+        Some((name, start)) => format!(" <{}+{}>", name, addr - start),
+        None => String::new(),
+    }
+}
+
+/// Reads the bytes of a NUL-terminated string from the inferior, up to max_len of them. Also
+/// returns whether the string was cut short.
+pub fn read_string_bytes(
+    inferior: &dyn Target,
+    addr: usize,
+    max_len: usize,
+) -> Option<(Vec<u8>, bool)> {
+    let mut bytes = Vec::new();
+    while bytes.len() < max_len {
+        let chunk = inferior.read_bytes(addr + bytes.len(), 8).ok()?;
+        if let Some(end) = chunk.iter().position(|b| *b == 0) {
+            bytes.extend_from_slice(&chunk[..end]);
+            return Some((bytes, false));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    bytes.truncate(max_len);
+    Some((bytes, true))
+}
+
+/// Reads a NUL-terminated string from the inferior, truncating long strings.
+pub fn read_string(inferior: &dyn Target, addr: usize) -> Option<String> {
+    let (bytes, truncated) = read_string_bytes(inferior, addr, MAX_STRING_LEN)?;
+    Some(format!(
+        "\"{}\"{}",
+        String::from_utf8_lossy(&bytes).escape_default(),
+        if truncated { "..." } else { "" }
+    ))
+}
+
+/// Formats the bytes of a value of the given type the way C would print them, or the way Rust
+/// would for types from Rust code.
+pub fn format_value(
+    inferior: &dyn Target,
+    debug_data: &DwarfData,
+    value_type: &Type,
+    bytes: &[u8],
+) -> String {
+    let value_type = debug_data.strip_typedefs(value_type);
+    if value_type.language == Language::Rust {
+        if let Some(formatted) = format_rust_value(inferior, debug_data, value_type, bytes) {
+            return formatted;
+        }
+    }
+    match &value_type.kind {
+        TypeKind::Base(encoding) => match encoding {
+            BaseEncoding::Signed => read_int(bytes).to_string(),
+            BaseEncoding::Unsigned => read_uint(bytes).to_string(),
+            BaseEncoding::SignedChar | BaseEncoding::UnsignedChar => format_char(bytes[0]),
+            BaseEncoding::Boolean => (read_uint(bytes) != 0).to_string(),
+            BaseEncoding::Float => match bytes.len() {
+                4 => format_float(f32::from_le_bytes(bytes.try_into().unwrap()) as f64),
+                8 => format_float(f64::from_le_bytes(bytes.try_into().unwrap())),
+                _ => format_raw(bytes),
+            },
+        },
+        TypeKind::Pointer(target) => {
+            let addr = read_uint(bytes) as usize;
+            if addr != 0 && is_char_type(debug_data, *target) {
+                match read_string(inferior, addr) {
+                    Some(string) => format!("{:#x} {}", addr, string),
+                    None => format!("{:#x} <error: Cannot access memory>", addr),
+                }
+            } else if is_function_type(debug_data, *target) {
+                let symbol = symbol_suffix(debug_data, addr);
+                format!("({}) {:#x}{}", value_type.name, addr, symbol)
+            } else {
+                format!("({}) {:#x}", value_type.name, addr)
+            }
+        }
+        TypeKind::Struct(members) | TypeKind::Union(members) => {
+            let fields: Vec<String> = members
+                .iter()
+                .map(|member| {
+                    let member_type = debug_data.get_type(member.type_offset);
+                    match member_type {
+                        Some(member_type) if member.offset + member_type.size <= bytes.len() => {
+                            let member_bytes =
+                                &bytes[member.offset..member.offset + member_type.size];
+                            format!(
+                                "{} = {}",
+                                member.name,
+                                format_value(inferior, debug_data, member_type, member_bytes)
+                            )
+                        }
+                        _ => format!("{} = <unknown>", member.name),
+                    }
+                })
+                .collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+        TypeKind::Array(element, dimensions) => match debug_data.get_type(*element) {
+            Some(element_type) => {
+                format_array(inferior, debug_data, element_type, dimensions, bytes)
+            }
+            None => format_raw(bytes),
+        },
+        TypeKind::Enum(variants) => {
+            let value = read_int(bytes);
+            match variants.iter().find(|(_, variant_value)| *variant_value == value) {
+                Some((name, _)) => name.clone(),
+                None => value.to_string(),
+            }
+        }
+        TypeKind::Variants(discriminant, variants) => {
+            match find_variant(debug_data, discriminant, variants, bytes) {
+                Some(member) => format_member(inferior, debug_data, member, bytes)
+                    .unwrap_or_else(|| "<unknown>".to_string()),
+                None => "<unknown variant>".to_string(),
+            }
+        }
+        TypeKind::Function(_) => "{function}".to_string(),
+        TypeKind::Typedef(_) | TypeKind::Qualified(_, _) | TypeKind::Unknown => format_raw(bytes),
+    }
+}
+
+/// Formats the Rust standard library types deet knows the layout of, and Rust structs and
+/// enum variants, in Rust syntax. Returns None for anything else.
+fn format_rust_value(
+    inferior: &dyn Target,
+    debug_data: &DwarfData,
+    value_type: &Type,
+    bytes: &[u8],
+) -> Option<String> {
+    let members = match &value_type.kind {
+        TypeKind::Struct(members) => members,
+        _ => return None,
+    };
+    let name = value_type.name.as_str();
+    if name == "&str" {
+        let addr = slice_pointer(debug_data, members, bytes)?;
+        let len = slice_len(members, bytes)?;
+        return Some(format_rust_string(inferior, addr, len));
+    }
+    if name.starts_with("&[") || name.starts_with("&mut [") {
+        let addr = slice_pointer(debug_data, members, bytes)?;
+        let len = slice_len(members, bytes)?;
+        let data_ptr = members.iter().find(|member| member.name == "data_ptr")?;
+        let element = match &debug_data.get_type(data_ptr.type_offset)?.kind {
+            TypeKind::Pointer(Some(element)) => debug_data.get_type(*element)?,
+            _ => return None,
+        };
+        return Some(format_elements(inferior, debug_data, element, addr, len));
+    }
+    if name.starts_with("Vec<") {
+        let element = debug_data.get_type(*value_type.params.first()?)?;
+        let addr = find_pointer(debug_data, value_type, bytes)?;
+        let len = slice_len(members, bytes)?;
+        return Some(format!(
+            "vec!{}",
+            format_elements(inferior, debug_data, element, addr, len)
+        ));
+    }
+    if name == "String" {
+        let vec = members.iter().find(|member| member.name == "vec")?;
+        let vec_type = debug_data.get_type(vec.type_offset)?;
+        let vec_bytes = bytes.get(vec.offset..vec.offset + vec_type.size)?;
+        let vec_members = match &vec_type.kind {
+            TypeKind::Struct(members) => members,
+            _ => return None,
+        };
+        let addr = find_pointer(debug_data, vec_type, vec_bytes)?;
+        let len = slice_len(vec_members, vec_bytes)?;
+        return Some(format_rust_string(inferior, addr, len));
+    }
+    // the struct of an enum variant or a user's struct
+    let fields = members
+        .iter()
+        .map(|member| {
+            let value = format_member(inferior, debug_data, member, bytes);
+            (member, value.unwrap_or_else(|| "<unknown>".to_string()))
+        })
+        .collect::<Vec<_>>();
+    if fields.is_empty() {
+        Some(name.to_string())
+    } else if fields.iter().all(|(member, _)| member.name.starts_with("__")) {
+        let values: Vec<String> = fields.into_iter().map(|(_, value)| value).collect();
+        Some(format!("{}({})", name, values.join(", ")))
+    } else {
+        let values: Vec<String> = fields
+            .into_iter()
+            .map(|(member, value)| format!("{}: {}", member.name, value))
+            .collect();
+        Some(format!("{} {{ {} }}", name, values.join(", ")))
+    }
+}
+
+/// Formats a member of a struct or enum whose bytes are given.
+fn format_member(
+    inferior: &dyn Target,
+    debug_data: &DwarfData,
+    member: &Member,
+    bytes: &[u8],
+) -> Option<String> {
+    let member_type = debug_data.get_type(member.type_offset)?;
+    let member_bytes = bytes.get(member.offset..member.offset + member_type.size)?;
+    Some(format_value(inferior, debug_data, member_type, member_bytes))
+}
+
+/// Picks the variant of a Rust enum whose discriminant matches the one in its bytes, or the
+/// variant without a discriminant value, which takes every other value.
+fn find_variant<'a>(
+    debug_data: &DwarfData,
+    discriminant: &Option<Member>,
+    variants: &'a [Variant],
+    bytes: &[u8],
+) -> Option<&'a Member> {
+    let value = discriminant.as_ref().and_then(|discriminant| {
+        let size = debug_data.get_type(discriminant.type_offset)?.size;
+        bytes.get(discriminant.offset..discriminant.offset + size).map(read_uint)
+    });
+    variants
+        .iter()
+        .find(|variant| variant.discriminant.is_some() && variant.discriminant == value)
+        .or_else(|| variants.iter().find(|variant| variant.discriminant.is_none()))
+        .map(|variant| &variant.member)
+}
+
+/// Returns the address in the data_ptr member of a slice or &str.
+fn slice_pointer(debug_data: &DwarfData, members: &[Member], bytes: &[u8]) -> Option<usize> {
+    let data_ptr = members.iter().find(|member| member.name == "data_ptr")?;
+    let size = debug_data.get_type(data_ptr.type_offset)?.size;
+    Some(read_uint(bytes.get(data_ptr.offset..data_ptr.offset + size)?) as usize)
+}
+
+/// Returns the length in the length or len member of a slice, &str or Vec.
+fn slice_len(members: &[Member], bytes: &[u8]) -> Option<usize> {
+    let len = members.iter().find(|member| member.name == "length" || member.name == "len")?;
+    Some(read_uint(bytes.get(len.offset..len.offset + 8)?) as usize)
+}
+
+/// Returns the first pointer nested anywhere in a struct, which for a Vec is its buffer.
+fn find_pointer(debug_data: &DwarfData, value_type: &Type, bytes: &[u8]) -> Option<usize> {
+    match &debug_data.strip_typedefs(value_type).kind {
+        TypeKind::Pointer(_) => Some(read_uint(bytes) as usize),
+        TypeKind::Struct(members) => members.iter().find_map(|member| {
+            let member_type = debug_data.get_type(member.type_offset)?;
+            let member_bytes = bytes.get(member.offset..member.offset + member_type.size)?;
+            find_pointer(debug_data, member_type, member_bytes)
+        }),
+        _ => None,
+    }
+}
+
+/// Formats len UTF-8 bytes at addr as a quoted string, truncating long strings.
+fn format_rust_string(inferior: &dyn Target, addr: usize, len: usize) -> String {
+    match inferior.read_bytes(addr, len.min(MAX_STRING_LEN)) {
+        Ok(bytes) => format!(
+            "\"{}\"{}",
+            String::from_utf8_lossy(&bytes).escape_default(),
+            if len > MAX_STRING_LEN { "..." } else { "" }
+        ),
+        Err(_) => format!("{:#x} <error: Cannot access memory>", addr),
+    }
+}
+
+/// Formats len elements of the given type at addr as [a, b, c].
+fn format_elements(
+    inferior: &dyn Target,
+    debug_data: &DwarfData,
+    element_type: &Type,
+    addr: usize,
+    len: usize,
+) -> String {
+    let count = len.min(MAX_ARRAY_ELEMENTS);
+    if element_type.size == 0 || count == 0 {
+        return "[]".to_string();
+    }
+    let bytes = match inferior.read_bytes(addr, count * element_type.size) {
+        Ok(bytes) => bytes,
+        Err(_) => return format!("{:#x} <error: Cannot access memory>", addr),
+    };
+    let mut elements: Vec<String> = bytes
+        .chunks(element_type.size)
+        .map(|chunk| format_value(inferior, debug_data, element_type, chunk))
+        .collect();
+    if len > MAX_ARRAY_ELEMENTS {
+        elements.push("...".to_string());
+    }
+    format!("[{}]", elements.join(", "))
+}
+
+/// Returns true if the type at the given offset is a (possibly qualified) char.
+fn is_char_type(debug_data: &DwarfData, offset: Option<usize>) -> bool {
+    match offset.and_then(|offset| debug_data.get_type(offset)) {
+        Some(char_type) => matches!(
+            debug_data.strip_typedefs(char_type).kind,
+            TypeKind::Base(BaseEncoding::SignedChar) | TypeKind::Base(BaseEncoding::UnsignedChar)
+        ),
+        None => false,
+    }
+}
+
+fn is_function_type(debug_data: &DwarfData, offset: Option<usize>) -> bool {
+    match offset.and_then(|offset| debug_data.get_type(offset)) {
+        Some(function_type) => matches!(
+            debug_data.strip_typedefs(function_type).kind,
+            TypeKind::Function(_)
+        ),
+        None => false,
+    }
+}
+
+/// Formats an array whose outermost dimension is dimensions[0]. char arrays are printed as
+/// strings.
+fn format_array(
+    inferior: &dyn Target,
+    debug_data: &DwarfData,
+    element_type: &Type,
+    dimensions: &[usize],
+    bytes: &[u8],
+) -> String {
+    if dimensions.is_empty() {
+        return format_value(inferior, debug_data, element_type, bytes);
+    }
+    let stride = element_type.size * dimensions[1..].iter().product::<usize>();
+    if dimensions.len() == 1
+        && matches!(
+            debug_data.strip_typedefs(element_type).kind,
+            TypeKind::Base(BaseEncoding::SignedChar) | TypeKind::Base(BaseEncoding::UnsignedChar)
+        )
+    {
+        let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        return format!("\"{}\"", String::from_utf8_lossy(&bytes[..end]).escape_default());
+    }
+    if stride == 0 {
+        return "{}".to_string();
+    }
+    let mut elements: Vec<String> = bytes
+        .chunks(stride)
+        .take(dimensions[0].min(MAX_ARRAY_ELEMENTS))
+        .map(|chunk| format_array(inferior, debug_data, element_type, &dimensions[1..], chunk))
+        .collect();
+    if dimensions[0] > MAX_ARRAY_ELEMENTS {
+        elements.push("...".to_string());
+    }
+    format!("{{{}}}", elements.join(", "))
+}
+
+fn format_raw(bytes: &[u8]) -> String {
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("<{}>", hex.join(" "))
+}
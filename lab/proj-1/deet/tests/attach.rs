@@ -0,0 +1,36 @@
+mod common;
+
+use common::{assert_contains, assert_lines, deet, sample};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn attaches_to_and_detaches_from_a_running_process() {
+    let program = sample("sleepy_print");
+    let mut sleeper = Command::new(&program)
+        .arg("30")
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("Could not start sleepy_print");
+    // give it time to get into its loop
+    thread::sleep(Duration::from_millis(500));
+    let pid = sleeper.id().to_string();
+    let output = deet(&["--pid", &pid], &["backtrace", "frame 3", "print num_seconds", "detach"]);
+    let still_running = sleeper.try_wait().unwrap().is_none();
+    let _ = sleeper.kill();
+    let _ = sleeper.wait();
+
+    assert_lines(
+        &output,
+        &[
+            &format!("Attached to process {}", pid),
+            "Child stopped (signal SIGSTOP)",
+            "=> 13           sleep(1);",
+            "$1 = 30",
+            &format!("Detached from process {}", pid),
+        ],
+    );
+    assert_contains(&output, " in main (argc=2, argv=");
+    assert!(still_running, "sleepy_print didn't survive being detached from");
+}
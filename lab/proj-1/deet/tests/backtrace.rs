@@ -0,0 +1,44 @@
+mod common;
+
+use common::{assert_lines, deet, sample, symbol_address};
+
+/// Returns the function and source location of each frame deet listed with a source location,
+/// leaving out return addresses and directories since those depend on how the sample was built.
+fn frames(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter(|line| line.starts_with('#'))
+        .filter_map(|line| {
+            let (number, rest) = line.split_once("  ")?;
+            let rest = rest.split_once(" in ").map_or(rest, |(_, function)| function);
+            let (function, location) = rest.split_once(" at ")?;
+            let file = location.rsplit('/').next()?;
+            Some(format!("{} {} {}", number, function, file))
+        })
+        .collect()
+}
+
+#[test]
+fn unwinds_from_the_first_instruction_of_a_function() {
+    let program = sample("function_calls");
+    // func3 hasn't pushed %rbp yet, so following the frame pointer chain would skip func2
+    let func3 = symbol_address(&program, "func3");
+    let output = deet(
+        &[&program],
+        &[&format!("break *{:#x}", func3), "run", "backtrace", "up", "print a", "frame 2"],
+    );
+    let frames = frames(&output);
+    assert_eq!(
+        frames[1..4],
+        [
+            "#1 func2 (a=42, b=5) function_calls.c:13",
+            "#2 func1 (a=42) function_calls.c:18",
+            "#3 main () function_calls.c:24",
+        ],
+        "{}",
+        output
+    );
+    // up and frame 2 select and show the callers
+    assert_eq!(frames[4..], frames[1..3], "{}", output);
+    assert_lines(&output, &["=> 13       func3(100);", "$1 = 42", "=> 18       func2(a, global);"]);
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::{assert_lines, deet, sample};
+
+#[test]
+fn ignores_disables_and_counts_hits() {
+    let program = sample("function_calls");
+    let output = deet(
+        &[&program],
+        &["break func3", "break func2", "ignore 1 1", "disable 2", "run", "info breakpoints"],
+    );
+    // func2 is disabled and the first call to func3 is ignored, so only the second one stops
+    assert_lines(
+        &output,
+        &[
+            "Will ignore next 1 crossings of breakpoint 1.",
+            "Breakpoint 1",
+            "Num     Type           Enb Address            What",
+            "\tbreakpoint already hit 2 times",
+        ],
+    );
+    assert!(!output.lines().any(|line| line == "Breakpoint 2"));
+    assert!(output.lines().any(|line| line.starts_with("2       breakpoint     n   ")));
+}
+
+#[test]
+fn applies_conditions_and_deletes() {
+    let program = sample("function_calls");
+    let output = deet(
+        &[&program],
+        &[
+            "break func3",
+            "condition 1 a == 7",
+            "run",
+            "condition 1",
+            "break func1",
+            "delete 1",
+            "run",
+            "info breakpoints",
+            "break nosuch",
+        ],
+    );
+    assert_lines(
+        &output,
+        &[
+            "Child exited (status 0)",
+            "Breakpoint 1 now unconditional.",
+            "Breakpoint 2",
+            "=> 17       printf(\"func1(%d) was called\\n\", a);",
+            "\tbreakpoint already hit 1 time",
+            "Function \"nosuch\" not defined.",
+        ],
+    );
+    assert!(!output.lines().any(|line| line.starts_with("1       breakpoint")));
+}
@@ -0,0 +1,113 @@
+//! Helpers for the end-to-end tests, which run deet on the programs in samples/.
+// each test file only uses some of them
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a deet session may take before the test gives up on it.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Builds a sample program and returns its path.
+pub fn sample(name: &str) -> String {
+    // tests run in parallel, and two of them mustn't build the same program at once
+    static BUILDING: Mutex<()> = Mutex::new(());
+    let _building = BUILDING.lock().unwrap_or_else(|err| err.into_inner());
+    let path = format!("samples/{}", name);
+    let status = Command::new("make")
+        .args(["-s", "-C", env!("CARGO_MANIFEST_DIR"), &path])
+        .status()
+        .expect("Could not run make");
+    assert!(status.success());
+    format!("{}/{}", env!("CARGO_MANIFEST_DIR"), path)
+}
+
+/// Returns the address of a function in program, as nm lists it.
+pub fn symbol_address(program: &str, name: &str) -> usize {
+    let output = Command::new("nm").arg(program).output().expect("Could not run nm");
+    let symbols = String::from_utf8_lossy(&output.stdout).to_string();
+    symbols
+        .lines()
+        .find_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [addr, "T", symbol] if *symbol == name => usize::from_str_radix(addr, 16).ok(),
+                _ => None,
+            }
+        })
+        .expect("Symbol not found")
+}
+
+/// Returns a new empty directory for a deet session to use as its home and working directory,
+/// so that sessions don't share history or .deetinit files.
+pub fn home_dir() -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let name = format!("home-{}-{}", std::process::id(), COUNT.fetch_add(1, Ordering::SeqCst));
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs deet with args in a fresh home directory, typing commands at its prompt, and returns
+/// everything it and the program printed.
+pub fn deet(args: &[&str], commands: &[&str]) -> String {
+    deet_in(&home_dir(), args, commands)
+}
+
+/// Runs deet with args in the given home directory, typing commands at its prompt, and
+/// returns everything it and the program printed.
+pub fn deet_in(home: &Path, args: &[&str], commands: &[&str]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_deet"))
+        .args(args)
+        .env("HOME", home)
+        .current_dir(home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Could not start deet");
+    let mut input = commands.join("\n");
+    input.push('\n');
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    let reader = thread::spawn(move || {
+        let mut output = String::new();
+        let _ = stdout.read_to_string(&mut output);
+        let _ = stderr.read_to_string(&mut output);
+        output
+    });
+    let started = Instant::now();
+    while child.try_wait().unwrap().is_none() {
+        if started.elapsed() > TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            panic!("deet didn't finish: {}", reader.join().unwrap());
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    reader.join().unwrap()
+}
+
+/// Checks that output has each of the lines in expected, in that order, allowing other lines
+/// in between.
+pub fn assert_lines(output: &str, expected: &[&str]) {
+    let mut lines = output.lines();
+    for line in expected {
+        if !lines.any(|output_line| output_line.trim_end() == *line) {
+            panic!("Expected the line {:?}, in order, in:\n{}", line, output);
+        }
+    }
+}
+
+/// Checks that output contains text.
+pub fn assert_contains(output: &str, text: &str) {
+    assert!(output.contains(text), "Expected {:?} in:\n{}", text, output);
+}
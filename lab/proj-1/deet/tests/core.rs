@@ -0,0 +1,31 @@
+mod common;
+
+use common::{assert_contains, assert_lines, deet_in, home_dir, sample};
+
+#[test]
+fn inspects_a_core_written_by_gcore() {
+    let program = sample("segfault");
+    let home = home_dir();
+    let output = deet_in(&home, &[&program], &["run", "gcore segfault.core"]);
+    assert_lines(&output, &["Child stopped (signal SIGSEGV)", "Saved corefile segfault.core"]);
+    assert!(home.join("segfault.core").exists());
+
+    let output = deet_in(
+        &home,
+        &[&program, "--core", "segfault.core"],
+        &["print a", "backtrace", "up", "print a", "info registers rip"],
+    );
+    assert_lines(
+        &output,
+        &[
+            "Program terminated with signal SIGSEGV.",
+            "=> 5        *(int*)0 = a;",
+            "$1 = 2",
+            "=> 11       func2(a % 5);",
+            "$2 = 42",
+        ],
+    );
+    assert_contains(&output, " in func1 (a=42) at ");
+    assert_contains(&output, " in main () at ");
+    assert!(output.lines().any(|line| line.starts_with("rip ") && line.contains(" <func2+")));
+}
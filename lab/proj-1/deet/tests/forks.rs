@@ -0,0 +1,65 @@
+mod common;
+
+use common::{assert_contains, assert_lines, deet, sample};
+
+#[test]
+fn follows_the_child_through_fork_and_exec() {
+    let program = sample("forks");
+    let output = deet(
+        &[&program],
+        &[
+            "set follow-fork-mode child",
+            "break in_child",
+            "break in_exec",
+            "run",
+            "print n",
+            "continue",
+            "print argc",
+            "continue",
+        ],
+    );
+    assert_lines(
+        &output,
+        &[
+            "Breakpoint 1",
+            "=> 6        printf(\"child %d\\n\", n);",
+            "$1 = 7",
+            "Breakpoint 2",
+            "=> 10       printf(\"exec'd with %d arguments\\n\", argc);",
+            "$2 = 2",
+            "Child exited (status 0)",
+        ],
+    );
+    assert_contains(&output, " fork to child process ");
+    assert_contains(&output, " is executing new program: ");
+    let reread = |line: &str| line.starts_with("Reading symbols from ") && line.ends_with("/forks");
+    assert!(output.lines().any(reread), "{}", output);
+}
+
+#[test]
+fn keeps_both_processes_when_not_detaching() {
+    let program = sample("forks");
+    let output = deet(
+        &[&program],
+        &[
+            "set detach-on-fork off",
+            "break in_child",
+            "break 19",
+            "run",
+            "inferiors",
+            "inferior 2",
+            "continue",
+            "continue",
+            "inferiors",
+        ],
+    );
+    assert_lines(&output, &["Breakpoint 2", "=> 19       if (pid == 0) {"]);
+    let listed: Vec<&str> = output.lines().filter(|line| line.contains(" process ")).collect();
+    assert_eq!(listed.len(), 4, "{}", output);
+    assert!(listed[0].starts_with("* 1    process "), "{}", output);
+    assert!(listed[1].starts_with("  2    process "), "{}", output);
+    assert!(listed[3].starts_with("* 2    process "), "{}", output);
+    assert_contains(&output, "[Switching to inferior 2 [process ");
+    // the child then runs on by itself, into in_child
+    assert_lines(&output, &["Breakpoint 1", "=> 6        printf(\"child %d\\n\", n);"]);
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::{sample, symbol_address};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Sends a packet and returns the data of the reply, after checking that the packet was
+/// acknowledged and the reply's checksum.
+fn request(stream: &mut TcpStream, packet: &str) -> String {
+    let checksum = packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+    write!(stream, "${}#{:02x}", packet, checksum).unwrap();
+    let mut ack = [0u8];
+    stream.read_exact(&mut ack).unwrap();
+    assert_eq!(ack[0], b'+');
+    let mut reply = Vec::new();
+    let mut byte = [0u8];
+    stream.read_exact(&mut byte).unwrap();
+    assert_eq!(byte[0], b'$');
+    loop {
+        stream.read_exact(&mut byte).unwrap();
+        if byte[0] == b'#' {
+            break;
+        }
+        reply.push(byte[0]);
+    }
+    let mut sent = [0u8; 2];
+    stream.read_exact(&mut sent).unwrap();
+    let expected = reply.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    assert_eq!(std::str::from_utf8(&sent).unwrap(), format!("{:02x}", expected));
+    stream.write_all(b"+").unwrap();
+    String::from_utf8(reply).unwrap()
+}
+
+#[test]
+fn serves_a_remote_debugger() {
+    let program = sample("function_calls");
+    let func2 = symbol_address(&program, "func2");
+    let mut server = Command::new(env!("CARGO_BIN_EXE_deet"))
+        .args(["--gdbserver", ":0", &program])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Could not start deet");
+    let mut lines = BufReader::new(server.stdout.take().unwrap()).lines();
+    let port: u16 = lines
+        .by_ref()
+        .map_while(Result::ok)
+        .find_map(|line| line.strip_prefix("Listening on port ").map(|port| port.to_string()))
+        .expect("deet didn't say which port it listens on")
+        .parse()
+        .unwrap();
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+
+    let supported = request(&mut stream, "qSupported:multiprocess+;swbreak+");
+    assert!(supported.contains("PacketSize="));
+    assert!(supported.contains("swbreak+"));
+    assert_eq!(request(&mut stream, "?"), "S05");
+    // malformed packets get an error rather than bringing the server down
+    assert_eq!(request(&mut stream, "H"), "E01");
+
+    // 24 registers of 8 or 4 bytes, then 8 x87 registers of 10 bytes and 8 of 4
+    let registers = request(&mut stream, "g");
+    assert_eq!(registers.len(), (17 * 8 + 7 * 4 + 8 * 10 + 8 * 4) * 2);
+
+    // func2 starts with push %rbp; mov %rsp,%rbp
+    let code = request(&mut stream, &format!("m{:x},4", func2));
+    assert_eq!(&code[..8], "554889e5");
+    assert_eq!(request(&mut stream, &format!("Z0,{:x},1", func2)), "OK");
+    // the breakpoint is hidden from memory reads
+    assert_eq!(request(&mut stream, &format!("m{:x},4", func2)), code);
+    assert_eq!(request(&mut stream, "mffffffffffffffff,10"), "E01");
+
+    let stop = request(&mut stream, "c");
+    assert!(stop.starts_with("T05"), "unexpected stop {}", stop);
+    assert!(stop.contains("swbreak:"), "unexpected stop {}", stop);
+    let registers = request(&mut stream, "g");
+    // rip is the 17th register, in little-endian order
+    let rip = u64::from_str_radix(&registers[16 * 16..17 * 16], 16).unwrap().swap_bytes();
+    assert_eq!(rip as usize, func2);
+
+    write!(stream, "$k#6b").unwrap();
+    let status = server.wait().unwrap();
+    assert!(status.success());
+    let output: Vec<String> = lines.map_while(Result::ok).collect();
+    assert!(output.iter().any(|line| line == "Remote side has terminated connection."));
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::{assert_lines, deet, deet_in, home_dir, sample};
+use std::fs;
+
+#[test]
+fn lists_source_around_functions_and_lines() {
+    let program = sample("function_calls");
+    let output = deet(&[&program], &["list func2", "list nosuch", "list function_calls.c:20"]);
+    assert_lines(
+        &output,
+        &[
+            "4",
+            "5\tvoid func3(int a) {",
+            "9\tvoid func2(int a, int b) {",
+            "13\t    func3(100);",
+            "Function \"nosuch\" not defined.",
+            "15",
+            "20\t    printf(\"end of func1\\n\");",
+            "24\t    func1(42);",
+        ],
+    );
+}
+
+#[test]
+fn shows_the_source_around_each_stop() {
+    let program = sample("function_calls");
+    let output = deet(&[&program], &["break func3", "run"]);
+    assert_lines(
+        &output,
+        &[
+            "Breakpoint 1",
+            "   4",
+            "   5    void func3(int a) {",
+            "=> 6        printf(\"Hello from func3! %d\\n\", a);",
+            "   7    }",
+            "   8",
+        ],
+    );
+}
+
+#[test]
+fn finds_sources_in_other_directories() {
+    let program = sample("function_calls");
+    let home = home_dir();
+    let source = fs::read_to_string(format!("{}.c", program)).unwrap();
+    fs::write(home.join("function_calls.c"), source.replace("func3(int a)", "func3(int moved)"))
+        .unwrap();
+    let directory = format!("directory {}", home.display());
+    let output = deet_in(&home, &[&program], &[&directory, "list func3"]);
+    assert_lines(
+        &output,
+        &[
+            &format!("Source directories searched: {}:$cdir", home.display()),
+            "5\tvoid func3(int moved) {",
+        ],
+    );
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use common::{assert_lines, deet, sample};
+
+#[test]
+fn breaks_at_file_lines_and_functions() {
+    let program = sample("function_calls");
+    let output = deet(
+        &[&program],
+        &[
+            "break function_calls.c:11",
+            "break function_calls.c:func3",
+            "break nosuch.c:3",
+            "break function_calls.c:999",
+            "run",
+            "continue",
+        ],
+    );
+    assert_lines(
+        &output,
+        &[
+            "No source file named nosuch.c.",
+            "No line 999 in file \"samples/function_calls.c\".",
+            "Breakpoint 1",
+            "=> 11       int sum = a + b;",
+            "Breakpoint 2",
+            "=> 6        printf(\"Hello from func3! %d\\n\", a);",
+        ],
+    );
+}
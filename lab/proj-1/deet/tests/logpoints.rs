@@ -0,0 +1,53 @@
+mod common;
+
+use common::{assert_lines, deet_in, home_dir, sample};
+use std::fs;
+
+/// Returns the messages in logpoint output, checking that each has a timestamp.
+fn messages(log: &str) -> Vec<&str> {
+    log.lines()
+        .filter_map(|line| line.strip_prefix('['))
+        .filter_map(|line| line.split_once("] "))
+        .map(|(time, message)| {
+            assert!(time.parse::<f64>().is_ok(), "bad timestamp in {:?}", message);
+            message
+        })
+        .collect()
+}
+
+#[test]
+fn logs_without_stopping() {
+    let program = sample("function_calls");
+    let home = home_dir();
+    let output = deet_in(
+        &home,
+        &[&program],
+        &[
+            "logpoint func3 \"func3 got {a}, global is {global}\"",
+            "logpoint function_calls.c:11 \"a+b={a + b} {nosuch}\"",
+            "run",
+            "set logpoint-file log.txt",
+            "run",
+        ],
+    );
+    let expected = [
+        "a+b=47 <No symbol \"nosuch\" in current context.>",
+        "func3 got 100, global is 5",
+        "func3 got 100, global is 5",
+    ];
+    // the second run logs to the file, and neither stops at the logpoints
+    assert_eq!(messages(&output), expected, "{}", output);
+    let log = fs::read_to_string(home.join("log.txt")).unwrap();
+    assert_eq!(messages(&log), expected, "{}", log);
+    assert!(!output.contains("Child stopped"), "{}", output);
+    let summary = [
+        "Child exited (status 0)",
+        "Logpoint hits:",
+        "  1 at func3: 2 times",
+        "  2 at function_calls.c:11: 1 time",
+    ];
+    assert_lines(
+        &output,
+        &[&summary[..], &["Logpoint messages will be written to log.txt."], &summary[..]].concat(),
+    );
+}
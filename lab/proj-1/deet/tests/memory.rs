@@ -0,0 +1,38 @@
+mod common;
+
+use common::{assert_contains, assert_lines, deet, sample};
+
+#[test]
+fn examines_memory() {
+    let program = sample("structs");
+    let output = deet(&[&program], &["break 21", "run", "x/s buf", "x/6xw grid", "x/2dw &e.pos"]);
+    assert_contains(&output, ":\t\"hi\"\n");
+    assert_contains(&output, " <grid>:\t0x00000001\t0x00000002\t0x00000003\t0x00000004\n");
+    assert_contains(&output, " <grid+16>:\t0x00000005\t0x00000006\n");
+    assert_contains(&output, ":\t3\t4\n");
+}
+
+#[test]
+fn reads_and_writes_registers_and_memory() {
+    let program = sample("function_calls");
+    let output = deet(
+        &[&program],
+        &[
+            "break func2",
+            "run",
+            "x/2i $rip",
+            "info registers rip",
+            "info registers eflags",
+            "set $rax = 0x1234",
+            "print $rax",
+            "set *&global = 77",
+            "print global",
+            "x/3cb &global",
+        ],
+    );
+    assert_lines(&output, &["$1 = 4660", "$2 = 77"]);
+    assert_contains(&output, "=> 0x40116b <func2+14>:\tmov    -0x18(%rbp),%edx\n");
+    assert_contains(&output, "rip            0x40116b           0x40116b <func2+14>\n");
+    assert!(output.lines().any(|line| line.starts_with("eflags ") && line.contains(" IF ]")));
+    assert_contains(&output, " <global>:\t77 'M'\t0 '\\0'\t0 '\\0'\n");
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::{assert_contains, assert_lines, deet, sample};
+
+#[test]
+fn relocates_position_independent_executables() {
+    let program = sample("pie_function_calls");
+    let output = deet(&[&program], &["break func3", "run", "backtrace", "print global"]);
+    assert_lines(
+        &output,
+        &["Breakpoint 1", "=> 6        printf(\"Hello from func3! %d\\n\", a);", "$1 = 5"],
+    );
+    assert_contains(&output, "#0  func3 (a=100) at ");
+    // the callers are found at their relocated addresses, well above the link-time ones
+    let callers: Vec<&str> = output.lines().filter(|line| line.contains(" in func")).collect();
+    assert_eq!(callers.len(), 2, "{}", output);
+    assert!(callers.iter().all(|line| !line[4..].starts_with("0x00000000")), "{}", output);
+}
+
+#[test]
+fn breaks_in_shared_libraries() {
+    let program = sample("pie_function_calls");
+    let output = deet(
+        &[&program],
+        &[
+            "break func3",
+            "run",
+            "info sharedlibrary",
+            "break puts",
+            "delete 1",
+            "continue",
+            "backtrace",
+        ],
+    );
+    assert_lines(&output, &["Breakpoint 2", "#0  puts ()"]);
+    assert!(output.lines().any(|line| line.ends_with("/libc.so.6")), "{}", output);
+    assert_contains(&output, " in func1 (a=42) at ");
+}
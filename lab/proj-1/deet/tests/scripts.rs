@@ -0,0 +1,50 @@
+mod common;
+
+use common::{assert_lines, deet_in, home_dir, sample};
+use std::fs;
+
+#[test]
+fn runs_init_files_scripts_and_breakpoint_commands() {
+    let program = sample("function_calls");
+    let home = home_dir();
+    fs::write(home.join(".deetinit"), "break func2\n").unwrap();
+    fs::write(home.join("script.deet"), "commands 1\nprint a\ncontinue\nend\nrun\n").unwrap();
+    let output = deet_in(&home, &["-x", "script.deet", &program], &["print 7"]);
+    // .deetinit sets the breakpoint, then the script's commands for it carry the program on to
+    // the end before the prompt gets a turn
+    assert_lines(&output, &["Breakpoint 1", "$1 = 42", "Child exited (status 0)", "$2 = 7"]);
+}
+
+#[test]
+fn sources_files_and_runs_user_defined_commands() {
+    let program = sample("function_calls");
+    let home = home_dir();
+    fs::write(home.join("show.deet"), "define show\nprint $arg0 + $arg1\nprint $argc\nend\n")
+        .unwrap();
+    let output = deet_in(
+        &home,
+        &[&program],
+        &[
+            "source show.deet",
+            "show 2 3",
+            "show 1",
+            "define last",
+            "print $arg10",
+            "end",
+            "last 0 1 2 3 4 5 6 7 8 9 10",
+            "last 0",
+            "source nosuch.deet",
+        ],
+    );
+    assert_lines(
+        &output,
+        &[
+            "$1 = 5",
+            "$2 = 2",
+            "Missing argument 1 in user function.",
+            "$3 = 10",
+            "Missing argument 10 in user function.",
+            "nosuch.deet: No such file or directory (os error 2).",
+        ],
+    );
+}
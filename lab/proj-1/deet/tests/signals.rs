@@ -0,0 +1,43 @@
+mod common;
+
+use common::{assert_lines, deet, sample};
+
+#[test]
+fn stops_on_signals_by_default() {
+    let program = sample("segfault");
+    let output = deet(&[&program], &["run", "continue"]);
+    assert_lines(
+        &output,
+        &[
+            "Child stopped (signal SIGSEGV)",
+            "=> 5        *(int*)0 = a;",
+            "Child exited (signal SIGSEGV)",
+        ],
+    );
+}
+
+#[test]
+fn passes_signals_without_stopping() {
+    let program = sample("segfault");
+    let output = deet(&[&program], &["handle SIGSEGV nostop print", "run", "info signals SIGSEGV"]);
+    assert_lines(
+        &output,
+        &[
+            "SIGSEGV       No\tYes\tYes\t\tSegmentation fault",
+            "Program received signal SIGSEGV, Segmentation fault.",
+            "Child exited (signal SIGSEGV)",
+            "SIGSEGV       No\tYes\tYes\t\tSegmentation fault",
+        ],
+    );
+    assert!(!output.contains("Child stopped (signal SIGSEGV)"));
+}
+
+#[test]
+fn delivers_signals_on_request() {
+    let program = sample("sleepy_print");
+    let output = deet(&[&program], &["break main", "run 3", "signal SIGTERM"]);
+    assert_lines(
+        &output,
+        &["Breakpoint 1", "Continuing with signal SIGTERM.", "Child exited (signal SIGTERM)"],
+    );
+}
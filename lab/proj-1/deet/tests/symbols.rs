@@ -0,0 +1,94 @@
+mod common;
+
+use common::{assert_lines, deet, deet_in, home_dir, sample};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Runs objcopy with args in dir.
+fn objcopy(dir: &Path, args: &[&str]) {
+    let status = Command::new("objcopy")
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .expect("Could not run objcopy");
+    assert!(status.success());
+}
+
+/// Returns the build ID of program in hex, as readelf shows it.
+fn build_id(program: &str) -> String {
+    let output =
+        Command::new("readelf").args(["-n", program]).output().expect("Could not run readelf");
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Build ID: ").map(|id| id.to_string()))
+        .expect("No build ID")
+}
+
+#[test]
+fn falls_back_to_the_symbol_table() {
+    let program = sample("nodebug_function_calls");
+    let output = deet(&[&program], &["break func3", "run", "backtrace", "disassemble", "print a"]);
+    assert_lines(
+        &output,
+        &[
+            &format!("(No debugging symbols found in {})", program),
+            "Line numbers and variables are unavailable.",
+            "Breakpoint 1",
+            "#0  func3 ()",
+            "Dump of assembler code for function func3:",
+            "No symbol \"a\" in current context.",
+        ],
+    );
+    let callers: Vec<&str> = output.lines().filter(|line| line.contains(" in func")).collect();
+    assert_eq!(callers.len(), 2, "{}", output);
+    assert!(callers[0].ends_with(" in func2 ()"), "{}", output);
+    assert!(callers[1].ends_with(" in func1 ()"), "{}", output);
+}
+
+#[test]
+fn reads_debug_files_named_by_debug_links() {
+    let program = sample("function_calls");
+    let home = home_dir();
+    objcopy(&home, &["--only-keep-debug", &program, "function_calls.debug"]);
+    objcopy(
+        &home,
+        &["--strip-debug", "--add-gnu-debuglink=function_calls.debug", &program, "stripped"],
+    );
+    let output = deet_in(&home, &["./stripped"], &["break func2", "run", "print a"]);
+    assert!(!output.contains("No debugging symbols"), "{}", output);
+    assert_lines(
+        &output,
+        &["Breakpoint 1", "=> 10       printf(\"func2(%d, %d) was called\\n\", a, b);", "$1 = 42"],
+    );
+}
+
+#[test]
+fn reads_debug_files_by_build_id() {
+    let program = sample("function_calls");
+    let home = home_dir();
+    let id = build_id(&program);
+    let dir = home.join("debug/.build-id").join(&id[..2]);
+    fs::create_dir_all(&dir).unwrap();
+    objcopy(&dir, &["--only-keep-debug", &program, &format!("{}.debug", &id[2..])]);
+    objcopy(&home, &["--strip-debug", &program, "stripped"]);
+    let output = deet_in(
+        &home,
+        &["./stripped"],
+        &[
+            &format!("set debug-file-directory {}/debug", home.display()),
+            "break func2",
+            "run",
+            "print a",
+        ],
+    );
+    assert_lines(
+        &output,
+        &[
+            "(No debugging symbols found in ./stripped)",
+            "Reading symbols from ./stripped",
+            "Breakpoint 1",
+            "$1 = 42",
+        ],
+    );
+}
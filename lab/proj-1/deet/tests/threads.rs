@@ -0,0 +1,37 @@
+mod common;
+
+use common::{assert_contains, assert_lines, deet, sample};
+
+#[test]
+fn stops_each_thread_at_a_shared_breakpoint() {
+    let program = sample("threads");
+    let output = deet(
+        &[&program],
+        &[
+            "break 12",
+            "run",
+            "info threads",
+            "print id",
+            "continue",
+            "print id",
+            "thread 1",
+            "continue",
+        ],
+    );
+    // the threads may reach the breakpoint in either order, but each must stop there once
+    let stops = output.lines().filter(|line| *line == "Breakpoint 1").count();
+    assert_eq!(stops, 2, "{}", output);
+    let mut ids: Vec<&str> = output
+        .lines()
+        .filter_map(|line| line.strip_prefix("$1 = ").or_else(|| line.strip_prefix("$2 = ")))
+        .collect();
+    ids.sort_unstable();
+    assert_eq!(ids, ["1", "2"], "{}", output);
+
+    let threads: Vec<&str> = output.lines().filter(|line| line.contains("    LWP ")).collect();
+    assert_eq!(threads.len(), 3, "{}", output);
+    assert!(threads[0].starts_with("  1    LWP "), "{}", output);
+    assert!(threads.iter().any(|line| line.starts_with("* ") && line.contains(" work (arg=")));
+    assert_contains(&output, "[Switching to thread 1 (LWP ");
+    assert_lines(&output, &["total = 3", "Child exited (status 0)"]);
+}
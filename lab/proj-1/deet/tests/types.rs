@@ -0,0 +1,44 @@
+mod common;
+
+use common::{assert_contains, assert_lines, deet, sample};
+
+#[test]
+fn prints_structs_arrays_enums_and_pointers() {
+    let program = sample("structs");
+    let output = deet(
+        &[&program],
+        &[
+            "break 21",
+            "run",
+            "print head->next->value",
+            "print *n1.next",
+            "print e",
+            "print ep->pos",
+            "print e.c",
+            "print grid",
+            "print buf",
+            "print big",
+            "print u.i",
+            "print fp",
+            "print *head",
+        ],
+    );
+    assert_lines(
+        &output,
+        &[
+            "$1 = 2",
+            "$2 = {value = 2, next = (struct node *) 0x0}",
+            "$3 = {name = 0x402004 \"player\", pos = {x = 3, y = 4}, c = GREEN, \
+             scores = {10, 20, 30}}",
+            "$4 = {x = 3, y = 4}",
+            "$5 = GREEN",
+            "$6 = {{1, 2, 3}, {4, 5, 6}}",
+            "$7 = \"hi\"",
+            "$8 = 99",
+            "$9 = 1",
+            "$10 = (void (*)()) 0x0",
+        ],
+    );
+    // the stack address differs from run to run
+    assert_contains(&output, "= {value = 1, next = (struct node *) 0x7ff");
+}
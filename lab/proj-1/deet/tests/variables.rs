@@ -0,0 +1,41 @@
+mod common;
+
+use common::{assert_lines, deet, sample};
+
+#[test]
+fn prints_locals_and_globals() {
+    let program = sample("function_calls");
+    let output = deet(
+        &[&program],
+        &[
+            "break func2",
+            "run",
+            "print a",
+            "print b",
+            "print global",
+            "next",
+            "next",
+            "info locals",
+            "print nosuch",
+        ],
+    );
+    assert_lines(
+        &output,
+        &[
+            "$1 = 42",
+            "$2 = 5",
+            "$3 = 5",
+            "a = 42",
+            "b = 5",
+            "sum = 47",
+            "No symbol \"nosuch\" in current context.",
+        ],
+    );
+}
+
+#[test]
+fn prints_global_arrays() {
+    let program = sample("structs");
+    let output = deet(&[&program], &["break main", "run", "print counter", "print grid[1][2]"]);
+    assert_lines(&output, &["$1 = 3", "$2 = 6"]);
+}
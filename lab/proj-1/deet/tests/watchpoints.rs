@@ -0,0 +1,33 @@
+mod common;
+
+use common::{assert_lines, deet, sample};
+
+#[test]
+fn stops_when_a_watched_variable_changes() {
+    let program = sample("function_calls");
+    let output = deet(&[&program], &["break func2", "run", "watch sum", "continue"]);
+    assert_lines(
+        &output,
+        &[
+            "Hardware watchpoint 2: sum",
+            "Hardware watchpoint 2: sum",
+            "New value = 47",
+            "=> 12       printf(\"sum = %d\\n\", sum);",
+        ],
+    );
+}
+
+#[test]
+fn stops_when_a_watched_variable_is_read() {
+    let program = sample("function_calls");
+    let output = deet(&[&program], &["break main", "run", "awatch global", "continue", "continue"]);
+    assert_lines(
+        &output,
+        &[
+            "Hardware access (read/write) watchpoint 2: global",
+            "Value = 5",
+            "=> 18       func2(a, global);",
+            "Child exited (status 0)",
+        ],
+    );
+}